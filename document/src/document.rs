@@ -1,11 +1,14 @@
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    io::{Read, Seek, Write},
-    path::PathBuf,
+use crate::{
+    default_store,
+    format::{deserialize_payload, encode_payloads},
+    revision::unix_now,
+    Causality, DocumentStoreRef, RetentionPolicy, Revision, VersionVector, CURRENT_FORMAT,
 };
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashSet, fmt::Debug, path::PathBuf, time::Duration};
 use utility::{generate_id, LocalLedgerError};
+use zeroize::Zeroizing;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Document<T> {
@@ -14,10 +17,39 @@ pub struct Document<T> {
     rev: String,
     data: T,
     seq: i64,
+    #[serde(deserialize_with = "deserialize_payload")]
     encrypted_data: Vec<u8>,
     encrypted: bool,
     has_been_decrypted: bool,
     rev_history: Vec<String>,
+    #[serde(default)]
+    revisions: Vec<Revision>,
+    #[serde(default)]
+    version: VersionVector,
+    /// On-disk format the Document is written in.  Docs that predate format versions are read
+    /// as `LEGACY_FORMAT`.
+    #[serde(default)]
+    format: u32,
+    /// Schema version of the payload.  Zero for docs written before schema versions were
+    /// tracked.
+    #[serde(default)]
+    schema_version: u32,
+    /// Unix timestamp (seconds) of when the Document was deleted.  Deleted Documents are kept
+    /// as tombstones, without any data, so the deletion can be synced to other devices.
+    #[serde(default)]
+    deleted_at: Option<u64>,
+    /// Unix timestamp (seconds) of when the Document was moved to the trash.  Trashed Documents
+    /// keep their data so they can be restored, but are not considered live.
+    #[serde(default)]
+    trashed_at: Option<u64>,
+    /// Version of the scheme the owner of the Document used to derive its uuid.  Lets the owner
+    /// find and migrate uuids derived with an older scheme.
+    #[serde(default)]
+    name_scheme: u32,
+    #[serde(skip, default = "default_store")]
+    store: DocumentStoreRef,
+    #[serde(skip)]
+    retention: RetentionPolicy,
 }
 
 const TEMP_SUFFIX: &str = "TEMP";
@@ -39,6 +71,15 @@ where
             encrypted: false,
             has_been_decrypted: false,
             rev_history: vec![],
+            revisions: vec![],
+            version: Default::default(),
+            format: CURRENT_FORMAT,
+            schema_version: 0,
+            deleted_at: None,
+            trashed_at: None,
+            name_scheme: 0,
+            store: default_store(),
+            retention: Default::default(),
         }
     }
 
@@ -58,24 +99,51 @@ where
             encrypted: false,
             has_been_decrypted: false,
             rev_history: vec![],
+            revisions: vec![],
+            version: Default::default(),
+            format: CURRENT_FORMAT,
+            schema_version: 0,
+            deleted_at: None,
+            trashed_at: None,
+            name_scheme: 0,
+            store: default_store(),
+            retention: Default::default(),
         }
     }
 
-    pub fn remove_doc(label: &str, uuid: &str) -> Result<(), LocalLedgerError> {
-        let mut path = get_or_create_doc_dir(label)?;
-        path.push(format!("{}.json", uuid));
+    /// Points the Document at a different store.  Documents use the `default_store` unless told
+    /// otherwise.
+    pub fn with_store(mut self, store: &DocumentStoreRef) -> Self {
+        self.set_store(store);
 
-        std::fs::remove_file(path).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to remove doc: {}", err.to_string()))
-        })?;
+        self
+    }
+
+    pub fn set_store(&mut self, store: &DocumentStoreRef) {
+        self.store = store.clone();
+    }
 
-        Ok(())
+    /// Sets how many prior revisions are kept when the Document is stored
+    pub fn set_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
     }
 
-    /// Loads Document from the filesystem
-    pub fn load(label: &str, uuid: &str) -> Result<Self, LocalLedgerError> {
-        let contents = load_from_disc(uuid, &label)?;
-        let doc = parse_doc::<T>(&contents)?;
+    pub fn remove_doc(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<(), LocalLedgerError> {
+        store.remove(label, uuid)
+    }
+
+    /// Loads Document from the store
+    pub fn load(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<Self, LocalLedgerError> {
+        let contents = store.read(label, uuid)?;
+        let doc = parse_doc::<T>(store, &contents)?;
 
         if doc.encrypted {
             return Err(LocalLedgerError::new("Load failed.  Data is encrypted"));
@@ -85,38 +153,66 @@ where
     }
 
     /// Tries to load document.  If it doesn't exist None is returned.
-    pub fn try_load(label: &str, uuid: &str) -> Option<Self> {
-        // If dir doesn't exist it is created.  Feels weird that a method
-        // called try_load will sometimes make a write (creating a dir)
-        // is this cringe?
-        match get_or_create_doc_dir(label) {
-            Ok(mut path) => {
-                path.push(format!("{}.json", uuid));
-
-                let file_exists = path.exists();
-
-                if !file_exists {
-                    return None;
-                }
+    pub fn try_load(store: &DocumentStoreRef, label: &str, uuid: &str) -> Option<Self> {
+        match store.exists(label, uuid) {
+            Ok(true) => match Document::<T>::load(store, label, uuid) {
+                Ok(doc) => Some(doc),
+                Err(_) => None,
+            },
+
+            _ => None,
+        }
+    }
 
-                match Document::<T>::load(label, uuid) {
-                    Ok(doc) => Some(doc),
-                    Err(_) => None,
-                }
-            }
+    /// Builds a Document from its serialized form, as found in the store or in a ledger dump
+    pub fn from_value(
+        store: &DocumentStoreRef,
+        mut doc_json: Value,
+    ) -> Result<Self, LocalLedgerError> {
+        if !doc_json.is_object() {
+            return Err(LocalLedgerError::new(
+                "Failed to parse doc file: not an object",
+            ));
+        }
 
-            Err(_) => None,
+        if doc_json["encrypted"] == true || !doc_json["deleted_at"].is_null() {
+            // The data of an encrypted doc or a tombstone is only a placeholder and may have been
+            // written with an older schema of `T`
+            doc_json["data"] = serde_json::to_value(T::default())
+                .map_err(|e| LocalLedgerError::new(&e.to_string()))?;
         }
+
+        let doc: Self = serde_json::from_value(doc_json)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to parse doc file: {}", err)))?;
+
+        Ok(doc.with_store(store))
     }
 
-    /// Loads Document from the filesystem, but calls decrypt transform function after reading from disk.
-    pub fn decrypt_load<F>(label: &str, uuid: &str, decrypt: F) -> Result<Self, LocalLedgerError>
+    /// Loads Document from the store without decrypting it
+    pub fn load_encrypted(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<Self, LocalLedgerError> {
+        let contents = store.read(label, uuid)?;
+
+        parse_doc::<T>(store, &contents)
+    }
+
+    /// Loads Document from the store, but calls decrypt transform function after reading it.
+    pub fn decrypt_load<F>(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+        decrypt: F,
+    ) -> Result<Self, LocalLedgerError>
     where
         F: Fn(&Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
     {
-        let contents = load_from_disc(uuid, label)?;
-        let mut parsed_doc = parse_doc(&contents)?;
-        let decrypted_data = decrypt(&parsed_doc.encrypted_data)?;
+        let contents = store.read(label, uuid)?;
+        let mut parsed_doc = parse_doc(store, &contents)?;
+        // The plaintext is wiped once it has been parsed
+        let decrypted_data = Zeroizing::new(decrypt(&parsed_doc.encrypted_data)?);
 
         let parsed_data: T = serde_json::from_slice(&decrypted_data).map_err(|err| {
             LocalLedgerError::new(&format!(
@@ -131,20 +227,6 @@ where
         Ok(parsed_doc)
     }
 
-    /// Loads conflict Document from the filesystem, but calls decrypt transform function after reading from disk.
-    pub fn decrypt_load_conf<F>(
-        label: &str,
-        uuid: &str,
-        decrypt: F,
-    ) -> Result<Self, LocalLedgerError>
-    where
-        F: Fn(&Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
-    {
-        let conf_uuid = format!("{}_{}", uuid, CONFLICT_SUFFIX);
-
-        Self::decrypt_load(label, &conf_uuid, decrypt)
-    }
-
     pub fn decrypt<F>(&mut self, decrypt_fn: F) -> Result<&Self, LocalLedgerError>
     where
         F: Fn(&Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
@@ -161,7 +243,7 @@ where
             return Err(err);
         }
 
-        let decrypted_data = decrypt_fn(&self.encrypted_data)?;
+        let decrypted_data = Zeroizing::new(decrypt_fn(&self.encrypted_data)?);
         let parsed_data: T = serde_json::from_slice(&decrypted_data).map_err(|err| {
             LocalLedgerError::new(&format!(
                 "Failed to parse decrypted data: {}",
@@ -175,83 +257,114 @@ where
         Ok(self)
     }
 
-    pub fn doc_exists(label: &str, uuid: &str) -> Result<bool, LocalLedgerError> {
-        let mut path = get_dir_path(label)?;
-        path.push(format!("{}.json", uuid));
+    /// Runs the encrypted payload of the Document, and those of its prior revisions, through
+    /// `transform` without recording a new revision.  Used to move a Document over to a new key.
+    pub fn reencrypt<F>(&mut self, transform: F) -> Result<&Self, LocalLedgerError>
+    where
+        F: Fn(&Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
+    {
+        if self.encrypted && !self.encrypted_data.is_empty() {
+            self.encrypted_data = transform(&self.encrypted_data)?;
+        }
+
+        for revision in self.revisions.iter_mut().filter(|r| r.encrypted) {
+            let payload = transform(revision.payload())?;
 
-        Ok(path.exists())
+            revision.set_payload(payload);
+        }
+
+        Ok(self)
     }
 
-    /// Gets all uuids that do not have a conflict
-    pub fn get_all_uuids(label: &str) -> Result<Vec<String>, LocalLedgerError> {
-        let doc_uuids = Self::do_get_all_uuids(label)?.0;
+    /// Serializes the Document the way it is written to its store
+    pub fn to_bytes(&self) -> Result<Vec<u8>, LocalLedgerError> {
+        let doc_json = self.to_json()?;
 
-        Ok(doc_uuids)
+        serde_json::to_vec(&doc_json).map_err(|e| LocalLedgerError::new(&e.to_string()))
+    }
+
+    pub fn doc_exists(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<bool, LocalLedgerError> {
+        store.exists(label, uuid)
     }
 
-    pub fn get_all_conflict_uuids(label: &str) -> Result<Vec<String>, LocalLedgerError> {
-        let doc_uuids = Self::do_get_all_uuids(label)?.1;
+    /// Gets all uuids that do not have a conflict.  Temp docs are left out.
+    pub fn get_all_uuids(
+        store: &DocumentStoreRef,
+        label: &str,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let doc_uuids = Self::do_get_all_uuids(store, label)?.0;
 
         Ok(doc_uuids)
     }
 
-    fn do_get_all_uuids(label: &str) -> Result<(Vec<String>, Vec<String>), LocalLedgerError> {
-        let path = get_dir_path(label)?;
-        let mut conf_ledger = HashMap::<String, bool>::new();
-        let doc_uuids = std::fs::read_dir(path)
-            .map_err(|e| LocalLedgerError::new(&e.to_string()))?
-            .try_fold(
-                (Vec::<String>::new(), Vec::<String>::new()),
-                |mut accum, dir_entry_result| match dir_entry_result {
-                    Ok(dir_entry) => {
-                        if dir_entry.path().is_dir() {
-                            return Ok(accum);
-                        }
+    /// Gets the uuids of all Documents that have at least one conflict sibling
+    pub fn get_all_conflict_uuids(
+        store: &DocumentStoreRef,
+        label: &str,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let doc_uuids = Self::do_get_all_uuids(store, label)?.1;
 
-                        let path = dir_entry.path();
-                        let file_name = path
-                            .file_stem()
-                            .and_then(|s| s.to_str())
-                            .ok_or(LocalLedgerError::new("Failed to find document uuid"))?;
-                        let is_conflict = file_name.contains(CONFLICT_SUFFIX);
-
-                        if is_conflict {
-                            let uuid = file_name
-                                .to_string()
-                                .replace(&format!("_{}", CONFLICT_SUFFIX), "");
-                            accum.1.push(uuid.clone());
-                            conf_ledger.insert(uuid.clone(), true);
-
-                            return Ok(accum);
-                        }
+        Ok(doc_uuids)
+    }
 
-                        if let Some(_) = conf_ledger.get(&file_name.to_string()) {
-                            return Ok(accum);
-                        }
+    /// Gets the uuids of the conflict siblings stored for the Document with `uuid`
+    pub fn get_conflict_uuids(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let conf_uuids: Vec<String> = store
+            .list(label)?
+            .into_iter()
+            .filter(|file_name| Self::conflict_parent_uuid(file_name) == Some(uuid))
+            .collect();
 
-                        accum.0.push(file_name.to_string());
+        Ok(conf_uuids)
+    }
 
-                        Ok(accum)
-                    }
+    fn do_get_all_uuids(
+        store: &DocumentStoreRef,
+        label: &str,
+    ) -> Result<(Vec<String>, Vec<String>), LocalLedgerError> {
+        let file_names = store.list(label)?;
+        let mut conflict_uuids: Vec<String> = file_names
+            .iter()
+            .filter_map(|file_name| Self::conflict_parent_uuid(file_name))
+            .map(|uuid| uuid.to_owned())
+            .collect();
 
-                    Err(e) => Err(LocalLedgerError::new(&format!(
-                        "Failed to find ledger uuids: {}",
-                        e.to_string()
-                    ))),
-                },
-            )?;
+        // A Document shows up once no matter how many siblings it has
+        conflict_uuids.sort();
+        conflict_uuids.dedup();
+
+        let conf_ledger: HashSet<&String> = conflict_uuids.iter().collect();
+        let doc_uuids: Vec<String> = file_names
+            .iter()
+            .filter(|file_name| {
+                Self::conflict_parent_uuid(file_name).is_none()
+                    && strip_doc_suffix(file_name, TEMP_SUFFIX).is_none()
+                    && !conf_ledger.contains(file_name)
+            })
+            .cloned()
+            .collect();
 
-        Ok(doc_uuids)
+        Ok((doc_uuids, conflict_uuids))
     }
 
-    /// Updates the fields of a Document
+    /// Updates the fields of a Document.  Updating a deleted or trashed Document brings it back.
     pub fn update<'a>(&'a mut self, updates: T) -> &'a mut Self {
         self.data = updates;
+        self.deleted_at = None;
+        self.trashed_at = None;
 
         self
     }
 
-    /// Saves Document to filesystem
+    /// Saves Document to its store
     pub fn store<'a>(&'a mut self) -> Result<&'a Self, LocalLedgerError> {
         if self.encrypted && !self.has_been_decrypted {
             return Err(LocalLedgerError::new(
@@ -259,30 +372,194 @@ where
             ));
         }
 
-        self.do_store()
+        self.do_store(true)
     }
 
-    /// Saves Document to filesystem, but calls encrypt transform function before writing to disk.
+    /// Saves Document to its store, but calls encrypt transform function before writing to disk.
     ///
     /// If successfull, this method clears the data currently being held in the Document.  Calling `read_data` afterward will give you default values and will not match what was saved to disk.  You must call `decrypt_load` in order to get the data again.
     pub fn store_encrypted<'a, F>(&'a mut self, encrypt: F) -> Result<&'a Self, LocalLedgerError>
     where
-        F: Fn(Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
+        F: Fn(&[u8]) -> Result<Vec<u8>, LocalLedgerError>,
     {
-        let bytes = self.data_to_bytes()?;
-        let encrypted_data = encrypt(bytes)?;
+        let bytes = Zeroizing::new(self.data_to_bytes()?);
+        let encrypted_data = encrypt(&bytes)?;
 
         self.encrypted_data = encrypted_data;
         self.encrypted = true;
 
         // If we make the has_been_decrypted field public we need to write a test for it
         self.has_been_decrypted = false;
-        self.do_store()
+        self.do_store(true)
+    }
+
+    /// Deletes the Document by turning it into a tombstone and storing it.  Unlike `remove`, the
+    /// deletion is recorded like any other update, so it wins over older copies of the Document
+    /// during a merge.  The data and history of the Document are dropped.
+    pub fn delete(&mut self) -> Result<&Self, LocalLedgerError> {
+        self.data = T::default();
+        self.encrypted_data = vec![];
+        self.encrypted = false;
+        self.has_been_decrypted = false;
+        self.revisions.clear();
+        self.deleted_at = Some(unix_now());
+        self.trashed_at = None;
+
+        self.do_store(true)
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    pub fn deleted_at(&self) -> Option<u64> {
+        self.deleted_at
+    }
+
+    /// Moves the Document to the trash and stores it.  The data and history are kept, and the
+    /// move is versioned like any other update so it syncs to other devices.  Works on encrypted
+    /// Documents without decrypting them.
+    pub fn trash(&mut self) -> Result<&Self, LocalLedgerError> {
+        if self.is_deleted() {
+            return Err(LocalLedgerError::new("Cannot trash a deleted Document"));
+        }
+
+        self.trashed_at = Some(unix_now());
+
+        self.do_store(false)
+    }
+
+    /// Takes the Document back out of the trash and stores it
+    pub fn restore_from_trash(&mut self) -> Result<&Self, LocalLedgerError> {
+        if !self.is_trashed() {
+            return Err(LocalLedgerError::new("Document is not in the trash"));
+        }
+
+        self.trashed_at = None;
+
+        self.do_store(false)
+    }
+
+    pub fn is_trashed(&self) -> bool {
+        self.trashed_at.is_some()
+    }
+
+    pub fn trashed_at(&self) -> Option<u64> {
+        self.trashed_at
+    }
+
+    /// Loads the tombstone stored under `uuid`.  Returns None if there is no Document or if it
+    /// has not been deleted.
+    pub fn load_tombstone(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<Option<Self>, LocalLedgerError> {
+        if !store.exists(label, uuid)? {
+            return Ok(None);
+        }
+
+        let doc = Self::load_encrypted(store, label, uuid)?;
+
+        Ok(Some(doc).filter(|d| d.is_deleted()))
+    }
+
+    /// Whether a Document that has been neither deleted nor trashed is stored under `uuid`
+    pub fn live_doc_exists(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<bool, LocalLedgerError> {
+        if !store.exists(label, uuid)? {
+            return Ok(false);
+        }
+
+        let removal = read_removal(store, label, uuid)?;
+
+        Ok(removal.deleted_at.is_none() && removal.trashed_at.is_none())
     }
 
-    /// Removes Document from filesystem
+    /// Reads when the Document stored under `uuid` was moved to the trash.  None if there is no
+    /// Document or if it is not in the trash.
+    pub fn read_trashed_at(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuid: &str,
+    ) -> Result<Option<u64>, LocalLedgerError> {
+        if !store.exists(label, uuid)? {
+            return Ok(None);
+        }
+
+        Ok(read_removal(store, label, uuid)?.trashed_at)
+    }
+
+    /// Removes the tombstones among the Documents `uuids` that were deleted longer than
+    /// `horizon` ago.  Once its tombstone is gone a deletion can no longer be synced, so the
+    /// horizon should be longer than devices are expected to go without syncing.  Documents that
+    /// can not be read are skipped.  Returns the purged uuids.
+    pub fn purge_tombstones(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuids: &[String],
+        horizon: Duration,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let cutoff = unix_now().saturating_sub(horizon.as_secs());
+        let mut purged = vec![];
+
+        for uuid in uuids {
+            match read_removal_or_skip(store, label, uuid).and_then(|removal| removal.deleted_at) {
+                Some(deleted_at) if deleted_at <= cutoff => {
+                    store.remove(label, uuid)?;
+                    purged.push(uuid.clone());
+                }
+                _ => {}
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Deletes the Documents among `uuids` that were moved to the trash longer than `horizon`
+    /// ago, leaving tombstones in their place.  Documents that can not be read are skipped.
+    /// Returns the uuids of the deleted Documents.
+    pub fn purge_trash(
+        store: &DocumentStoreRef,
+        label: &str,
+        uuids: &[String],
+        horizon: Duration,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let cutoff = unix_now().saturating_sub(horizon.as_secs());
+        let mut purged = vec![];
+
+        for uuid in uuids {
+            match read_removal_or_skip(store, label, uuid).and_then(|removal| removal.trashed_at) {
+                Some(trashed_at) if trashed_at <= cutoff => {
+                    match Self::load_encrypted(store, label, uuid) {
+                        Ok(mut doc) => {
+                            doc.delete()?;
+                            purged.push(uuid.clone());
+                        }
+                        Err(err) => {
+                            tracing::warn!("Skipped doc {} of {}: {}", uuid, label, err.message)
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Folds the version of `other_doc` into this one, so this copy supersedes both once it is
+    /// stored.  Used to settle a conflict in favor of this copy.
+    pub fn merge_version(&mut self, other_doc: &Document<T>) {
+        self.version.merge(&other_doc.version);
+    }
+
+    /// Removes Document from its store
     pub fn remove(&mut self) -> Result<(), LocalLedgerError> {
-        Document::<T>::remove_doc(&self.label, &self.uuid)
+        Document::<T>::remove_doc(&self.store, &self.label, &self.uuid)
     }
 
     /// Return read only Document data
@@ -342,12 +619,17 @@ where
         self.uuid.clone()
     }
 
+    /// Returns a handle to the store the Document is saved to
+    pub fn get_store(&self) -> DocumentStoreRef {
+        self.store.clone()
+    }
+
     pub fn has_been_decrypted(&self) -> bool {
         self.has_been_decrypted
     }
 
     pub fn get_data_dir(&self) -> Result<PathBuf, LocalLedgerError> {
-        get_dir_path(&self.label)
+        self.store.dir_path(&self.label)
     }
 
     pub fn rev(&self) -> &str {
@@ -358,58 +640,198 @@ where
         &self.label
     }
 
-    pub fn check_for_conflict(&self, other_doc: &Document<T>) -> bool {
-        let conflict_present = check_rev_history(&self, other_doc);
+    /// Returns the prior revisions of this Document, oldest first
+    pub fn history(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    /// Describes what is wrong with the rev history of this Document.  None if nothing is.  A
+    /// stored Document has a rev, a seq of at least one and one earlier rev in its history for
+    /// every store after the first.
+    pub fn rev_history_problem(&self) -> Option<String> {
+        if self.rev.is_empty() {
+            return Some("Document has no rev".to_owned());
+        }
+
+        if self.rev_history.contains(&self.rev) {
+            return Some(format!("Rev {} is in its own history", self.rev));
+        }
+
+        let unique_revs: HashSet<&String> = self.rev_history.iter().collect();
+
+        if unique_revs.len() != self.rev_history.len() {
+            return Some("Rev history has duplicate revs".to_owned());
+        }
+
+        if self.seq < 1 || self.rev_history.len() as i64 >= self.seq {
+            return Some(format!(
+                "Seq {} does not match a rev history of {} revs",
+                self.seq,
+                self.rev_history.len()
+            ));
+        }
+
+        None
+    }
+
+    /// Fixes what `rev_history_problem` finds, by dropping the revs that do not belong in the
+    /// history and bumping the seq to match it.  Takes effect the next time the Document is
+    /// stored.  Returns false if the Document has no rev, that can not be fixed.
+    pub fn repair_rev_history(&mut self) -> bool {
+        if self.rev.is_empty() {
+            return false;
+        }
+
+        let mut seen = HashSet::new();
+        let rev = self.rev.clone();
+
+        self.rev_history
+            .retain(|history_rev| *history_rev != rev && seen.insert(history_rev.clone()));
+
+        if self.rev_history.len() as i64 >= self.seq {
+            self.seq = self.rev_history.len() as i64 + 1;
+        }
+
+        true
+    }
+
+    /// Makes the payload of a prior revision the current one and stores the Document.  The
+    /// replaced payload is kept in the history like any other update.
+    ///
+    /// Encrypted payloads are restored as is, so the Document has to be decrypted again before
+    /// its data can be read.
+    pub fn restore_rev(&mut self, rev: &str) -> Result<&Self, LocalLedgerError> {
+        let revision = self
+            .revisions
+            .iter()
+            .find(|r| r.rev == rev)
+            .cloned()
+            .ok_or(LocalLedgerError::new(&format!(
+                "Revision {} not found",
+                rev
+            )))?;
+
+        self.schema_version = revision.schema_version;
+
+        if revision.encrypted {
+            self.encrypted_data = revision.payload().clone();
+            self.encrypted = true;
+            self.has_been_decrypted = false;
+            self.data = T::default();
+        } else {
+            self.data =
+                revision.read_data(|_| Err(LocalLedgerError::new("Revision is not encrypted")))?;
+            self.encrypted_data = vec![];
+            self.encrypted = false;
+        }
+
+        self.do_store(true)
+    }
+
+    pub fn version(&self) -> &VersionVector {
+        &self.version
+    }
 
-        conflict_present
+    pub fn format(&self) -> u32 {
+        self.format
+    }
+
+    pub fn name_scheme(&self) -> u32 {
+        self.name_scheme
+    }
+
+    pub fn set_name_scheme(&mut self, name_scheme: u32) {
+        self.name_scheme = name_scheme;
+    }
+
+    pub fn schema_version(&self) -> u32 {
+        self.schema_version
+    }
+
+    /// Sets the schema version the payload is written with.  Takes effect the next time the
+    /// Document is stored.
+    pub fn set_schema_version(&mut self, schema_version: u32) {
+        self.schema_version = schema_version;
+    }
+
+    /// Works out how this Document relates to another copy of it.  Only
+    /// `Causality::Concurrent` copies are in conflict, the others can be ordered.
+    pub fn check_for_conflict(&self, other_doc: &Document<T>) -> Causality {
+        if self.version.is_empty() || other_doc.version.is_empty() {
+            // Docs written before version vectors were introduced only have their rev history
+            return compare_rev_history(self, other_doc);
+        }
+
+        self.version.compare(&other_doc.version)
+    }
+
+    /// Saves the Document exactly as it is, without recording a new revision.  This is how a copy
+    /// of the Document made on another device gets adopted.  Fails with a conflict unless the
+    /// stored copy is an ancestor of this one.
+    pub fn store_as_is(&mut self) -> Result<&Self, LocalLedgerError> {
+        let base_version = self.version.clone();
+
+        self.write_doc(&base_version, false)
     }
 
     /// Save document as a temporary file.  Warning, this method does not encrypt nor does it do conflict detection.  Last call wins.
     pub fn temp_store(&mut self) -> Result<&Self, LocalLedgerError> {
-        self.do_temp_store(TEMP_SUFFIX)
+        let temp_uuid = format!("{}_{}", self.uuid, TEMP_SUFFIX);
+
+        self.do_temp_store(&temp_uuid)
     }
 
-    /// Save document as a conflict.  Warning, this method does not encrypt nor does it do conflict detection.  Last call wins
+    /// Save document as a conflict sibling of the Document it is a copy of.  Every conflicting rev
+    /// gets a sibling of its own, so storing one conflict never overwrites another.  Warning, this
+    /// method does not encrypt nor does it do conflict detection.
     pub fn conflict_store(&mut self) -> Result<&Self, LocalLedgerError> {
-        self.do_temp_store(CONFLICT_SUFFIX)
+        let conf_uuid = Self::conflict_uuid(&self.uuid, &self.rev);
+
+        self.do_temp_store(&conf_uuid)
     }
 
-    fn do_temp_store(&mut self, temp_name: &str) -> Result<&Self, LocalLedgerError> {
-        let temp_uuid = format!("{}_{}", self.uuid, temp_name);
-        self.append_uuid(&temp_uuid);
+    fn do_temp_store(&mut self, temp_uuid: &str) -> Result<&Self, LocalLedgerError> {
+        self.append_uuid(temp_uuid);
 
+        let doc_json = self.to_json()?;
         let doc_bytes =
-            serde_json::to_vec(&self).map_err(|e| LocalLedgerError::new(&e.to_string()))?;
-        let mut path = get_or_create_doc_dir(&self.label)?;
-        path.push(format!("{}.json", temp_uuid));
-        let mut doc_file = get_or_create_doc_file(&path)?;
-
-        // Set length of file to insure we are replacing the contents.
-        // This can be done via call the .truncate() in get_or_create_doc_file
-        doc_file.set_len(doc_bytes.len() as u64).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to set file length: {}", err.to_string()))
-        })?;
+            serde_json::to_vec(&doc_json).map_err(|e| LocalLedgerError::new(&e.to_string()))?;
 
-        doc_file.write_all(&doc_bytes).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to save doc: {}", err.to_string()))
-        })?;
+        self.store.write(&self.label, temp_uuid, &doc_bytes)?;
 
         Ok(self)
     }
 
-    pub fn get_all_temp_uuids(label: &str) -> Result<Vec<String>, LocalLedgerError> {
-        let temp_uuids: Vec<String> = Document::<T>::get_all_uuids(label)?
+    pub fn get_all_temp_uuids(
+        store: &DocumentStoreRef,
+        label: &str,
+    ) -> Result<Vec<String>, LocalLedgerError> {
+        let temp_uuids: Vec<String> = store
+            .list(label)?
             .into_iter()
-            .filter(|uuid| uuid.ends_with(TEMP_SUFFIX))
+            .filter(|uuid| strip_doc_suffix(uuid, TEMP_SUFFIX).is_some())
             .collect();
 
         Ok(temp_uuids)
     }
 
+    /// Returns the uuid the conflicting copy of a Document at `rev` is stored under
+    pub fn conflict_uuid(uuid: &str, rev: &str) -> String {
+        format!("{}_{}_{}", uuid, CONFLICT_SUFFIX, rev)
+    }
+
+    /// Returns the uuid of the Document a conflict sibling belongs to.  None if `uuid` is not a
+    /// conflict sibling.  Siblings stored before every rev got its own are named `<uuid>_CONF`.
+    pub fn conflict_parent_uuid(uuid: &str) -> Option<&str> {
+        strip_doc_suffix(uuid, CONFLICT_SUFFIX).or_else(|| {
+            let (conf_uuid, _rev) = uuid.rsplit_once('_')?;
+
+            strip_doc_suffix(conf_uuid, CONFLICT_SUFFIX)
+        })
+    }
+
     pub fn temp_uuid_to_uuid(temp_uuid: &str) -> Result<String, LocalLedgerError> {
-        // I really don't like this function.  It's super hacky.  Should find a better way to
-        // accomplish this
-        match temp_uuid.split("_").next() {
+        match strip_doc_suffix(temp_uuid, TEMP_SUFFIX) {
             Some(uuid) => Ok(uuid.to_string()),
             None => Err(LocalLedgerError::new(&format!(
                 "The provided temp_uuid, {}, is not valid",
@@ -432,7 +854,18 @@ where
     //     Ok(doc)
     // }
 
-    fn do_store<'a>(&'a mut self) -> Result<&'a Self, LocalLedgerError> {
+    /// Bumps the rev and version of the Document and writes it.  `keep_revision` keeps the
+    /// payload being replaced in the history.
+    fn do_store<'a>(&'a mut self, keep_revision: bool) -> Result<&'a Self, LocalLedgerError> {
+        let base_version = self.version.clone();
+
+        self.next_rev()?;
+        self.write_doc(&base_version, keep_revision)
+    }
+
+    /// Bumps the rev and version of the Document without writing it, for Documents that are
+    /// written some other way, see `to_bytes`
+    pub fn next_rev(&mut self) -> Result<&mut Self, LocalLedgerError> {
         let new_rev = generate_id();
         self.seq += 1;
 
@@ -442,45 +875,71 @@ where
         }
 
         self.rev = new_rev;
-        let mut doc_json =
-            serde_json::to_value(&self).map_err(|e| LocalLedgerError::new(&e.to_string()))?;
+        self.version.increment(&self.store.device_id()?);
 
-        if self.encrypted {
-            // we dont want to write the data field to disc
-            // This is probably a better way to do this... YOLO!
-            let data_json = serde_json::to_value(T::default())
-                .map_err(|e| LocalLedgerError::new(&e.to_string()))?;
-            doc_json["data"] = data_json;
+        Ok(self)
+    }
+
+    /// Writes the Document to its store.  `base_version` is the version the Document had when it
+    /// was read, anything newer in the store is a conflict.
+    fn write_doc(
+        &mut self,
+        base_version: &VersionVector,
+        keep_revision: bool,
+    ) -> Result<&Self, LocalLedgerError> {
+        // Keeps other processes from writing between the conflict check and the write
+        let _write_lock = self.store.lock_writes(&self.label)?;
+
+        if self.store.exists(&self.label, &self.uuid)? {
+            let curr_contents = self.store.read(&self.label, &self.uuid)?;
+            let curr_doc = do_check_for_conflict::<T>(&curr_contents, self, base_version)?;
+
+            if keep_revision && !curr_doc.is_deleted() && !self.is_deleted() {
+                self.revisions.push(curr_doc.to_revision()?);
+                self.retention.apply(&mut self.revisions);
+            }
         }
 
+        let doc_json = self.to_json()?;
         let doc_json_str = serde_json::to_string(&doc_json).map_err(|serde_err| {
             LocalLedgerError::new(&format!(
                 "Failed to serialize document: {}",
                 serde_err.to_string()
             ))
         })?;
-        let mut path = get_or_create_doc_dir(&self.label)?;
-        path.push(format!("{}.json", self.uuid));
-        let file_exists = path.exists();
-        let mut doc_file = get_or_create_doc_file(&path)?;
 
-        if file_exists {
-            do_check_for_conflict::<T>(&mut doc_file, &self.rev_history, &self.rev)?;
-        }
+        self.store
+            .write(&self.label, &self.uuid, doc_json_str.as_bytes())?;
 
-        let doc_bytes = doc_json_str.as_bytes();
+        Ok(self)
+    }
 
-        // Set length of file to insure we are replacing the contents.
-        // This can be done via call the .truncate() in get_or_create_doc_file
-        doc_file.set_len(doc_bytes.len() as u64).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to set file length: {}", err.to_string()))
-        })?;
+    /// Serializes the Document in the layout of its format.  Encrypted Documents are written
+    /// without their data.
+    fn to_json(&self) -> Result<Value, LocalLedgerError> {
+        let mut doc_json = serde_json::to_value(StoredDocument::from(self))
+            .map_err(|e| LocalLedgerError::new(&e.to_string()))?;
 
-        doc_file.write_all(doc_bytes).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to save doc: {}", err.to_string()))
-        })?;
+        encode_payloads(&mut doc_json, self.format)?;
 
-        Ok(self)
+        Ok(doc_json)
+    }
+
+    /// Captures the current payload as a Revision
+    fn to_revision(&self) -> Result<Revision, LocalLedgerError> {
+        let payload = if self.encrypted {
+            self.encrypted_data.clone()
+        } else {
+            self.data_to_bytes()?
+        };
+
+        Ok(Revision::new(
+            &self.rev,
+            self.seq,
+            self.schema_version,
+            self.encrypted,
+            payload,
+        ))
     }
 
     fn data_to_bytes(&self) -> Result<Vec<u8>, LocalLedgerError> {
@@ -493,139 +952,151 @@ where
     }
 }
 
-fn load_from_disc(uuid: &str, label: &str) -> Result<String, LocalLedgerError> {
-    //let path = format!("{}/{}.json", get_dir_path(label), uuid);
-    let mut path = get_dir_path(label)?;
-
-    path.push(format!("{}.json", uuid));
-
-    let mut doc_file = std::fs::File::open(path).map_err(|err| {
-        LocalLedgerError::new(&format!("Document not found: {}", err.to_string()))
-    })?;
-
-    let mut contents = String::new();
-    doc_file.read_to_string(&mut contents).unwrap();
-
-    Ok(contents)
+/// Returns the uuid of the Document a temp or conflict copy was made from.  None if `uuid` does
+/// not end in `_<suffix>`.
+fn strip_doc_suffix<'a>(uuid: &'a str, suffix: &str) -> Option<&'a str> {
+    uuid.strip_suffix(suffix)?.strip_suffix('_')
 }
 
-fn get_or_create_doc_dir(doc_label: &str) -> Result<PathBuf, LocalLedgerError> {
-    let path = get_dir_path(doc_label)?;
-
-    std::fs::create_dir_all(&path).map_err(|err| {
-        LocalLedgerError::new(&format!(
-            "Failed to create document directory: {}",
-            err.to_string()
-        ))
-    })?;
-
-    Ok(path)
+/// The fields of a Document as they are written to its store.  The data of an encrypted
+/// Document is swapped for its default here, so it never ends up in a `Value` that is not wiped.
+#[derive(Serialize)]
+struct StoredDocument<'a, T: Clone> {
+    label: &'a str,
+    uuid: &'a str,
+    rev: &'a str,
+    data: Cow<'a, T>,
+    seq: i64,
+    encrypted_data: &'a [u8],
+    encrypted: bool,
+    has_been_decrypted: bool,
+    rev_history: &'a [String],
+    revisions: &'a [Revision],
+    version: &'a VersionVector,
+    format: u32,
+    schema_version: u32,
+    deleted_at: Option<u64>,
+    trashed_at: Option<u64>,
+    name_scheme: u32,
 }
 
-//fn get_or_create_temp_doc_dir(doc_label: &str) -
+impl<'a, T> From<&'a Document<T>> for StoredDocument<'a, T>
+where
+    T: Clone + Default,
+{
+    fn from(doc: &'a Document<T>) -> Self {
+        StoredDocument {
+            label: &doc.label,
+            uuid: &doc.uuid,
+            rev: &doc.rev,
+            data: if doc.encrypted {
+                Cow::Owned(T::default())
+            } else {
+                Cow::Borrowed(&doc.data)
+            },
+            seq: doc.seq,
+            encrypted_data: &doc.encrypted_data,
+            encrypted: doc.encrypted,
+            has_been_decrypted: doc.has_been_decrypted,
+            rev_history: &doc.rev_history,
+            revisions: &doc.revisions,
+            version: &doc.version,
+            format: doc.format,
+            schema_version: doc.schema_version,
+            deleted_at: doc.deleted_at,
+            trashed_at: doc.trashed_at,
+            name_scheme: doc.name_scheme,
+        }
+    }
+}
 
-fn get_dir_path(doc_label: &str) -> Result<PathBuf, LocalLedgerError> {
-    let mut base_dir = dirs::home_dir().map_or(
-        Err(LocalLedgerError::new("Failed to get directory path")),
-        |d| Ok(d),
-    )?;
+/// When a Document was deleted or trashed, if it was
+#[derive(Deserialize)]
+struct Removal {
+    #[serde(default)]
+    deleted_at: Option<u64>,
+    #[serde(default)]
+    trashed_at: Option<u64>,
+}
 
-    base_dir.push(".fizzgig");
-    base_dir.push(doc_label);
+/// Reads whether the Document stored under `uuid` was deleted or trashed, without parsing all of
+/// it
+fn read_removal(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+) -> Result<Removal, LocalLedgerError> {
+    let contents = store.read(label, uuid)?;
 
-    Ok(base_dir)
+    serde_json::from_slice(&contents).map_err(|err| {
+        LocalLedgerError::new(&format!("Failed to parse doc file: {}", err.to_string()))
+    })
 }
 
-fn get_or_create_doc_file(file_path: &PathBuf) -> Result<std::fs::File, LocalLedgerError> {
-    let doc_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(file_path)
-        .map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to open file: {}", err.to_string()))
-        })?;
-
-    Ok(doc_file)
+/// Like `read_removal`, but logs a Document that can not be read instead of failing, for passes
+/// over many Documents that should not stop at a damaged one
+fn read_removal_or_skip(store: &DocumentStoreRef, label: &str, uuid: &str) -> Option<Removal> {
+    read_removal(store, label, uuid)
+        .map_err(|err| tracing::warn!("Skipped doc {} of {}: {}", uuid, label, err.message))
+        .ok()
 }
 
 fn parse_doc<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
-    contents: &str,
+    store: &DocumentStoreRef,
+    contents: &[u8],
 ) -> Result<Document<T>, LocalLedgerError> {
-    let doc: Document<T> = serde_json::from_str(&contents).map_err(|err| {
+    let doc_json: Value = serde_json::from_slice(contents).map_err(|err| {
         LocalLedgerError::new(&format!("Failed to parse doc file: {}", err.to_string()))
     })?;
 
-    Ok(doc)
+    Document::<T>::from_value(store, doc_json)
 }
 
 fn do_check_for_conflict<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
-    doc_file: &mut std::fs::File,
-    new_rev_history: &Vec<String>,
-    new_rev: &str,
-) -> Result<(), LocalLedgerError> {
-    let mut curr_contents = String::new();
-
-    doc_file.read_to_string(&mut curr_contents).map_err(|err| {
-        LocalLedgerError::new(&format!("Failed to load file: {}", err.to_string()))
-    })?;
-
-    let curr_doc: Document<T> = serde_json::from_str(&curr_contents).map_err(|err| {
+    curr_contents: &[u8],
+    new_doc: &Document<T>,
+    base_version: &VersionVector,
+) -> Result<Document<T>, LocalLedgerError> {
+    let curr_doc: Document<T> = parse_doc(&new_doc.store, curr_contents).map_err(|err| {
         LocalLedgerError::new(&format!(
             "Failed to parse previous doc file: {}",
-            err.to_string()
+            err.message
         ))
     })?;
 
-    let new_rev_history_iter = new_rev_history.into_iter();
-    let curr_rev_history_iter = curr_doc.rev_history.into_iter();
-
-    if new_rev_history_iter.len() >= curr_rev_history_iter.len() {
-        let new_rev = new_rev.to_string();
-        let mut side_by_side_history = curr_rev_history_iter
-            .chain(vec![curr_doc.rev])
-            .zip(new_rev_history_iter.chain(vec![&new_rev]));
+    let causality = if curr_doc.version.is_empty() {
+        compare_rev_history(new_doc, &curr_doc)
+    } else {
+        base_version.compare(&curr_doc.version)
+    };
 
-        while let Some((rev_1, rev_2)) = side_by_side_history.next() {
-            if &rev_1 != rev_2 {
-                return Err(LocalLedgerError::conflict("Document update conflict"));
-            }
+    match causality {
+        Causality::Equal | Causality::Descendant => Ok(curr_doc),
+        Causality::Ancestor | Causality::Concurrent => {
+            Err(LocalLedgerError::conflict("Document update conflict"))
         }
-    } else {
-        return Err(LocalLedgerError::conflict("Document update conflict"));
     }
-
-    doc_file.rewind().map_err(|err| {
-        LocalLedgerError::new(&format!("Failed to reset file: {}", err.to_string()))
-    })?;
-
-    Ok(())
 }
 
-/// Check rev history for conflicts.  Returning true if there are conflict and false otherwise
-fn check_rev_history<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
+/// Orders two copies of a doc by their linear rev histories.  Only used for docs that were
+/// written before version vectors were introduced.
+fn compare_rev_history<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
     our_doc: &Document<T>,
     other_doc: &Document<T>,
-) -> bool {
-    // TODO We got dup code.  with logic in the body of do_check_for_conflict
-    let other_rev_history = (&other_doc.rev_history).into_iter();
-    let our_rev_history = (&our_doc.rev_history).into_iter();
-
-    if other_rev_history.len() >= our_rev_history.len() {
-        let mut side_by_side_history = our_rev_history
-            .chain(vec![&our_doc.rev])
-            .zip(other_rev_history.chain(vec![&other_doc.rev]));
-
-        while let Some((rev_1, rev_2)) = side_by_side_history.next() {
-            if &rev_1 != &rev_2 {
-                return true;
-            }
-        }
+) -> Causality {
+    if our_doc.rev == other_doc.rev {
+        return Causality::Equal;
+    }
+
+    if other_doc.rev_history.contains(&our_doc.rev) {
+        return Causality::Ancestor;
+    }
 
-        return false;
+    if our_doc.rev_history.contains(&other_doc.rev) {
+        return Causality::Descendant;
     }
 
-    true
+    Causality::Concurrent
 }
 
 #[cfg(test)]
@@ -640,14 +1111,19 @@ mod tests {
         name: String,
     }
 
+    fn test_store() -> DocumentStoreRef {
+        std::sync::Arc::new(crate::MemoryStore::new())
+    }
+
     #[test]
     fn should_save_doc() {
+        let store = test_store();
         let person = Person {
             age: 1,
             name: "duder".to_owned(),
         };
 
-        let mut doc = Document::new("Person");
+        let mut doc = Document::new("Person").with_store(&store);
 
         doc.update(person.clone()).store().unwrap();
 
@@ -658,15 +1134,16 @@ mod tests {
 
     #[test]
     fn should_load_doc() {
+        let store = test_store();
         let person = Person {
             age: 1,
             name: "duder".to_owned(),
         };
 
-        let mut doc = Document::new("Person");
+        let mut doc = Document::new("Person").with_store(&store);
         doc.update(person.clone()).store().unwrap();
 
-        let mut loaded_doc = Document::<Person>::load("Person", &doc.uuid).unwrap();
+        let mut loaded_doc = Document::<Person>::load(&store, "Person", &doc.uuid).unwrap();
         let loaded_person = loaded_doc.read_data().unwrap();
 
         assert_eq!(loaded_person, &person);
@@ -676,12 +1153,13 @@ mod tests {
 
     #[test]
     fn should_update_doc() {
+        let store = test_store();
         let person = Person {
             age: 1,
             name: "duder".to_owned(),
         };
 
-        let mut doc = Document::new("Person");
+        let mut doc = Document::new("Person").with_store(&store);
         doc.update(person.clone()).store().unwrap();
 
         let updated_person = Person {
@@ -691,7 +1169,7 @@ mod tests {
 
         doc.update(updated_person.clone()).store().unwrap();
 
-        let loaded_doc = Document::<Person>::load("Person", &doc.uuid).unwrap();
+        let loaded_doc = Document::<Person>::load(&store, "Person", &doc.uuid).unwrap();
         let loaded_person = loaded_doc.read_data().unwrap();
 
         assert_eq!(loaded_person, &updated_person);
@@ -701,17 +1179,18 @@ mod tests {
 
     #[test]
     fn should_receive_document_update_conflict() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
         doc_0.update(person.clone()).store().unwrap();
 
         let doc_uuid = doc_0.read_uuid();
 
-        let mut doc_1 = Document::<Person>::load("Person", doc_uuid).unwrap();
+        let mut doc_1 = Document::<Person>::load(&store, "Person", doc_uuid).unwrap();
 
         doc_0.update(Person {
             age: 31,
@@ -733,7 +1212,8 @@ mod tests {
 
     #[test]
     fn load_should_fail_if_doc_does_not_exist() {
-        let err = Document::<Person>::load("Person", "some invalid id").unwrap_err();
+        let store = test_store();
+        let err = Document::<Person>::load(&store, "Person", "some invalid id").unwrap_err();
 
         let contains_correct_msg = err.to_string().contains("Document not found: ");
 
@@ -742,19 +1222,20 @@ mod tests {
 
     #[test]
     fn should_remove_doc() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
         doc_0.update(person.clone()).store().unwrap();
 
         let uuid = doc_0.read_uuid().to_owned();
 
         doc_0.remove().unwrap();
 
-        let err = Document::<Person>::load("Person", &uuid).unwrap_err();
+        let err = Document::<Person>::load(&store, "Person", &uuid).unwrap_err();
 
         let contains_correct_msg = err.to_string().contains("Document not found: ");
 
@@ -763,14 +1244,16 @@ mod tests {
 
     #[test]
     fn should_be_able_to_create_a_hash_map_document() {
+        let store = test_store();
         let mut hs = HashMap::new();
         hs.insert("hello".to_owned(), "world".to_owned());
 
-        let mut hash_map_doc_0 = Document::<HashMap<String, String>>::new("Config");
+        let mut hash_map_doc_0 =
+            Document::<HashMap<String, String>>::new("Config").with_store(&store);
         hash_map_doc_0.update(hs.clone()).store().unwrap();
 
         let hash_map_doc_1 =
-            Document::<HashMap<String, String>>::load("Config", hash_map_doc_0.read_uuid())
+            Document::<HashMap<String, String>>::load(&store, "Config", hash_map_doc_0.read_uuid())
                 .unwrap();
         //hash_map_doc_1.load(hash_map_doc_0.read_uuid()).unwrap();
 
@@ -783,47 +1266,59 @@ mod tests {
 
     #[test]
     fn should_store_encrypted_data() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0
             .update(person.clone())
             .store_encrypted(|_data| Ok(b"ENCRYPTED_DATA".to_vec()))
             .unwrap();
 
-        let doc_1 = Document::<Person>::decrypt_load("Person", &doc_0.uuid, |_encrypted_data| {
-            let decrypted_data = serde_json::to_vec(&person).unwrap();
+        let doc_1 =
+            Document::<Person>::decrypt_load(&store, "Person", &doc_0.uuid, |_encrypted_data| {
+                let decrypted_data = serde_json::to_vec(&person).unwrap();
 
-            Ok(decrypted_data)
-        })
-        .unwrap();
+                Ok(decrypted_data)
+            })
+            .unwrap();
 
         assert_eq!(doc_0.read_uuid(), doc_1.read_uuid());
 
         assert_eq!(doc_1.read_data().unwrap(), &person);
 
+        // Only the encrypted payload is written
+        let stored_json: Value =
+            serde_json::from_slice(&store.read("Person", &doc_0.uuid).unwrap()).unwrap();
+
+        assert_eq!(
+            stored_json["data"],
+            serde_json::to_value(Person::default()).unwrap()
+        );
+
         doc_0.remove().unwrap();
     }
 
     #[test]
     fn load_should_fail_if_doc_is_encrypted() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0
             .update(person.clone())
             .store_encrypted(|_data| Ok(b"ENCRYPTED_DATA".to_vec()))
             .unwrap();
 
-        let failed_doc = Document::<Person>::load("Person", &doc_0.uuid).unwrap_err();
+        let failed_doc = Document::<Person>::load(&store, "Person", &doc_0.uuid).unwrap_err();
 
         let received_err_msg = &failed_doc.message;
 
@@ -836,12 +1331,13 @@ mod tests {
 
     #[test]
     fn store_should_fail_if_doc_is_encrypted() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0
             .update(person.clone())
@@ -865,12 +1361,13 @@ mod tests {
 
     #[test]
     fn should_be_able_to_query_document_for_its_decrypted_state() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "Duderino".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0
             .update(person.clone())
@@ -879,7 +1376,7 @@ mod tests {
 
         assert!(!doc_0.has_been_decrypted());
 
-        let mut doc_1 = Document::<Person>::decrypt_load(&doc_0.label, &doc_0.uuid, |_| {
+        let mut doc_1 = Document::<Person>::decrypt_load(&store, &doc_0.label, &doc_0.uuid, |_| {
             let decrypted_data = serde_json::to_vec(&person).unwrap();
 
             Ok(decrypted_data)
@@ -903,12 +1400,13 @@ mod tests {
 
     #[test]
     fn should_be_able_to_store_doc_with_no_changes() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "duder".to_owned(),
         };
 
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0.update(person.clone()).store().unwrap();
 
@@ -922,18 +1420,21 @@ mod tests {
 
     #[test]
     fn should_not_overwrite_with_multiple_calls_to_store_encrypted() {
+        let store = test_store();
         let person = Person {
             age: 21,
             name: "duder".to_string(),
         };
-        let mut doc_0 = Document::new("Person");
+        let mut doc_0 = Document::new("Person").with_store(&store);
 
         doc_0
             .update(person.clone())
-            .store_encrypted(|d| Ok(d))
+            .store_encrypted(|d| Ok(d.to_vec()))
             .expect("Failed to store");
 
-        doc_0.store_encrypted(|d| Ok(d)).expect("Failed to store");
+        doc_0
+            .store_encrypted(|d| Ok(d.to_vec()))
+            .expect("Failed to store");
 
         assert_eq!(doc_0.data.name, "duder".to_string());
         assert_eq!(doc_0.data.age, 21);
@@ -941,6 +1442,509 @@ mod tests {
         doc_0.remove().unwrap();
     }
 
+    #[test]
+    fn should_save_and_load_doc_with_memory_store() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let person = Person {
+            age: 21,
+            name: "duder".to_owned(),
+        };
+
+        let mut doc_0 = Document::new("Person").with_store(&store);
+        doc_0.update(person.clone()).store().unwrap();
+
+        let doc_1 = Document::<Person>::load(&store, "Person", doc_0.read_uuid()).unwrap();
+
+        assert_eq!(doc_1.read_data().unwrap(), &person);
+        assert_eq!(
+            Document::<Person>::get_all_uuids(&store, "Person").unwrap(),
+            vec![doc_0.get_uuid()]
+        );
+        assert!(
+            !Document::<Person>::doc_exists(&test_store(), "Person", doc_0.read_uuid()).unwrap()
+        );
+
+        doc_0.remove().unwrap();
+
+        assert!(Document::<Person>::get_all_uuids(&store, "Person")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn should_keep_prior_revisions() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut doc_0 = Document::new("Person").with_store(&store);
+
+        for age in 1..=3 {
+            doc_0
+                .update(Person {
+                    age,
+                    name: "duder".to_owned(),
+                })
+                .store()
+                .unwrap();
+        }
+
+        let history: Vec<Person> = doc_0
+            .history()
+            .iter()
+            .map(|r| r.read_data(|d| Ok(d.clone())).unwrap())
+            .collect();
+
+        assert_eq!(
+            history.iter().map(|p| p.age).collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        assert_eq!(doc_0.history()[1].rev, doc_0.rev_history[1]);
+
+        let loaded_doc = Document::<Person>::load(&store, "Person", doc_0.read_uuid()).unwrap();
+
+        assert_eq!(loaded_doc.history(), doc_0.history());
+    }
+
+    #[test]
+    fn should_only_keep_revisions_allowed_by_retention_policy() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut doc_0 = Document::new("Person").with_store(&store);
+
+        doc_0.set_retention(RetentionPolicy {
+            max_revisions: 2,
+            max_age: None,
+        });
+
+        for age in 1..=5 {
+            doc_0
+                .update(Person {
+                    age,
+                    name: "duder".to_owned(),
+                })
+                .store()
+                .unwrap();
+        }
+
+        let kept_seqs: Vec<_> = doc_0.history().iter().map(|r| r.seq).collect();
+
+        assert_eq!(kept_seqs, vec![3, 4]);
+
+        doc_0.set_retention(RetentionPolicy {
+            max_revisions: 0,
+            max_age: None,
+        });
+        doc_0.store().unwrap();
+
+        assert!(doc_0.history().is_empty());
+    }
+
+    #[test]
+    fn should_restore_prior_encrypted_revision() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut doc_0 = Document::new("Person").with_store(&store);
+
+        doc_0
+            .update(Person {
+                age: 21,
+                name: "duder".to_owned(),
+            })
+            .store_encrypted(|d| Ok(d.to_vec()))
+            .unwrap();
+        doc_0
+            .update(Person {
+                age: 22,
+                name: "duder".to_owned(),
+            })
+            .store_encrypted(|d| Ok(d.to_vec()))
+            .unwrap();
+
+        let first_rev = doc_0.history()[0].rev.clone();
+
+        doc_0.restore_rev(&first_rev).unwrap();
+
+        let restored_doc = Document::<Person>::decrypt_load(
+            &store,
+            "Person",
+            doc_0.read_uuid(),
+            |d| Ok(d.clone()),
+        )
+        .unwrap();
+
+        assert_eq!(restored_doc.read_data().unwrap().age, 21);
+        assert_eq!(restored_doc.history().len(), 2);
+        assert!(doc_0.restore_rev("not a rev").is_err());
+    }
+
+    #[test]
+    fn should_read_legacy_doc_format() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let legacy_doc = r#"{
+            "label": "Person",
+            "uuid": "legacy",
+            "rev": "REV_1",
+            "data": { "age": 0, "name": "" },
+            "seq": 1,
+            "encrypted_data": [104, 105],
+            "encrypted": true,
+            "has_been_decrypted": false,
+            "rev_history": []
+        }"#;
+
+        store
+            .write("Person", "legacy", legacy_doc.as_bytes())
+            .unwrap();
+
+        let doc = Document::<Person>::decrypt_load(&store, "Person", "legacy", |encrypted_data| {
+            assert_eq!(encrypted_data, &b"hi".to_vec());
+
+            Ok(serde_json::to_vec(&Person {
+                age: 21,
+                name: "duder".to_owned(),
+            })
+            .unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(doc.format(), crate::LEGACY_FORMAT);
+        assert_eq!(doc.read_data().unwrap().age, 21);
+
+        assert!(crate::upgrade_doc_format(&store, "Person", "legacy").unwrap());
+        assert!(!crate::upgrade_doc_format(&store, "Person", "legacy").unwrap());
+
+        let upgraded: serde_json::Value =
+            serde_json::from_slice(&store.read("Person", "legacy").unwrap()).unwrap();
+
+        assert_eq!(upgraded["encrypted_data"], "aGk=");
+        assert_eq!(upgraded["rev"], "REV_1");
+    }
+
+    #[test]
+    fn should_write_payloads_as_base64() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut doc = Document::<Person>::new("Person").with_store(&store);
+
+        doc.store_encrypted(|_| Ok(vec![104, 105])).unwrap();
+        doc.store_encrypted(|_| Ok(vec![104, 105, 33])).unwrap();
+
+        let stored: serde_json::Value =
+            serde_json::from_slice(&store.read("Person", &doc.get_uuid()).unwrap()).unwrap();
+
+        assert_eq!(stored["format"], crate::CURRENT_FORMAT);
+        assert_eq!(stored["encrypted_data"], "aGkh");
+        assert_eq!(stored["revisions"][0]["payload"], "aGk=");
+
+        let loaded = Document::<Person>::decrypt_load(&store, "Person", &doc.get_uuid(), |d| {
+            assert_eq!(d, &b"hi!".to_vec());
+
+            Ok(serde_json::to_vec(&Person::default()).unwrap())
+        })
+        .unwrap();
+
+        assert_eq!(loaded.history()[0].payload(), &b"hi".to_vec());
+    }
+
+    #[test]
+    fn should_leave_tombstone_when_deleted() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let person = Person {
+            age: 21,
+            name: "duder".to_owned(),
+        };
+        let mut doc = Document::new("Person").with_store(&store);
+
+        doc.update(person.clone()).store().unwrap();
+        doc.store().unwrap();
+        doc.delete().unwrap();
+
+        let uuid = doc.get_uuid();
+        let mut tombstone = Document::<Person>::load(&store, "Person", &uuid).unwrap();
+
+        assert!(tombstone.is_deleted());
+        assert_eq!(tombstone.read_data().unwrap(), &Person::default());
+        assert!(tombstone.history().is_empty());
+        assert!(!Document::<Person>::live_doc_exists(&store, "Person", &uuid).unwrap());
+        assert!(Document::<Person>::load_tombstone(&store, "Person", &uuid)
+            .unwrap()
+            .is_some());
+
+        tombstone.update(person.clone()).store().unwrap();
+
+        assert!(Document::<Person>::live_doc_exists(&store, "Person", &uuid).unwrap());
+        assert_eq!(
+            Document::<Person>::load(&store, "Person", &uuid)
+                .unwrap()
+                .read_data()
+                .unwrap(),
+            &person
+        );
+    }
+
+    #[test]
+    fn should_keep_data_of_trashed_docs() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let person = Person {
+            age: 21,
+            name: "duder".to_owned(),
+        };
+        let mut doc = Document::new("Person").with_store(&store);
+
+        doc.update(person.clone()).store().unwrap();
+        doc.trash().unwrap();
+
+        let uuid = doc.get_uuid();
+        let mut trashed = Document::<Person>::load(&store, "Person", &uuid).unwrap();
+
+        assert!(trashed.is_trashed());
+        assert!(!trashed.is_deleted());
+        assert_eq!(trashed.read_data().unwrap(), &person);
+        assert!(trashed.history().is_empty());
+        assert!(!Document::<Person>::live_doc_exists(&store, "Person", &uuid).unwrap());
+        assert_eq!(
+            Document::<Person>::read_trashed_at(&store, "Person", &uuid).unwrap(),
+            trashed.trashed_at()
+        );
+
+        trashed.restore_from_trash().unwrap();
+
+        assert!(Document::<Person>::live_doc_exists(&store, "Person", &uuid).unwrap());
+        assert!(trashed.restore_from_trash().is_err());
+
+        trashed.delete().unwrap();
+
+        assert!(!trashed.is_trashed());
+        assert!(trashed.trash().is_err());
+    }
+
+    #[test]
+    fn should_only_purge_trash_past_horizon() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut trashed_doc = Document::<Person>::new("Person").with_store(&store);
+        let mut live_doc = Document::<Person>::new("Person").with_store(&store);
+
+        trashed_doc.store().unwrap();
+        trashed_doc.trash().unwrap();
+        live_doc.store().unwrap();
+        // A damaged doc is skipped
+        store.write("Person", "broken", b"{").unwrap();
+
+        let uuids = store.list("Person").unwrap();
+        let purged =
+            Document::<Person>::purge_trash(&store, "Person", &uuids, Duration::from_secs(3600))
+                .unwrap();
+
+        assert!(purged.is_empty());
+
+        let purged =
+            Document::<Person>::purge_trash(&store, "Person", &uuids, Duration::ZERO).unwrap();
+        let uuid = trashed_doc.get_uuid();
+
+        assert_eq!(purged, vec![uuid.clone()]);
+        assert!(Document::<Person>::load_tombstone(&store, "Person", &uuid)
+            .unwrap()
+            .is_some());
+        assert!(
+            Document::<Person>::live_doc_exists(&store, "Person", &live_doc.get_uuid()).unwrap()
+        );
+    }
+
+    #[test]
+    fn should_only_purge_tombstones_past_horizon() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut deleted_doc = Document::<Person>::new("Person").with_store(&store);
+        let mut live_doc = Document::<Person>::new("Person").with_store(&store);
+
+        deleted_doc.store().unwrap();
+        deleted_doc.delete().unwrap();
+        live_doc.store().unwrap();
+
+        let uuids = store.list("Person").unwrap();
+        let purged = Document::<Person>::purge_tombstones(
+            &store,
+            "Person",
+            &uuids,
+            Duration::from_secs(3600),
+        )
+        .unwrap();
+
+        assert!(purged.is_empty());
+
+        let purged =
+            Document::<Person>::purge_tombstones(&store, "Person", &uuids, Duration::ZERO).unwrap();
+
+        assert_eq!(purged, vec![deleted_doc.get_uuid()]);
+        assert_eq!(store.list("Person").unwrap(), vec![live_doc.get_uuid()]);
+    }
+
+    fn copy_to_device(doc: &Document<Person>, store: &DocumentStoreRef) -> Document<Person> {
+        let doc_json = serde_json::to_value(doc).unwrap();
+
+        serde_json::from_value::<Document<Person>>(doc_json)
+            .unwrap()
+            .with_store(store)
+    }
+
+    #[test]
+    fn should_order_copies_made_on_different_devices() {
+        let store_a: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let store_b: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let store_c: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let person = Person {
+            age: 21,
+            name: "duder".to_owned(),
+        };
+
+        let mut doc_a = Document::new("Person").with_store(&store_a);
+        doc_a.update(person.clone()).store().unwrap();
+
+        let mut doc_b = copy_to_device(&doc_a, &store_b);
+        doc_b.store_as_is().unwrap();
+        let mut doc_c = copy_to_device(&doc_a, &store_c);
+        doc_c.store_as_is().unwrap();
+
+        assert_eq!(doc_c.check_for_conflict(&doc_b), Causality::Equal);
+
+        doc_b
+            .update(Person {
+                age: 30,
+                ..person.clone()
+            })
+            .store()
+            .unwrap();
+
+        assert_eq!(doc_c.check_for_conflict(&doc_b), Causality::Ancestor);
+        assert_eq!(doc_b.check_for_conflict(&doc_a), Causality::Descendant);
+
+        let mut doc_c = copy_to_device(&doc_b, &store_c);
+        doc_c.store_as_is().unwrap();
+        doc_c
+            .update(Person {
+                age: 31,
+                ..person.clone()
+            })
+            .store()
+            .unwrap();
+
+        // A never saw B's update directly, but C's copy still descends from A's
+        assert_eq!(doc_a.check_for_conflict(&doc_c), Causality::Ancestor);
+
+        doc_a
+            .update(Person {
+                age: 40,
+                ..person.clone()
+            })
+            .store()
+            .unwrap();
+
+        assert_eq!(doc_a.check_for_conflict(&doc_c), Causality::Concurrent);
+
+        let err = copy_to_device(&doc_c, &store_a).store_as_is().unwrap_err();
+
+        assert!(err.is_conflict_err());
+    }
+
+    #[test]
+    fn should_fall_back_to_rev_history_for_docs_without_versions() {
+        let mut doc_0 = Document::<Person>::new("Person");
+        doc_0.rev = "REV_2".to_owned();
+        doc_0.rev_history = vec!["REV_1".to_owned()];
+
+        let mut doc_1 = doc_0.clone();
+        doc_1.rev = "REV_3".to_owned();
+        doc_1.rev_history = vec!["REV_1".to_owned(), "REV_2".to_owned()];
+
+        let mut doc_2 = doc_0.clone();
+        doc_2.rev = "REV_4".to_owned();
+
+        assert_eq!(doc_0.check_for_conflict(&doc_0.clone()), Causality::Equal);
+        assert_eq!(doc_0.check_for_conflict(&doc_1), Causality::Ancestor);
+        assert_eq!(doc_1.check_for_conflict(&doc_0), Causality::Descendant);
+        assert_eq!(doc_1.check_for_conflict(&doc_2), Causality::Concurrent);
+    }
+
+    #[test]
+    fn should_find_and_repair_broken_rev_histories() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let mut doc_0 = Document::<Person>::new("Person").with_store(&store);
+
+        doc_0.store().unwrap();
+        doc_0.store().unwrap();
+        assert_eq!(doc_0.rev_history_problem(), None);
+
+        let mut doc_1 = doc_0.clone();
+        doc_1.rev_history.push(doc_1.rev.clone());
+        assert!(doc_1.rev_history_problem().is_some());
+
+        let mut doc_2 = doc_0.clone();
+        doc_2.rev_history.push(doc_2.rev_history[0].clone());
+        doc_2.rev_history.push("REV_X".to_owned());
+        assert!(doc_2.rev_history_problem().is_some());
+
+        for doc in [&mut doc_1, &mut doc_2] {
+            assert!(doc.repair_rev_history());
+            assert_eq!(doc.rev_history_problem(), None);
+        }
+
+        assert_eq!(doc_1.rev_history, doc_0.rev_history);
+        assert_eq!(doc_2.seq, 3);
+
+        let mut doc_3 = doc_0.clone();
+        doc_3.rev = String::new();
+        assert!(doc_3.rev_history_problem().is_some());
+        assert!(!doc_3.repair_rev_history());
+    }
+
+    #[test]
+    fn should_keep_a_sibling_per_conflicting_rev() {
+        let store: DocumentStoreRef = std::sync::Arc::new(crate::MemoryStore::new());
+        let person = Person {
+            age: 21,
+            name: "duder".to_owned(),
+        };
+
+        let mut doc_0 = Document::new("Person").with_store(&store);
+        doc_0.update(person.clone()).store().unwrap();
+
+        let uuid = doc_0.get_uuid();
+        let mut conf_uuids = vec![];
+
+        for age in [30, 40] {
+            let mut conf_doc = doc_0.clone();
+            conf_doc.update(Person {
+                age,
+                ..person.clone()
+            });
+            conf_doc.rev = format!("REV{}", age);
+            conf_doc.conflict_store().unwrap();
+            conf_uuids.push(conf_doc.get_uuid());
+
+            assert_eq!(
+                Document::<Person>::conflict_parent_uuid(&conf_doc.get_uuid()),
+                Some(uuid.as_str())
+            );
+        }
+
+        // Storing the same conflict twice does not add another sibling
+        let mut conf_doc = Document::<Person>::load(&store, "Person", &conf_uuids[0]).unwrap();
+        conf_doc.append_uuid(&uuid);
+        conf_doc.conflict_store().unwrap();
+        conf_uuids.sort();
+
+        assert_eq!(
+            Document::<Person>::get_conflict_uuids(&store, "Person", &uuid).unwrap(),
+            conf_uuids
+        );
+        assert_eq!(
+            Document::<Person>::get_all_conflict_uuids(&store, "Person").unwrap(),
+            vec![uuid.clone()]
+        );
+        assert!(Document::<Person>::get_all_uuids(&store, "Person")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            Document::<Person>::conflict_parent_uuid(&format!("{}_CONF", uuid)),
+            Some(uuid.as_str())
+        );
+        assert_eq!(Document::<Person>::conflict_parent_uuid(&uuid), None);
+    }
+
     // #[test]
     // fn should_alksdfjasdlkfj() {
     //     let person = Person {
@@ -954,6 +1958,6 @@ mod tests {
     //         .expect("Failed to store");
 
     //     let mut loaded_doc =
-    //         Document::<Person>::decrypt_load(&doc_0.label, &doc_0.uuid, |i| Ok(i.clone())).unwrap();
+    //         Document::<Person>::decrypt_load(&default_store(), &doc_0.label, &doc_0.uuid, |i| Ok(i.clone())).unwrap();
     // }
 }
@@ -0,0 +1,634 @@
+use crate::{LockMode, StoreLock};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+use utility::{generate_id, LocalLedgerError};
+
+/// Shared handle to a storage backend.  Documents hold on to one of these so they know where to
+/// write themselves.
+pub type DocumentStoreRef = Arc<dyn DocumentStore>;
+
+/// Storage backend used by `Document`.  Documents are grouped by label and addressed by uuid.
+pub trait DocumentStore: Debug + Send + Sync {
+    /// Reads the raw contents of a document
+    fn read(&self, label: &str, uuid: &str) -> Result<Vec<u8>, LocalLedgerError>;
+
+    /// Writes the raw contents of a document, replacing whatever was there before
+    fn write(&self, label: &str, uuid: &str, contents: &[u8]) -> Result<(), LocalLedgerError>;
+
+    fn remove(&self, label: &str, uuid: &str) -> Result<(), LocalLedgerError>;
+
+    fn exists(&self, label: &str, uuid: &str) -> Result<bool, LocalLedgerError>;
+
+    /// Lists the uuids of every document stored under `label`
+    fn list(&self, label: &str) -> Result<Vec<String>, LocalLedgerError>;
+
+    /// Directory holding the documents for `label`.  Stores that do not live on the filesystem
+    /// return an error.
+    fn dir_path(&self, label: &str) -> Result<PathBuf, LocalLedgerError>;
+
+    /// Id of the device this store lives on.  Documents use it to version their updates, so it has
+    /// to stay the same for the lifetime of the store and must not be shared with other devices.
+    fn device_id(&self) -> Result<String, LocalLedgerError>;
+
+    /// Cleans up anything an interrupted write may have left behind under `label`.  Returns the
+    /// names of the files that were cleaned up.
+    fn recover(&self, _label: &str) -> Result<Vec<String>, LocalLedgerError> {
+        Ok(vec![])
+    }
+
+    /// Takes an advisory lock on `label` without waiting for it.  Fails with a
+    /// `LocalLedgerError::locked` error if another process holds it in a conflicting mode.
+    fn try_lock(&self, _label: &str, _mode: LockMode) -> Result<StoreLock, LocalLedgerError> {
+        Ok(StoreLock::none())
+    }
+
+    /// Waits until no other process is writing to `label`, then keeps others from writing
+    /// until the returned lock is dropped
+    fn lock_writes(&self, _label: &str) -> Result<StoreLock, LocalLedgerError> {
+        Ok(StoreLock::none())
+    }
+
+    /// Removes every document stored under `label`, along with anything else the store keeps
+    /// for it
+    fn remove_label(&self, label: &str) -> Result<(), LocalLedgerError> {
+        self.list(label)?
+            .iter()
+            .try_for_each(|uuid| self.remove(label, uuid))
+    }
+}
+
+/// File, in the root of a `FileSystemStore`, holding the id of the device.  It lives outside of
+/// the label directories so it never gets synced to another device.
+const DEVICE_ID_FILE_NAME: &str = ".device_id";
+
+/// Extension of the file a document is written to before it is renamed into place
+const WRITE_TEMP_EXTENSION: &str = "tmp";
+
+/// Extension given to documents that could not be parsed during recovery
+const CORRUPT_EXTENSION: &str = "corrupt";
+
+/// Extension of the file, next to a label directory, that guards the label as a whole
+const LOCK_EXTENSION: &str = "lock";
+
+/// Extension of the file, next to a label directory, that serializes writes to the label
+const WRITE_LOCK_EXTENSION: &str = "write.lock";
+
+/// Returns the store used by default, which keeps documents under `~/.fizzgig/<label>`
+pub fn default_store() -> DocumentStoreRef {
+    Arc::new(FileSystemStore::home())
+}
+
+/// Stores each document as `<root>/<label>/<uuid>.json`
+#[derive(Debug, Clone)]
+pub struct FileSystemStore {
+    root: Option<PathBuf>,
+}
+
+impl FileSystemStore {
+    /// Creates a store rooted at the given directory
+    pub fn new(root: PathBuf) -> Self {
+        Self { root: Some(root) }
+    }
+
+    /// Creates a store rooted at `~/.fizzgig`
+    pub fn home() -> Self {
+        let root = dirs::home_dir().map(|mut d| {
+            d.push(".fizzgig");
+            d
+        });
+
+        Self { root }
+    }
+
+    fn label_dir(&self, label: &str) -> Result<PathBuf, LocalLedgerError> {
+        let mut path = self
+            .root
+            .clone()
+            .ok_or(LocalLedgerError::new("Failed to get directory path"))?;
+
+        path.push(label);
+
+        Ok(path)
+    }
+
+    fn doc_path(&self, label: &str, uuid: &str) -> Result<PathBuf, LocalLedgerError> {
+        let mut path = self.label_dir(label)?;
+        path.push(format!("{}.json", uuid));
+
+        Ok(path)
+    }
+
+    /// Lock files live next to the label directory, so they are never synced with its documents
+    fn lock_path(&self, label: &str, extension: &str) -> Result<PathBuf, LocalLedgerError> {
+        let root = self
+            .root
+            .clone()
+            .ok_or(LocalLedgerError::new("Failed to get directory path"))?;
+
+        std::fs::create_dir_all(&root).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to create document directory: {}", err))
+        })?;
+
+        Ok(root.join(format!("{}.{}", label, extension)))
+    }
+
+    fn get_or_create_label_dir(&self, label: &str) -> Result<PathBuf, LocalLedgerError> {
+        let path = self.label_dir(label)?;
+
+        std::fs::create_dir_all(&path).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to create document directory: {}", err))
+        })?;
+
+        Ok(path)
+    }
+}
+
+impl DocumentStore for FileSystemStore {
+    fn read(&self, label: &str, uuid: &str) -> Result<Vec<u8>, LocalLedgerError> {
+        let path = self.doc_path(label, uuid)?;
+        let mut doc_file = std::fs::File::open(path)
+            .map_err(|err| LocalLedgerError::new(&format!("Document not found: {}", err)))?;
+        let mut contents = vec![];
+
+        doc_file
+            .read_to_end(&mut contents)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to load file: {}", err)))?;
+
+        Ok(contents)
+    }
+
+    fn write(&self, label: &str, uuid: &str, contents: &[u8]) -> Result<(), LocalLedgerError> {
+        // Write the new contents next to the doc, flush them to disk and then swap them in with a
+        // rename.  A crash at any point leaves either the old or the new doc, never half of one.
+        let dir = self.get_or_create_label_dir(label)?;
+        let path = dir.join(format!("{}.json", uuid));
+        let temp_path = dir.join(format!("{}.json.{}", uuid, WRITE_TEMP_EXTENSION));
+
+        let write_result = write_synced(&temp_path, contents)
+            .and_then(|_| std::fs::rename(&temp_path, &path))
+            .and_then(|_| sync_dir(&dir));
+
+        write_result.map_err(|err| {
+            let _ = std::fs::remove_file(&temp_path);
+
+            LocalLedgerError::new(&format!("Failed to save doc: {}", err))
+        })
+    }
+
+    fn remove(&self, label: &str, uuid: &str) -> Result<(), LocalLedgerError> {
+        let path = self.doc_path(label, uuid)?;
+
+        std::fs::remove_file(path)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to remove doc: {}", err)))
+    }
+
+    fn exists(&self, label: &str, uuid: &str) -> Result<bool, LocalLedgerError> {
+        Ok(self.doc_path(label, uuid)?.exists())
+    }
+
+    fn list(&self, label: &str) -> Result<Vec<String>, LocalLedgerError> {
+        let path = self.get_or_create_label_dir(label)?;
+        let mut uuids = vec![];
+
+        for dir_entry_result in
+            std::fs::read_dir(path).map_err(|e| LocalLedgerError::new(&e.to_string()))?
+        {
+            let dir_entry = dir_entry_result.map_err(|e| {
+                LocalLedgerError::new(&format!("Failed to find ledger uuids: {}", e))
+            })?;
+            let path = dir_entry.path();
+
+            if path.is_dir() || path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let uuid = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or(LocalLedgerError::new("Failed to find document uuid"))?;
+
+            uuids.push(uuid.to_string());
+        }
+
+        Ok(uuids)
+    }
+
+    fn dir_path(&self, label: &str) -> Result<PathBuf, LocalLedgerError> {
+        self.label_dir(label)
+    }
+
+    fn device_id(&self) -> Result<String, LocalLedgerError> {
+        let root = self
+            .root
+            .clone()
+            .ok_or(LocalLedgerError::new("Failed to get directory path"))?;
+        let path = root.join(DEVICE_ID_FILE_NAME);
+
+        if let Ok(device_id) = std::fs::read_to_string(&path) {
+            let device_id = device_id.trim();
+
+            if !device_id.is_empty() {
+                return Ok(device_id.to_string());
+            }
+        }
+
+        let device_id = generate_id();
+
+        std::fs::create_dir_all(&root)
+            .and_then(|_| write_synced(&path, device_id.as_bytes()))
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to save device id: {}", err)))?;
+
+        Ok(device_id)
+    }
+
+    fn try_lock(&self, label: &str, mode: LockMode) -> Result<StoreLock, LocalLedgerError> {
+        let path = self.lock_path(label, LOCK_EXTENSION)?;
+
+        StoreLock::acquire(&path, label, mode, false)
+    }
+
+    fn lock_writes(&self, label: &str) -> Result<StoreLock, LocalLedgerError> {
+        let path = self.lock_path(label, WRITE_LOCK_EXTENSION)?;
+
+        StoreLock::acquire(&path, label, LockMode::Exclusive, true)
+    }
+
+    fn remove_label(&self, label: &str) -> Result<(), LocalLedgerError> {
+        let path = self.label_dir(label)?;
+
+        if path.exists() {
+            std::fs::remove_dir_all(&path).map_err(|err| {
+                LocalLedgerError::new(&format!("Failed to remove document directory: {}", err))
+            })?;
+        }
+
+        // Lock files may still be held open, and are harmless if they stay behind
+        for extension in [LOCK_EXTENSION, WRITE_LOCK_EXTENSION] {
+            let _ = std::fs::remove_file(self.lock_path(label, extension)?);
+        }
+
+        Ok(())
+    }
+
+    fn recover(&self, label: &str) -> Result<Vec<String>, LocalLedgerError> {
+        let path = self.get_or_create_label_dir(label)?;
+        let mut recovered = vec![];
+
+        for dir_entry_result in
+            std::fs::read_dir(path).map_err(|e| LocalLedgerError::new(&e.to_string()))?
+        {
+            let dir_entry = dir_entry_result
+                .map_err(|e| LocalLedgerError::new(&format!("Failed to recover docs: {}", e)))?;
+            let path = dir_entry.path();
+            let file_name = dir_entry.file_name().to_string_lossy().to_string();
+
+            if path.is_dir() {
+                continue;
+            }
+
+            match path.extension().and_then(|e| e.to_str()) {
+                // Leftover from a write that never made it to the rename.  The doc it was meant
+                // to replace is still intact.
+                Some(WRITE_TEMP_EXTENSION) => {
+                    std::fs::remove_file(&path).map_err(|err| {
+                        LocalLedgerError::new(&format!("Failed to remove {}: {}", file_name, err))
+                    })?;
+                }
+
+                // Docs written before writes were atomic can be truncated.  Move them out of the
+                // way instead of deleting them so whatever is left can still be salvaged by hand.
+                Some("json") => {
+                    let contents = std::fs::read(&path).map_err(|err| {
+                        LocalLedgerError::new(&format!("Failed to load file: {}", err))
+                    })?;
+
+                    if serde_json::from_slice::<serde::de::IgnoredAny>(&contents).is_ok() {
+                        continue;
+                    }
+
+                    let corrupt_path = path.with_extension(format!("json.{}", CORRUPT_EXTENSION));
+
+                    std::fs::rename(&path, corrupt_path).map_err(|err| {
+                        LocalLedgerError::new(&format!(
+                            "Failed to quarantine {}: {}",
+                            file_name, err
+                        ))
+                    })?;
+                }
+
+                _ => continue,
+            }
+
+            recovered.push(file_name);
+        }
+
+        Ok(recovered)
+    }
+}
+
+fn write_synced(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Makes sure a rename inside `dir` survives a power loss
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> std::io::Result<()> {
+    // Directories can't be opened as files on windows.  NTFS journals the rename for us.
+    Ok(())
+}
+
+/// Keeps documents in memory.  Nothing is persisted once the store is dropped.  Every
+/// MemoryStore acts as its own device.
+pub struct MemoryStore {
+    docs: RwLock<HashMap<String, BTreeMap<String, Vec<u8>>>>,
+    device_id: String,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self {
+            docs: Default::default(),
+            device_id: generate_id(),
+        }
+    }
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for MemoryStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't dump the stored documents, they can be large and contain secrets
+        f.debug_struct("MemoryStore").finish_non_exhaustive()
+    }
+}
+
+impl DocumentStore for MemoryStore {
+    fn read(&self, label: &str, uuid: &str) -> Result<Vec<u8>, LocalLedgerError> {
+        let docs = self
+            .docs
+            .read()
+            .map_err(|_| LocalLedgerError::new("Memory store is poisoned"))?;
+
+        docs.get(label)
+            .and_then(|label_docs| label_docs.get(uuid))
+            .cloned()
+            .ok_or(LocalLedgerError::new(&format!(
+                "Document not found: {}",
+                uuid
+            )))
+    }
+
+    fn write(&self, label: &str, uuid: &str, contents: &[u8]) -> Result<(), LocalLedgerError> {
+        let mut docs = self
+            .docs
+            .write()
+            .map_err(|_| LocalLedgerError::new("Memory store is poisoned"))?;
+
+        docs.entry(label.to_string())
+            .or_default()
+            .insert(uuid.to_string(), contents.to_vec());
+
+        Ok(())
+    }
+
+    fn remove(&self, label: &str, uuid: &str) -> Result<(), LocalLedgerError> {
+        let mut docs = self
+            .docs
+            .write()
+            .map_err(|_| LocalLedgerError::new("Memory store is poisoned"))?;
+
+        docs.get_mut(label)
+            .and_then(|label_docs| label_docs.remove(uuid))
+            .map(|_| ())
+            .ok_or(LocalLedgerError::new(&format!(
+                "Failed to remove doc: {} not found",
+                uuid
+            )))
+    }
+
+    fn exists(&self, label: &str, uuid: &str) -> Result<bool, LocalLedgerError> {
+        let docs = self
+            .docs
+            .read()
+            .map_err(|_| LocalLedgerError::new("Memory store is poisoned"))?;
+
+        Ok(docs
+            .get(label)
+            .is_some_and(|label_docs| label_docs.contains_key(uuid)))
+    }
+
+    fn list(&self, label: &str) -> Result<Vec<String>, LocalLedgerError> {
+        let docs = self
+            .docs
+            .read()
+            .map_err(|_| LocalLedgerError::new("Memory store is poisoned"))?;
+
+        Ok(docs
+            .get(label)
+            .map(|label_docs| label_docs.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    fn dir_path(&self, _label: &str) -> Result<PathBuf, LocalLedgerError> {
+        Err(LocalLedgerError::new(
+            "Memory store is not backed by a directory",
+        ))
+    }
+
+    fn device_id(&self) -> Result<String, LocalLedgerError> {
+        Ok(self.device_id.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utility::generate_id;
+
+    fn temp_fs_store() -> (FileSystemStore, PathBuf) {
+        let mut root = std::env::temp_dir();
+        root.push(format!("fizzgig-test-{}", generate_id()));
+
+        (FileSystemStore::new(root.clone()), root)
+    }
+
+    fn round_trip(store: &dyn DocumentStore) {
+        store.write("Label", "doc-1", b"hello").unwrap();
+        store.write("Label", "doc-2", b"world").unwrap();
+        store.write("Label", "doc-1", b"hi").unwrap();
+
+        assert_eq!(store.read("Label", "doc-1").unwrap(), b"hi".to_vec());
+        assert!(store.exists("Label", "doc-2").unwrap());
+
+        let mut uuids = store.list("Label").unwrap();
+        uuids.sort();
+
+        assert_eq!(uuids, vec!["doc-1".to_string(), "doc-2".to_string()]);
+
+        store.remove("Label", "doc-2").unwrap();
+
+        assert!(!store.exists("Label", "doc-2").unwrap());
+        assert!(store.read("Label", "doc-2").is_err());
+        assert!(store.list("Other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn memory_store_should_round_trip_docs() {
+        round_trip(&MemoryStore::new());
+    }
+
+    #[test]
+    fn file_system_store_should_round_trip_docs() {
+        let (store, root) = temp_fs_store();
+
+        round_trip(&store);
+
+        let mut expected_dir = root.clone();
+        expected_dir.push("Label");
+
+        assert_eq!(store.dir_path("Label").unwrap(), expected_dir);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_remove_labels() {
+        let (store, root) = temp_fs_store();
+        let _lock = store.try_lock("Label", LockMode::Exclusive).unwrap();
+
+        store.write("Label", "doc-1", b"hello").unwrap();
+        store.write("Other", "doc-1", b"world").unwrap();
+        store.remove_label("Label").unwrap();
+
+        assert!(!root.join("Label").exists());
+        assert!(store.list("Label").unwrap().is_empty());
+        assert_eq!(store.read("Other", "doc-1").unwrap(), b"world".to_vec());
+
+        let memory_store = MemoryStore::new();
+
+        memory_store.write("Label", "doc-1", b"hello").unwrap();
+        memory_store.remove_label("Label").unwrap();
+
+        assert!(memory_store.list("Label").unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_only_list_json_docs() {
+        let (store, root) = temp_fs_store();
+
+        store.write("Label", "doc-1", b"{}").unwrap();
+
+        let mut stray_file = store.dir_path("Label").unwrap();
+        stray_file.push(".DS_Store");
+        std::fs::write(stray_file, b"").unwrap();
+
+        assert_eq!(store.list("Label").unwrap(), vec!["doc-1".to_string()]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_keep_its_device_id() {
+        let (store, root) = temp_fs_store();
+        let device_id = store.device_id().unwrap();
+
+        assert_eq!(
+            FileSystemStore::new(root.clone()).device_id().unwrap(),
+            device_id
+        );
+        assert!(store.list("Label").unwrap().is_empty());
+
+        let (other_store, other_root) = temp_fs_store();
+
+        assert_ne!(other_store.device_id().unwrap(), device_id);
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(other_root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_not_leave_temp_files_behind() {
+        let (store, root) = temp_fs_store();
+
+        store.write("Label", "doc-1", b"{}").unwrap();
+        store.write("Label", "doc-1", b"{\"a\":1}").unwrap();
+
+        let file_names: Vec<_> = std::fs::read_dir(store.dir_path("Label").unwrap())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+
+        assert_eq!(file_names, vec!["doc-1.json".to_string()]);
+        assert_eq!(store.read("Label", "doc-1").unwrap(), b"{\"a\":1}".to_vec());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_lock_labels() {
+        let (store, root) = temp_fs_store();
+
+        let shared_lock = store.try_lock("Person", LockMode::Shared).unwrap();
+        let other_shared_lock = store.try_lock("Person", LockMode::Shared).unwrap();
+        let err = store.try_lock("Person", LockMode::Exclusive).unwrap_err();
+
+        assert!(err.is_locked_err());
+
+        // Locks are per label
+        let _other_label_lock = store.try_lock("Animal", LockMode::Exclusive).unwrap();
+
+        drop(shared_lock);
+        drop(other_shared_lock);
+
+        let _exclusive_lock = store.try_lock("Person", LockMode::Exclusive).unwrap();
+        let err = store.try_lock("Person", LockMode::Shared).unwrap_err();
+
+        assert!(err.is_locked_err());
+        // Lock files stay out of the label directory
+        assert!(root.join("Person.lock").exists());
+        assert!(!root.join("Person").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn file_system_store_should_recover_from_interrupted_writes() {
+        let (store, root) = temp_fs_store();
+
+        store.write("Label", "doc-1", b"{\"a\":1}").unwrap();
+
+        let dir = store.dir_path("Label").unwrap();
+        std::fs::write(dir.join("doc-1.json.tmp"), b"{\"a\":").unwrap();
+        std::fs::write(dir.join("doc-2.json"), b"{\"a\":").unwrap();
+
+        let mut recovered = store.recover("Label").unwrap();
+        recovered.sort();
+
+        assert_eq!(recovered, vec!["doc-1.json.tmp", "doc-2.json"]);
+        assert_eq!(store.read("Label", "doc-1").unwrap(), b"{\"a\":1}".to_vec());
+        assert_eq!(store.list("Label").unwrap(), vec!["doc-1".to_string()]);
+        assert!(dir.join("doc-2.json.corrupt").exists());
+        assert!(store.recover("Label").unwrap().is_empty());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use crate::DocumentStoreRef;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{de, Deserialize, Deserializer};
+use serde_json::Value;
+use utility::LocalLedgerError;
+
+/// Layout written before Documents carried a format version.  Byte payloads are stored as JSON
+/// arrays of numbers.
+pub const LEGACY_FORMAT: u32 = 0;
+/// Byte payloads are stored as base64 strings
+pub const COMPACT_FORMAT: u32 = 1;
+/// Format newly created Documents are written in
+pub const CURRENT_FORMAT: u32 = COMPACT_FORMAT;
+
+/// Reads a byte payload written in any format
+pub(crate) fn deserialize_payload<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Payload {
+        Encoded(String),
+        Bytes(Vec<u8>),
+    }
+
+    match Payload::deserialize(deserializer)? {
+        Payload::Encoded(encoded) => STANDARD.decode(encoded).map_err(de::Error::custom),
+        Payload::Bytes(bytes) => Ok(bytes),
+    }
+}
+
+/// Rewrites the byte payloads of a serialized Document into the layout of `format`
+pub(crate) fn encode_payloads(doc_json: &mut Value, format: u32) -> Result<(), LocalLedgerError> {
+    if format < COMPACT_FORMAT {
+        return Ok(());
+    }
+
+    encode_payload(&mut doc_json["encrypted_data"])?;
+
+    if let Some(revisions) = doc_json["revisions"].as_array_mut() {
+        for revision in revisions.iter_mut() {
+            encode_payload(&mut revision["payload"])?;
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_payload(payload: &mut Value) -> Result<(), LocalLedgerError> {
+    if !payload.is_array() {
+        return Ok(());
+    }
+
+    let bytes: Vec<u8> = serde_json::from_value(payload.take())
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to encode doc payload: {}", err)))?;
+
+    *payload = Value::String(STANDARD.encode(bytes));
+
+    Ok(())
+}
+
+/// Rewrites a stored Document in the current format.  The Document's contents, rev and version
+/// are left untouched, so copies on other devices still compare as equal.  Returns false if the
+/// Document was already up to date.
+pub fn upgrade_doc_format(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+) -> Result<bool, LocalLedgerError> {
+    let _write_lock = store.lock_writes(label)?;
+    let contents = store.read(label, uuid)?;
+    let mut doc_json: Value = serde_json::from_slice(&contents)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to parse doc file: {}", err)))?;
+
+    if !doc_json.is_object() {
+        return Err(LocalLedgerError::new(&format!("Not a doc: {}", uuid)));
+    }
+
+    let format = doc_json["format"].as_u64().unwrap_or(LEGACY_FORMAT as u64);
+
+    if format >= CURRENT_FORMAT as u64 {
+        return Ok(false);
+    }
+
+    encode_payloads(&mut doc_json, CURRENT_FORMAT)?;
+    doc_json["format"] = Value::from(CURRENT_FORMAT);
+
+    let doc_bytes = serde_json::to_vec(&doc_json)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to serialize document: {}", err)))?;
+
+    store.write(label, uuid, &doc_bytes)?;
+
+    Ok(true)
+}
@@ -1,3 +1,13 @@
 mod document;
+mod document_store;
+mod format;
+mod revision;
+mod store_lock;
+mod version_vector;
 
 pub use crate::document::*;
+pub use crate::document_store::*;
+pub use crate::format::{upgrade_doc_format, COMPACT_FORMAT, CURRENT_FORMAT, LEGACY_FORMAT};
+pub use crate::revision::*;
+pub use crate::store_lock::*;
+pub use crate::version_vector::*;
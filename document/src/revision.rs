@@ -0,0 +1,109 @@
+use crate::format::deserialize_payload;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use utility::LocalLedgerError;
+use zeroize::Zeroizing;
+
+/// A prior version of a Document's payload.  Encrypted Documents keep the encrypted bytes, so
+/// nothing is exposed by holding on to old revisions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Revision {
+    pub rev: String,
+    pub seq: i64,
+    /// Unix timestamp (seconds) of when this revision was replaced by a newer one
+    pub replaced_at: u64,
+    /// Schema version of the payload
+    #[serde(default)]
+    pub schema_version: u32,
+    pub encrypted: bool,
+    #[serde(deserialize_with = "deserialize_payload")]
+    payload: Vec<u8>,
+}
+
+impl Revision {
+    pub(crate) fn new(
+        rev: &str,
+        seq: i64,
+        schema_version: u32,
+        encrypted: bool,
+        payload: Vec<u8>,
+    ) -> Self {
+        Self {
+            rev: rev.to_owned(),
+            seq,
+            replaced_at: unix_now(),
+            schema_version,
+            encrypted,
+            payload,
+        }
+    }
+
+    pub(crate) fn payload(&self) -> &Vec<u8> {
+        &self.payload
+    }
+
+    pub(crate) fn set_payload(&mut self, payload: Vec<u8>) {
+        self.payload = payload;
+    }
+
+    /// Reads the data held by this revision.  `decrypt` is only called if the revision is
+    /// encrypted.
+    pub fn read_data<T, F>(&self, decrypt: F) -> Result<T, LocalLedgerError>
+    where
+        T: DeserializeOwned,
+        F: Fn(&Vec<u8>) -> Result<Vec<u8>, LocalLedgerError>,
+    {
+        let bytes = Zeroizing::new(if self.encrypted {
+            decrypt(&self.payload)?
+        } else {
+            self.payload.clone()
+        });
+
+        serde_json::from_slice(&bytes).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to parse revision data: {}", err))
+        })
+    }
+}
+
+/// Controls how many prior revisions a Document holds on to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Maximum number of prior revisions to keep.  Zero turns history off.
+    pub max_revisions: usize,
+    /// Revisions that were replaced longer ago than this are dropped
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_revisions: 10,
+            max_age: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Drops the revisions this policy no longer wants to keep.  `revisions` is ordered oldest
+    /// first.
+    pub(crate) fn apply(&self, revisions: &mut Vec<Revision>) {
+        if let Some(max_age) = self.max_age {
+            let cutoff = unix_now().saturating_sub(max_age.as_secs());
+
+            revisions.retain(|r| r.replaced_at >= cutoff);
+        }
+
+        if revisions.len() > self.max_revisions {
+            let excess = revisions.len() - self.max_revisions;
+
+            revisions.drain(..excess);
+        }
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,73 @@
+use fs2::FileExt;
+use std::{
+    fs::{File, OpenOptions},
+    path::Path,
+};
+use utility::LocalLedgerError;
+
+/// How a lock is shared with other holders
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Any number of holders can share the lock, as long as nobody holds it exclusively
+    Shared,
+    /// Only one holder at a time
+    Exclusive,
+}
+
+/// Advisory lock on the documents of a label.  The lock is released when this is dropped.
+#[derive(Debug, Default)]
+pub struct StoreLock {
+    file: Option<File>,
+}
+
+impl StoreLock {
+    /// A lock that guards nothing.  Used by stores that can not be shared between processes.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Locks the file at `path`, creating it if needed.  When `wait` is false a lock held
+    /// elsewhere fails with a `LocalLedgerError::locked` error instead of blocking.
+    pub(crate) fn acquire(
+        path: &Path,
+        label: &str,
+        mode: LockMode,
+        wait: bool,
+    ) -> Result<Self, LocalLedgerError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .open(path)
+            .map_err(|err| {
+                LocalLedgerError::new(&format!("Failed to open lock file for {}: {}", label, err))
+            })?;
+
+        // Newer std versions have inherent methods with the same names, so call the trait ones
+        let locked = match (mode, wait) {
+            (LockMode::Shared, true) => FileExt::lock_shared(&file),
+            (LockMode::Shared, false) => FileExt::try_lock_shared(&file),
+            (LockMode::Exclusive, true) => FileExt::lock_exclusive(&file),
+            (LockMode::Exclusive, false) => FileExt::try_lock_exclusive(&file),
+        };
+
+        match locked {
+            Ok(()) => Ok(Self { file: Some(file) }),
+            Err(err) if err.raw_os_error() == fs2::lock_contended_error().raw_os_error() => Err(
+                LocalLedgerError::locked(&format!("{} is in use by another process", label)),
+            ),
+            Err(err) => Err(LocalLedgerError::new(&format!(
+                "Failed to lock {}: {}",
+                label, err
+            ))),
+        }
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = FileExt::unlock(file);
+        }
+    }
+}
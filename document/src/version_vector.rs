@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap};
+
+/// How a Document relates to another copy of itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Causality {
+    /// Both copies have seen exactly the same updates
+    Equal,
+    /// This copy is older.  The other copy contains every update this one has.
+    Ancestor,
+    /// This copy is newer.  It contains every update the other copy has.
+    Descendant,
+    /// Both copies were updated independently of each other
+    Concurrent,
+}
+
+/// Per device update counters.  Every time a device stores a Document it bumps its own counter,
+/// which lets copies from any number of devices be ordered causally.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionVector(BTreeMap<String, u64>);
+
+impl VersionVector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an update made by `device_id`
+    pub fn increment(&mut self, device_id: &str) {
+        *self.0.entry(device_id.to_owned()).or_insert(0) += 1;
+    }
+
+    /// Returns the number of updates made by `device_id`
+    pub fn get(&self, device_id: &str) -> u64 {
+        self.0.get(device_id).copied().unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Folds in every update recorded by `other`
+    pub fn merge(&mut self, other: &VersionVector) {
+        for (device_id, count) in other.0.iter() {
+            let entry = self.0.entry(device_id.clone()).or_insert(0);
+
+            *entry = (*entry).max(*count);
+        }
+    }
+
+    /// Compares this vector against `other`
+    pub fn compare(&self, other: &VersionVector) -> Causality {
+        let mut ordering = Ordering::Equal;
+
+        for device_id in self.0.keys().chain(other.0.keys()) {
+            let device_ordering = self.get(device_id).cmp(&other.get(device_id));
+
+            ordering = match (ordering, device_ordering) {
+                (o, Ordering::Equal) => o,
+                (Ordering::Equal, o) => o,
+                (o_1, o_2) if o_1 == o_2 => o_1,
+                _ => return Causality::Concurrent,
+            };
+        }
+
+        match ordering {
+            Ordering::Equal => Causality::Equal,
+            Ordering::Less => Causality::Ancestor,
+            Ordering::Greater => Causality::Descendant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vv(counts: &[(&str, u64)]) -> VersionVector {
+        let mut v = VersionVector::new();
+
+        for (device_id, count) in counts {
+            for _ in 0..*count {
+                v.increment(device_id);
+            }
+        }
+
+        v
+    }
+
+    #[test]
+    fn should_order_version_vectors() {
+        let base = vv(&[("A", 1)]);
+
+        assert_eq!(base.compare(&vv(&[("A", 1)])), Causality::Equal);
+        assert_eq!(
+            base.compare(&vv(&[("A", 1), ("B", 1)])),
+            Causality::Ancestor
+        );
+        assert_eq!(vv(&[("A", 2)]).compare(&base), Causality::Descendant);
+        assert_eq!(VersionVector::new().compare(&base), Causality::Ancestor);
+        assert_eq!(
+            vv(&[("A", 1), ("B", 1)]).compare(&vv(&[("A", 1), ("C", 1)])),
+            Causality::Concurrent
+        );
+        assert_eq!(
+            vv(&[("A", 2), ("B", 1)]).compare(&vv(&[("A", 1), ("B", 2)])),
+            Causality::Concurrent
+        );
+    }
+
+    #[test]
+    fn should_merge_version_vectors() {
+        let mut v_1 = vv(&[("A", 2), ("B", 1)]);
+        let v_2 = vv(&[("A", 1), ("B", 3), ("C", 1)]);
+
+        v_1.merge(&v_2);
+
+        assert_eq!(v_1, vv(&[("A", 2), ("B", 3), ("C", 1)]));
+        assert_eq!(v_1.compare(&v_2), Causality::Descendant);
+    }
+}
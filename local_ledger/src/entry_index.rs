@@ -0,0 +1,46 @@
+use crate::Migrate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Uuid of the doc the entry index of a ledger is kept in
+pub(crate) const INDEX_DOC_UUID: &str = "INDEX_DOC";
+
+/// Maps the opaque ids entries are stored under back to their names.  The index is kept in a doc
+/// of its own, encrypted like any entry, so entry names never show up on disk.
+///
+/// Names are only ever added.  The name of a deleted entry stays, its tombstone still needs it.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct EntryIndex {
+    names: BTreeMap<String, String>,
+}
+
+impl Migrate for EntryIndex {}
+
+impl EntryIndex {
+    /// Returns the name of the entry stored under `uuid`
+    pub(crate) fn entry_name(&self, uuid: &str) -> Option<&str> {
+        self.names.get(uuid).map(String::as_str)
+    }
+
+    /// Records that `entry_name` is stored under `uuid`.  Returns false if it already was.
+    pub(crate) fn insert(&mut self, uuid: &str, entry_name: &str) -> bool {
+        if self.entry_name(uuid) == Some(entry_name) {
+            return false;
+        }
+
+        self.names.insert(uuid.to_owned(), entry_name.to_owned());
+
+        true
+    }
+
+    /// Adds the names of `other` that are missing from this index.  Returns false if there were
+    /// none.
+    pub(crate) fn union(&mut self, other: &EntryIndex) -> bool {
+        other
+            .names
+            .iter()
+            .fold(false, |changed, (uuid, entry_name)| {
+                self.insert(uuid, entry_name) || changed
+            })
+    }
+}
@@ -0,0 +1,125 @@
+use crate::vault_key::VaultKeys;
+use utility::LocalLedgerError;
+
+/// Scheme used to turn entry names into doc uuids.  Docs are stored under opaque ids, and the
+/// names are kept in the encrypted entry index.  Docs written at scheme 0 used the entry name as
+/// is, docs written at scheme 1 an escaped entry name.
+pub(crate) const NAME_SCHEME: u32 = 2;
+
+const ESCAPED_NAME_SCHEME: u32 = 1;
+
+/// Longest entry name that is accepted, in bytes
+const MAX_NAME_LEN: usize = 200;
+
+/// Checks that `entry_name` can be stored and returns the id of its doc
+pub(crate) fn entry_name_to_uuid(
+    entry_name: &str,
+    keys: &VaultKeys,
+) -> Result<String, LocalLedgerError> {
+    if entry_name.is_empty() {
+        return Err(LocalLedgerError::new("Label cannot be empty"));
+    }
+
+    if entry_name.chars().any(char::is_control) {
+        return Err(LocalLedgerError::new(
+            "Label cannot contain control characters",
+        ));
+    }
+
+    if entry_name.len() > MAX_NAME_LEN {
+        return Err(LocalLedgerError::new("Label is too long"));
+    }
+
+    keys.entry_id(entry_name)
+}
+
+/// Recovers the entry name from the uuid of a doc written with an older name scheme.  The temp
+/// or conflict suffix of the uuid, if any, is returned along with it.
+pub(crate) fn legacy_uuid_to_entry_name(
+    legacy_uuid: &str,
+    name_scheme: u32,
+) -> Result<(String, &str), LocalLedgerError> {
+    if name_scheme >= ESCAPED_NAME_SCHEME {
+        // Escaped names never contain `_`, so the first one starts the suffix
+        let (escaped_name, suffix) = legacy_uuid
+            .find('_')
+            .map_or((legacy_uuid, ""), |i| legacy_uuid.split_at(i));
+
+        return Ok((unescape_entry_name(escaped_name)?, suffix));
+    }
+
+    let entry_name = ["_CONF", "_TEMP"]
+        .iter()
+        .find_map(|suffix| legacy_uuid.strip_suffix(suffix))
+        .unwrap_or(legacy_uuid);
+
+    Ok((entry_name.to_owned(), &legacy_uuid[entry_name.len()..]))
+}
+
+/// Reverses the `%XX` escapes entry names were written with at name scheme 1
+fn unescape_entry_name(escaped_name: &str) -> Result<String, LocalLedgerError> {
+    let invalid = || LocalLedgerError::new(&format!("Invalid entry uuid: {}", escaped_name));
+    let mut bytes = Vec::with_capacity(escaped_name.len());
+    let mut rest = escaped_name.as_bytes();
+
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail.get(..2).ok_or_else(invalid)?;
+            let hex = std::str::from_utf8(hex).map_err(|_| invalid())?;
+
+            bytes.push(u8::from_str_radix(hex, 16).map_err(|_| invalid())?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault_key::{CompositeKey, WrappedVaultKey};
+
+    #[test]
+    fn should_recover_entry_names_from_legacy_uuids() {
+        let cases = [
+            ("my_bank", 0, "my_bank", ""),
+            ("my_bank_CONF", 0, "my_bank", "_CONF"),
+            ("my_bank_TEMP", 0, "my_bank", "_TEMP"),
+            ("my%5Fbank", 1, "my_bank", ""),
+            ("%4Dy Bank_CONF_REV1", 1, "My Bank", "_CONF_REV1"),
+            (
+                "..%2F..%2Fetc%2Fpasswd_TEMP",
+                1,
+                "../../etc/passwd",
+                "_TEMP",
+            ),
+            ("contrase%C3%B1a", 1, "contraseña", ""),
+        ];
+
+        for (legacy_uuid, name_scheme, entry_name, suffix) in cases {
+            assert_eq!(
+                legacy_uuid_to_entry_name(legacy_uuid, name_scheme).unwrap(),
+                (entry_name.to_owned(), suffix)
+            );
+        }
+
+        assert!(legacy_uuid_to_entry_name("%4", 1).is_err());
+        assert!(legacy_uuid_to_entry_name("%ZZ", 1).is_err());
+    }
+
+    #[test]
+    fn should_reject_illegal_entry_names() {
+        let (_, vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+        let keys = VaultKeys::new(vault_key, CompositeKey::new("master_password".to_owned()));
+
+        assert!(entry_name_to_uuid("", &keys).is_err());
+        assert!(entry_name_to_uuid("tab\there", &keys).is_err());
+        assert!(entry_name_to_uuid(&"a".repeat(MAX_NAME_LEN + 1), &keys).is_err());
+        assert!(entry_name_to_uuid("../../etc/passwd", &keys).is_ok());
+    }
+}
@@ -0,0 +1,68 @@
+use document::DocumentStoreRef;
+use serde::{Deserialize, Serialize};
+use utility::LocalLedgerError;
+
+/// What is wrong with a doc, see `LocalLedger::verify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProblemKind {
+    /// The doc can not be parsed
+    CorruptJson,
+    /// The payload of the doc can not be decrypted with the vault key
+    Undecryptable,
+    /// A doc a merge stored temporarily, that the merge never got to
+    OrphanedTemp,
+    /// A conflicting copy of a doc that no longer exists
+    OrphanedConflict,
+    /// The rev, seq and rev history of the doc do not add up
+    BrokenRevHistory,
+    /// The meta doc can not be read, or does not hold the vault key the ledger was opened with
+    MetaDocMismatch,
+}
+
+/// A problem `LocalLedger::verify` found with a doc of the ledger
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IntegrityProblem {
+    /// Uuid the doc is stored under
+    pub uuid: String,
+    /// Name of the entry the doc belongs to, if it is known
+    pub entry_name: Option<String>,
+    pub kind: ProblemKind,
+    pub detail: String,
+}
+
+/// What `LocalLedger::repair` did about a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairOutcome {
+    /// The doc was moved out of the ledger, see `quarantine_label`
+    Quarantined,
+    /// The doc was fixed in place
+    Fixed,
+    /// Nothing could be done about it safely
+    NotRepaired,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairedProblem {
+    pub problem: IntegrityProblem,
+    pub outcome: RepairOutcome,
+}
+
+/// Label the docs that were taken out of the ledger `label` are kept under, so whatever is left
+/// of them can still be salvaged by hand
+pub(crate) fn quarantine_label(label: &str) -> String {
+    format!("{}.quarantine", label)
+}
+
+/// Moves the doc `uuid` of the ledger `label` to the quarantine, as is
+pub(crate) fn quarantine_doc(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+) -> Result<(), LocalLedgerError> {
+    let contents = store.read(label, uuid)?;
+
+    store.write(&quarantine_label(label), uuid, &contents)?;
+    store.remove(label, uuid)
+}
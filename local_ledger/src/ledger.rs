@@ -1,102 +1,310 @@
-use crate::LedgerDump;
+use crate::{
+    entry_index::{EntryIndex, INDEX_DOC_UUID},
+    entry_name::{entry_name_to_uuid, legacy_uuid_to_entry_name, NAME_SCHEME},
+    integrity::{
+        quarantine_doc, quarantine_label, IntegrityProblem, ProblemKind, RepairOutcome,
+        RepairedProblem,
+    },
+    merge_preview::{EntryChange, MergePreview, MetaDocCompatibility},
+    migrate::migrate_payload,
+    rekey::{commit_staged_docs, discard_staged_docs, recover_rekey, stage_doc, staging_label},
+    search::{SearchHit, SearchIndex},
+    sharing::{decrypt_with, encrypt_to, identity_from_key, public_key_of, SharedEntry},
+    vault_key::{CompositeKey, VaultKeys, WrappedVaultKey},
+    LedgerDump, Migrate, Searchable,
+};
 use age::secrecy::{ExposeSecret, Secret};
-use document::Document;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use document::{
+    default_store, upgrade_doc_format, Causality, Document, DocumentStoreRef, LockMode,
+    RetentionPolicy, StoreLock,
+};
 use pwhash::bcrypt;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use std::{
-    fmt::Debug,
-    io::{Read, Write},
-    num::NonZeroUsize,
-    path::PathBuf,
-};
+use std::{cell::Cell, fmt::Debug, num::NonZeroUsize, path::PathBuf, time::Duration};
 use tokio_stream::{Stream, StreamExt};
 use utility::LocalLedgerError;
+use zeroize::Zeroizing;
 
 const META_DOC_UUID: &str = "META_DOC";
 
+/// The entry index only ever grows, older copies of it are of no use
+const INDEX_RETENTION: RetentionPolicy = RetentionPolicy {
+    max_revisions: 0,
+    max_age: None,
+};
+
 #[derive(Debug)]
 pub struct LocalLedger<T> {
     // This is the name of the ledger, but also functions as the label that is used for the
     // Documents managed by the ledger
     pub name: String,
+    // Holds decrypted entries.  Entry types wipe their secrets when dropped, as they are on
+    // eviction, see `utility::SensitiveString`.
     doc_cache: lru::LruCache<String, Document<T>>,
     meta_doc: Document<LocalLedgerMetaData>,
-    pw: Secret<String>,
+    // Decrypted copy of the entry index, `index_doc` is what it is stored as
+    entry_index: EntryIndex,
+    index_doc: Document<EntryIndex>,
+    search_index: SearchIndex,
+    keys: VaultKeys,
+    store: DocumentStoreRef,
+    retention: RetentionPolicy,
+    // Held for as long as the ledger is open
+    _vault_lock: StoreLock,
+}
+
+/// A prior revision of a ledger entry
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryRevision<T> {
+    pub rev: String,
+    pub seq: i64,
+    /// Unix timestamp (seconds) of when the revision was replaced
+    pub replaced_at: u64,
+    pub data: T,
+}
+
+/// An entry that was moved to the trash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrashedEntry {
+    pub entry_name: String,
+    /// Unix timestamp (seconds) of when the entry was moved to the trash
+    pub trashed_at: u64,
+}
+
+/// How a conflicted ledger entry gets settled
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution<T> {
+    /// Keep the entry as it is on this device
+    Original,
+    /// Keep the conflicting copy with the given rev
+    Sibling(String),
+    /// Replace the entry with a value merged from the copies
+    Merged(T),
 }
 
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 struct LocalLedgerMetaData {
+    /// bcrypt hash of the master password.  Only ledgers made before they had a vault key have
+    /// one.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pw_hash: String,
+    #[serde(default)]
+    vault_key: Option<WrappedVaultKey>,
+    /// age X25519 identity of the vault, sealed with the vault key and base64 encoded.  Only
+    /// ledgers that made their identity before it was derived from the vault key have one, see
+    /// `LocalLedger::public_key`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    identity: Option<String>,
+    /// Whether the vault key is a random one.  Ledgers made before they had a vault key get one
+    /// derived from the password, which is replaced by a random one the first time the password
+    /// or keyfile is changed, see `LocalLedger::change_password`.  Meta docs from before this
+    /// was recorded count as derived.
+    #[serde(default)]
+    random_vault_key: bool,
+    /// The derived vault key that was replaced, sealed with the vault key and base64 encoded,
+    /// see `VaultKeys::set_legacy_vault_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    legacy_vault_key: Option<String>,
 }
 
 impl<T> LocalLedger<T>
 where
-    T: Clone + Serialize + DeserializeOwned + Default + Debug,
+    T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate + Searchable,
 {
+    /// Opens the ledger `name` in the default store, creating it if it does not exist yet
     pub fn new(name: &str, ledger_password: String) -> Result<Self, LocalLedgerError> {
+        Self::new_with_store(name, ledger_password, default_store())
+    }
+
+    /// Whether the ledger `name` has been created in `store`
+    pub fn exists(store: &DocumentStoreRef, name: &str) -> Result<bool, LocalLedgerError> {
+        store.exists(name, META_DOC_UUID)
+    }
+
+    /// Opens the ledger `name` in the given store, creating it if it does not exist yet.  Other
+    /// processes can open the ledger at the same time, their writes are serialized.
+    pub fn new_with_store(
+        name: &str,
+        ledger_password: String,
+        store: DocumentStoreRef,
+    ) -> Result<Self, LocalLedgerError> {
+        Self::new_with_lock_mode(name, ledger_password, store, LockMode::Shared)
+    }
+
+    /// Opens the ledger `name` in the given store, holding its lock in `lock_mode` until the
+    /// ledger is dropped.  Fails with a `LocalLedgerError::locked` error if another process
+    /// holds the lock in a conflicting mode.
+    pub fn new_with_lock_mode(
+        name: &str,
+        ledger_password: String,
+        store: DocumentStoreRef,
+        lock_mode: LockMode,
+    ) -> Result<Self, LocalLedgerError> {
+        Self::new_with_composite_key(name, CompositeKey::new(ledger_password), store, lock_mode)
+    }
+
+    /// Like `new_with_lock_mode`, but unlocks the ledger with `composite_key`.  A ledger created
+    /// with a keyfile in its key requires the keyfile from then on, see `set_keyfile`.
+    pub fn new_with_composite_key(
+        name: &str,
+        composite_key: CompositeKey,
+        store: DocumentStoreRef,
+        lock_mode: LockMode,
+    ) -> Result<Self, LocalLedgerError> {
+        let vault_lock = store.try_lock(name, lock_mode)?;
         let cache_size = match NonZeroUsize::new(100) {
             Some(size) => Ok(size),
             None => Err(LocalLedgerError::new("Failed to initialize doc cache")),
         }?;
         let doc_cache = lru::LruCache::new(cache_size);
-        let maybe_meta_doc = try_load_meta_doc(name);
+        let recovered = {
+            // Only clean up once nobody else is in the middle of a write
+            let _write_lock = store.lock_writes(name)?;
+            let recovered = store.recover(name)?;
 
-        let meta_doc = match maybe_meta_doc {
-            Some(loaded_meta_doc) => {
-                let loaded_pw_hash = loaded_meta_doc.read_data()?.pw_hash.as_str();
-                let correct_pw = bcrypt::verify(ledger_password.as_str(), loaded_pw_hash);
+            recover_rekey(&store, name)?;
+            recovered
+        };
 
-                if !correct_pw {
-                    return Err(LocalLedgerError::new("Incorrect password"));
-                }
+        if !recovered.is_empty() {
+            tracing::warn!("Cleaned up after interrupted writes: {:?}", recovered);
+        }
 
-                loaded_meta_doc
+        let maybe_meta_doc = try_load_meta_doc(&store, name);
+
+        // The password only goes through the KDF once, to unwrap the vault key
+        let (meta_doc, vault_key) = match maybe_meta_doc {
+            Some(mut loaded_meta_doc) => {
+                let meta_data = loaded_meta_doc.read_data()?.clone();
+
+                match &meta_data.vault_key {
+                    Some(wrapped_key) => {
+                        let vault_key = wrapped_key.unwrap(&composite_key)?;
+
+                        (loaded_meta_doc, vault_key)
+                    }
+                    None => {
+                        if composite_key.has_keyfile() {
+                            return Err(LocalLedgerError::new("Ledger does not use a keyfile"));
+                        }
+
+                        let ledger_password = composite_key.password();
+                        let correct_pw = bcrypt::verify(ledger_password, &meta_data.pw_hash);
+
+                        if !correct_pw {
+                            return Err(LocalLedgerError::new("Incorrect password"));
+                        }
+
+                        let (wrapped_key, vault_key) =
+                            WrappedVaultKey::from_legacy(ledger_password, &meta_data.pw_hash)?;
+
+                        loaded_meta_doc.update(LocalLedgerMetaData {
+                            vault_key: Some(wrapped_key),
+                            ..meta_data
+                        });
+                        // Every device derives the same vault key, so adding it is not an update
+                        // that needs to be synced
+                        loaded_meta_doc.store_as_is()?;
+                        tracing::info!("Added a vault key to {}", name);
+
+                        (loaded_meta_doc, vault_key)
+                    }
+                }
             }
             None => {
-                let pw_hash = bcrypt::hash(&ledger_password).map_err(|err| {
-                    LocalLedgerError::new(&format!(
-                        "LocalLedger creation failed: {}",
-                        err.to_string()
-                    ))
-                })?;
-                let mut created_doc = create_meta_doc(name);
-
-                created_doc.update(LocalLedgerMetaData { pw_hash });
+                let (wrapped_key, vault_key) = WrappedVaultKey::generate(&composite_key)?;
+                let mut created_doc = create_meta_doc(&store, name);
+
+                created_doc.update(LocalLedgerMetaData {
+                    vault_key: Some(wrapped_key),
+                    random_vault_key: true,
+                    ..Default::default()
+                });
                 created_doc.store()?;
-                created_doc
+                (created_doc, vault_key)
             }
         };
 
-        Ok(LocalLedger {
+        let mut keys = VaultKeys::new(vault_key, composite_key);
+
+        // Kept to read what was sealed with the derived vault key a random one replaced
+        if let Some(sealed_key) = &meta_doc.read_data()?.legacy_vault_key {
+            let sealed_key = STANDARD.decode(sealed_key).map_err(|err| {
+                LocalLedgerError::new(&format!("Failed to decode vault key: {}", err))
+            })?;
+            let legacy_vault_key = keys.open_key(&sealed_key)?;
+
+            keys.set_legacy_vault_key(legacy_vault_key);
+        }
+
+        let (index_doc, entry_index) = load_entry_index(&store, name, &keys)?;
+        let mut ledger = LocalLedger {
             name: name.to_owned(),
             doc_cache,
             //assoc_doc,
             meta_doc,
-            pw: Secret::new(ledger_password),
-        })
+            entry_index,
+            index_doc,
+            search_index: SearchIndex::default(),
+            keys,
+            store,
+            retention: Default::default(),
+            _vault_lock: vault_lock,
+        };
+        let migrated = ledger.migrate_entry_names()?;
+
+        if !migrated.is_empty() {
+            tracing::info!("Migrated {} entries to opaque uuids", migrated.len());
+        }
+
+        ledger.rebuild_search_index()?;
+
+        Ok(ledger)
     }
 
     /// Creates a new entry to the ledger.  Returning a uuid.
     pub fn create(&mut self, data: T, entry_name: &str) -> Result<String, LocalLedgerError> {
-        if entry_name.len() == 0 {
-            return Err(LocalLedgerError::new("Label cannot be empty"));
-        }
+        let uuid = &self.entry_uuid(entry_name)?;
 
-        let label_already_in_use = self.entry_name_already_in_use(entry_name)?;
+        let label_already_in_use = self.entry_name_already_in_use(uuid)?;
 
         if label_already_in_use {
             return Err(LocalLedgerError::new("Labels must be unique"));
         }
 
-        let mut encrypted_doc = Document::<T>::new(&self.name);
-        encrypted_doc.append_uuid(entry_name);
+        if Document::<T>::read_trashed_at(&self.store, &self.name, uuid)?.is_some() {
+            return Err(LocalLedgerError::new(
+                "An entry with this label is in the trash",
+            ));
+        }
+
+        // A deleted entry is brought back on top of its tombstone, so the new entry wins over the
+        // deletion when syncing
+        let mut encrypted_doc = match Document::<T>::load_tombstone(&self.store, &self.name, uuid)?
+        {
+            Some(tombstone) => tombstone,
+            None => {
+                let mut new_doc = Document::<T>::new(&self.name).with_store(&self.store);
+                new_doc.append_uuid(uuid);
+                new_doc
+            }
+        };
+        // The name goes into the index first, so an entry is never stored without it
+        if self.entry_index.insert(uuid, entry_name) {
+            self.store_entry_index()?;
+        }
+
+        let search_terms = data.search_terms();
+
         encrypted_doc.update(data);
-        encrypt_store_doc(&mut encrypted_doc, &self.pw.expose_secret())?;
+        encrypt_store_doc(&mut encrypted_doc, &self.keys, self.retention)?;
 
         let doc_uuid = encrypted_doc.get_uuid();
 
         self.doc_cache.put(doc_uuid.clone(), encrypted_doc);
+        self.search_index.insert(entry_name, search_terms);
 
         Ok(doc_uuid)
     }
@@ -104,7 +312,7 @@ where
     /// Reads data in a document
     pub fn read<'a>(&'a mut self, uuid: String) -> Result<&'a T, LocalLedgerError> {
         let doc_is_cached = self.doc_cache.contains(&uuid);
-        let key = &self.pw.expose_secret();
+        let keys = &self.keys;
 
         if doc_is_cached {
             let mut cached_doc = self.doc_cache.get_mut(&uuid).map_or(
@@ -113,16 +321,16 @@ where
             )?;
 
             if !cached_doc.has_been_decrypted() {
-                decrypt_load_doc(&mut cached_doc, &uuid, &key)?;
+                decrypt_load_doc(&mut cached_doc, &uuid, keys)?;
             }
 
             return cached_doc.read_data();
         }
 
-        let mut loaded_doc = Document::<T>::new(&self.name);
+        let mut loaded_doc = Document::<T>::new(&self.name).with_store(&self.store);
 
         tracing::info!("calling decrypt load");
-        decrypt_load_doc(&mut loaded_doc, &uuid, &key)?;
+        decrypt_load_doc(&mut loaded_doc, &uuid, keys)?;
         tracing::info!("decrypt load success");
 
         self.doc_cache.put(uuid.clone(), loaded_doc);
@@ -135,633 +343,3768 @@ where
         cached_doc.read_data()
     }
 
-    pub fn get_conf(&mut self, entry_name: &str) -> Result<Document<T>, LocalLedgerError> {
-        let key = &self.pw.expose_secret();
-        let conf_doc = decrypt_load_conf::<T>(&self.name, key, entry_name)?;
+    /// Gets every conflicting copy of the entry, one per conflicting rev
+    pub fn get_conf(&mut self, entry_name: &str) -> Result<Vec<Document<T>>, LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
+        let keys = &self.keys;
 
-        Ok(conf_doc)
+        Document::<T>::get_conflict_uuids(&self.store, &self.name, uuid)?
+            .iter()
+            .map(|conf_uuid| decrypt_load::<T>(&self.store, &self.name, conf_uuid, keys))
+            .collect()
     }
 
+    /// Settles all conflicts of an entry at once.  Conflicting copies that were not kept are
+    /// dropped.
     pub fn resolve(
         &mut self,
         entry_name: &str,
-        keep_original: bool,
+        resolution: Resolution<T>,
     ) -> Result<(), LocalLedgerError> {
         // There is knowledge of the internals of the Document struct embedded in this logic.
         // This methods knows that the uuids of the original doc and the conflict doc differ.
         // I think temp_doc and conf_doc management should be handled in the Ledger and not the
         // Document struct
-        let original_doc = self.get_doc(entry_name)?;
+        let uuid = &self.entry_uuid(entry_name)?;
+        let original_doc = self.get_doc(uuid)?.clone();
         let original_uuid = original_doc.get_uuid();
-        let key = self.pw.expose_secret().clone();
-        let mut loaded_conf_doc: Document<T> = decrypt_load_conf(&self.name, &key, entry_name)?;
-        let conf_doc_uuid = loaded_conf_doc.get_uuid();
+        let conf_docs = self.get_conf(entry_name)?;
 
-        if keep_original {
-            self.remove(loaded_conf_doc.read_uuid())?;
-        } else {
-            loaded_conf_doc.append_uuid(&original_uuid);
-            self.remove(&original_uuid)?;
-            self.remove(&conf_doc_uuid)?;
-            encrypt_store_doc(&mut loaded_conf_doc, &key)?;
+        if conf_docs.is_empty() {
+            return Err(LocalLedgerError::new("Entry has no conflicts."));
         }
 
-        Ok(())
+        let mut kept_doc = match resolution {
+            Resolution::Original => original_doc.clone(),
+            Resolution::Sibling(rev) => {
+                let mut conf_doc = conf_docs
+                    .iter()
+                    .find(|conf_doc| conf_doc.rev() == rev)
+                    .cloned()
+                    .ok_or(LocalLedgerError::new("Conflict not found."))?;
+
+                conf_doc.append_uuid(&original_uuid);
+                conf_doc
+            }
+            Resolution::Merged(data) => {
+                let mut merged_doc = original_doc.clone();
+
+                merged_doc.update(data);
+                merged_doc
+            }
+        };
+
+        // Whichever copy is kept has to supersede all of them, otherwise the copies that were
+        // dropped come back as conflicts on the next merge
+        kept_doc.merge_version(&original_doc);
+        conf_docs
+            .iter()
+            .for_each(|conf_doc| kept_doc.merge_version(conf_doc));
+        encrypt_store_doc(&mut kept_doc, &self.keys, self.retention)?;
+        conf_docs.iter().try_for_each(|conf_doc| {
+            Document::<T>::remove_doc(&self.store, &self.name, &conf_doc.get_uuid())
+        })?;
+
+        let _ = self.doc_cache.pop_entry(&original_uuid);
+
+        self.reindex_entry(entry_name)
     }
 
     pub fn read_by_entry_name<'a>(
         &'a mut self,
         entry_name: &str,
     ) -> Result<&'a T, LocalLedgerError> {
-        self.read(entry_name.to_string())
+        let uuid = self.entry_uuid(entry_name)?;
+
+        self.read(uuid)
     }
 
     /// Updates document for given `entry_name` with given `data`
     pub fn update(&mut self, entry_name: &str, data: T) -> Result<(), LocalLedgerError> {
-        let entry_exists = self.entry_name_already_in_use(entry_name)?;
+        let uuid = &self.entry_uuid(entry_name)?;
+        let entry_exists = self.entry_name_already_in_use(uuid)?;
 
         if !entry_exists {
             return Err(LocalLedgerError::new("Entry name not found."));
         }
 
-        let doc_is_cached = self.doc_cache.contains(entry_name); // entry_name_already_in_use does a cache check, and we do another on this line.  we should fix this later lol
-        let key = &self.pw.expose_secret();
+        let search_terms = data.search_terms();
+        let doc_is_cached = self.doc_cache.contains(uuid); // entry_name_already_in_use does a cache check, and we do another on this line.  we should fix this later lol
+        let keys = &self.keys;
 
         if doc_is_cached {
-            let mut cached_doc = self.doc_cache.get_mut(entry_name).map_or(
+            let mut cached_doc = self.doc_cache.get_mut(uuid).map_or(
                 Err(LocalLedgerError::new("Failed to get doc from cached")),
                 |d| Ok(d),
             )?;
 
             if !cached_doc.has_been_decrypted() {
-                decrypt_load_doc(cached_doc, entry_name, key)?;
+                decrypt_load_doc(cached_doc, uuid, keys)?;
             }
 
             cached_doc.update(data);
 
-            encrypt_store_doc(&mut cached_doc, &self.pw.expose_secret())?;
+            encrypt_store_doc(&mut cached_doc, &self.keys, self.retention)?;
+            self.search_index.insert(entry_name, search_terms);
 
             return Ok(());
         }
 
-        let mut doc = Document::<T>::new(&self.name);
-        decrypt_load_doc(&mut doc, entry_name, &self.pw.expose_secret())?;
+        let mut doc = Document::<T>::new(&self.name).with_store(&self.store);
+        decrypt_load_doc(&mut doc, uuid, &self.keys)?;
         doc.update(data);
-        encrypt_store_doc(&mut doc, &self.pw.expose_secret())?;
+        encrypt_store_doc(&mut doc, &self.keys, self.retention)?;
 
-        self.doc_cache.put(entry_name.to_owned(), doc);
+        self.doc_cache.put(uuid.to_owned(), doc);
+        self.search_index.insert(entry_name, search_terms);
 
         Ok(())
     }
 
-    pub fn remove(&mut self, entry_name: &str) -> Result<(), LocalLedgerError> {
-        let doc_is_cached = self.doc_cache.contains(entry_name);
+    /// Sets how many prior revisions are kept for each entry.  Takes effect the next time an
+    /// entry is written.
+    pub fn set_revision_retention(&mut self, retention: RetentionPolicy) {
+        self.retention = retention;
+    }
 
-        if doc_is_cached {
-            let cached_doc = self
-                .doc_cache
-                .get_mut(entry_name)
-                .ok_or(LocalLedgerError::new("Failed to get doc from cache"))?;
+    /// Returns the prior revisions of an entry, oldest first
+    pub fn entry_history(
+        &mut self,
+        entry_name: &str,
+    ) -> Result<Vec<EntryRevision<T>>, LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
+        let history = self.get_doc(uuid)?.history().to_vec();
+
+        history
+            .iter()
+            .map(|revision| {
+                let data = revision.read_data(|encrypted_data| {
+                    migrate_payload::<T>(revision.schema_version, self.keys.open(encrypted_data)?)
+                })?;
+
+                Ok(EntryRevision {
+                    rev: revision.rev.clone(),
+                    seq: revision.seq,
+                    replaced_at: revision.replaced_at,
+                    data,
+                })
+            })
+            .collect()
+    }
+
+    /// Makes a prior revision of an entry its current value.  The value being replaced is kept in
+    /// the entry's history.
+    pub fn restore_entry_revision(
+        &mut self,
+        entry_name: &str,
+        rev: &str,
+    ) -> Result<(), LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
+        let retention = self.retention;
+        // Make sure the doc is cached and decrypted
+        let _ = self.get_doc(uuid)?;
+        let doc = self
+            .doc_cache
+            .get_mut(uuid)
+            .ok_or(LocalLedgerError::new("Failed to get doc from cache"))?;
 
-            cached_doc.remove()?;
+        doc.set_retention(retention);
+        doc.restore_rev(rev)?;
 
-            let _ = self.doc_cache.pop_entry(entry_name);
-        } else {
-            Document::<T>::remove_doc(&self.name, entry_name)?;
+        // The restored payload still needs to be decrypted, so drop the stale cached copy
+        let _ = self.doc_cache.pop_entry(uuid);
+
+        self.reindex_entry(entry_name)
+    }
+
+    /// Re-encrypts every entry that was written with an older schema of `T`.  Entries are migrated
+    /// whenever they are read, so this is only needed to upgrade the stored copies in bulk.
+    /// Entries that can not be read are skipped, see `repair`.  Returns the number of entries
+    /// that were rewritten.
+    pub fn migrate_entries(&mut self) -> Result<usize, LocalLedgerError> {
+        let mut migrated = 0;
+
+        for entry_name in self.list_entry_labels()? {
+            match self.migrate_entry(&entry_name) {
+                Ok(true) => migrated += 1,
+                Ok(false) => {}
+                Err(err) => tracing::warn!("Failed to migrate {}: {}", entry_name, err),
+            }
         }
 
-        Ok(())
+        Ok(migrated)
     }
 
-    pub fn list_entry_labels(&self) -> Result<Vec<String>, LocalLedgerError> {
-        let labels: Vec<String> = Document::<T>::get_all_uuids(&self.name)?
-            .into_iter()
-            .filter(|uuid| uuid != META_DOC_UUID)
-            .collect();
+    /// Rewrites `entry_name` if it was written with an older schema of `T`.  Returns true if it
+    /// was.
+    fn migrate_entry(&mut self, entry_name: &str) -> Result<bool, LocalLedgerError> {
+        let schema_version = self
+            .get_doc(&self.entry_uuid(entry_name)?)?
+            .schema_version();
 
-        Ok(labels)
+        if schema_version >= T::SCHEMA_VERSION {
+            return Ok(false);
+        }
+
+        let data = self.read_by_entry_name(entry_name)?.clone();
+
+        self.update(entry_name, data)?;
+
+        Ok(true)
     }
 
-    pub fn list_entries_with_conflicts(&self) -> Result<Vec<String>, LocalLedgerError> {
-        let labels: Vec<_> = Document::<T>::get_all_conflict_uuids(&self.name)?;
+    /// Rewrites every doc of the ledger that is still in an older on-disk format.  Older docs
+    /// are read just fine, so this is opt in.  Returns the number of docs that were upgraded.
+    pub fn upgrade_format(&mut self) -> Result<usize, LocalLedgerError> {
+        let mut upgraded = 0;
 
-        Ok(labels)
+        for uuid in self.store.list(&self.name)? {
+            if upgrade_doc_format(&self.store, &self.name, &uuid)? {
+                upgraded += 1;
+            }
+        }
+
+        // Cached docs still remember the format they were loaded with
+        self.doc_cache.clear();
+        self.meta_doc = try_load_meta_doc(&self.store, &self.name)
+            .ok_or(LocalLedgerError::new("Failed to reload meta doc"))?;
+        (self.index_doc, self.entry_index) = load_entry_index(&self.store, &self.name, &self.keys)?;
+
+        Ok(upgraded)
     }
 
-    pub fn get_ledger_dir(&self) -> Result<PathBuf, LocalLedgerError> {
-        self.meta_doc.get_data_dir()
+    /// Changes the master password of the ledger.  The vault key is wrapped with `new_password`,
+    /// and payloads that are still encrypted with the old password, from before the ledger had a
+    /// vault key, are moved over to the vault key.  The rewritten docs are staged and only moved
+    /// into place once all of them are ready, so a change that gets interrupted is either
+    /// finished or rolled back the next time the ledger is opened.
+    ///
+    /// A vault key that was derived from the old password is replaced by a random one, and every
+    /// payload is sealed again with it, so the old password opens nothing that is written from
+    /// then on, not even with an old copy of the ledger.  That also gives the ledger a new public
+    /// key, see `public_key`.
+    ///
+    /// Copies of the ledger on other devices keep their own password, unless the vault key was
+    /// replaced.  Those have to be unlocked with `new_password` once the change is synced to
+    /// them, see `merge`.
+    pub fn change_password(
+        &mut self,
+        old_password: &str,
+        new_password: String,
+    ) -> Result<(), LocalLedgerError> {
+        let old_key = self
+            .keys
+            .composite_key()
+            .with_password(old_password.to_owned());
+        let new_key = self.keys.composite_key().with_password(new_password);
+
+        self.rewrap_vault_key(&old_key, new_key)
     }
 
-    /// Retrieves all ledger's contents into a Read implementation.  Each doc is separated by a `\n` char
-    pub fn doc_dump(&self) -> Result<LedgerDump, LocalLedgerError> {
-        let src_dir = self.get_ledger_dir()?;
-        let ld = LedgerDump::new(src_dir).map_err(|msg| LocalLedgerError::new(&msg))?;
+    /// Adds a keyfile to what unlocks the ledger, replaces it, or with None, goes back to the
+    /// password alone.  Like a password change, this only applies to the copy of the ledger on
+    /// this device, and replaces a vault key that was derived from the password, see
+    /// `change_password`.
+    ///
+    /// Copies of the ledger from before the keyfile was added, synced or backed up, hold the
+    /// vault key wrapped with the password alone.  Unless the vault key was replaced, they open
+    /// everything written after the keyfile was added as well.
+    pub fn set_keyfile(
+        &mut self,
+        password: &str,
+        keyfile_contents: Option<&[u8]>,
+    ) -> Result<(), LocalLedgerError> {
+        let old_key = self.keys.composite_key().with_password(password.to_owned());
+        let new_key = old_key.with_keyfile_contents(keyfile_contents);
 
-        Ok(ld)
+        self.rewrap_vault_key(&old_key, new_key)
     }
 
-    pub async fn merge<S>(&mut self, mut s: S) -> Result<(), LocalLedgerError>
-    where
-        S: Stream<Item = Result<Value, Box<dyn std::error::Error>>> + Unpin,
-    {
-        // This method is pretty beefy.  Probably should clean it up at some point.
-        // I suppose this is an apology to my future self or whoever is dumb enough
-        // to work on this
-        let mut meta_doc_has_been_stored = false;
-        let mut temp_stored_uuids: Vec<String> = vec![];
-        let mut conflict_uuids: Vec<String> = vec![];
+    /// Whether a keyfile is needed to open the ledger
+    pub fn uses_keyfile(&self) -> Result<bool, LocalLedgerError> {
+        Ok(self
+            .meta_doc
+            .read_data()?
+            .vault_key
+            .as_ref()
+            .is_some_and(WrappedVaultKey::requires_keyfile))
+    }
 
-        while let Some(item) = s.next().await {
-            let val = item.map_err(|e| LocalLedgerError::new(&e.to_string()))?;
-            let uuid = assert_str(&val["uuid"]).map_err(|e| LocalLedgerError::new(&e))?;
+    /// Wraps the vault key with `new_key` once `old_key` has been checked against it.  A vault
+    /// key that was derived from the password is replaced by a random one instead.
+    fn rewrap_vault_key(
+        &mut self,
+        old_key: &CompositeKey,
+        new_key: CompositeKey,
+    ) -> Result<(), LocalLedgerError> {
+        let meta_data = self.meta_doc.read_data()?;
+        let wrapped_key = meta_data
+            .vault_key
+            .as_ref()
+            .ok_or(LocalLedgerError::new("Ledger has no vault key"))?;
 
-            tracing::info!("merging doc uuid: {}", &uuid);
+        // Fails if the old password or keyfile is not the right one
+        wrapped_key.unwrap(old_key)?;
 
-            if uuid.as_str() == META_DOC_UUID {
-                let mut incomming_meta_doc =
-                    serde_json::from_value::<Document<LocalLedgerMetaData>>(val)
-                        .map_err(|e| LocalLedgerError::new(&e.to_string()))?;
-                let conflict = Document::<LocalLedgerMetaData>::check_for_conflict(
-                    &self.meta_doc,
-                    &incomming_meta_doc,
-                );
+        // The password hash a derived vault key comes from is in every old copy of the meta doc
+        let (new_wrapped_key, new_keys) = if meta_data.random_vault_key {
+            let new_wrapped_key = WrappedVaultKey::rewrap(&new_key, self.keys.vault_key())?;
 
-                if conflict {
-                    // Conflict in the meta doc probably means that decryption is most likely to
-                    // fail for the new imported docs.  We need to clear everything out and notify
-                    // the user.
-                    tracing::warn!("Meta doc conflict detected.");
-                    temp_stored_uuids
-                        .into_iter()
-                        .chain(conflict_uuids.into_iter())
-                        .try_for_each(|uuid| Document::<T>::remove_doc(&self.name, &uuid))?;
+            (new_wrapped_key, self.keys.with_composite_key(new_key))
+        } else {
+            self.keys.with_random_vault_key(new_key)?
+        };
 
-                    return Err(LocalLedgerError::meta_doc_conflict(
-                        "META_DOC conflict found during merge.",
-                    ));
-                }
+        {
+            // Nobody else may write to the ledger until every doc has been moved over
+            let _write_lock = self.store.lock_writes(&self.name)?;
 
-                incomming_meta_doc.store()?;
-                meta_doc_has_been_stored = true;
+            // Leftovers of a change that failed are of no use
+            discard_staged_docs(&self.store, &self.name)?;
 
-                continue;
+            if let Err(err) = self.stage_rekey(&new_keys, new_wrapped_key) {
+                discard_staged_docs(&self.store, &self.name)?;
+
+                return Err(err);
             }
 
-            let mut incomming_ledger_doc = serde_json::from_value::<Document<T>>(val)
-                .map_err(|e| LocalLedgerError::new(&e.to_string()))?;
-            let our_ledger_doc = self.get_doc(&uuid)?;
-            let conflict = Document::<T>::check_for_conflict(our_ledger_doc, &incomming_ledger_doc);
+            commit_staged_docs(&self.store, &self.name)?;
+        }
 
-            if conflict {
-                // Mark document as conflict
-                incomming_ledger_doc.conflict_store()?;
-                conflict_uuids.push(incomming_ledger_doc.get_uuid());
-                tracing::warn!("Conflict found!");
-                continue;
-            }
+        self.keys = new_keys;
+        self.doc_cache.clear();
+        self.meta_doc = try_load_meta_doc(&self.store, &self.name)
+            .ok_or(LocalLedgerError::new("Failed to reload meta doc"))?;
+        (self.index_doc, self.entry_index) = load_entry_index(&self.store, &self.name, &self.keys)?;
 
-            tracing::info!("No conflict found.");
+        Ok(())
+    }
 
-            if !meta_doc_has_been_stored {
-                incomming_ledger_doc.temp_store()?;
-                temp_stored_uuids.push(incomming_ledger_doc.get_uuid());
+    /// Stages the meta doc with `new_wrapped_key`, along with every doc that still has payloads
+    /// that can only be read with the old password.  When `new_keys` holds a new vault key,
+    /// every doc is staged, sealed with it.
+    fn stage_rekey(
+        &self,
+        new_keys: &VaultKeys,
+        new_wrapped_key: WrappedVaultKey,
+    ) -> Result<(), LocalLedgerError> {
+        let replaced_vault_key =
+            new_keys.vault_key().expose_secret() != self.keys.vault_key().expose_secret();
+
+        for uuid in self.store.list(&self.name)? {
+            let contents = if uuid == META_DOC_UUID {
                 continue;
-            }
+            } else if uuid == INDEX_DOC_UUID {
+                // Sealed with the vault key from the start
+                if !replaced_vault_key {
+                    continue;
+                }
 
-            let key = &self.pw.expose_secret();
-            incomming_ledger_doc.decrypt(|encrypted_data| {
-                 tracing::info!("In decrypt callback");
-                 let decryptor = match age::Decryptor::new(&encrypted_data[..]).map_err(|err| {
-                     tracing::error!("decryptor error: {:?}", err);
-                     LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-                 })? {
-                     age::Decryptor::Passphrase(d) => Ok(d),
-                     _ => Err(LocalLedgerError::new("Failed to decrypt. Received encrypted data that was secured by some means other than a passphrase."))
-                 }?;
+                let mut index_doc =
+                    Document::<EntryIndex>::load_encrypted(&self.store, &self.name, &uuid)?;
 
-                 tracing::info!("decrypting data...");
-                 let mut decrypted = vec![];
-                 let mut reader = decryptor
-                     .decrypt(&Secret::new(key.to_string()), None)
-                     .map_err(|err| {
-                         LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-                     })?;
-                 tracing::info!("decrypting data success");
+                reseal_payloads(&mut index_doc, &self.keys, new_keys)?;
+                index_doc.to_bytes()?
+            } else {
+                let mut doc = Document::<T>::load_encrypted(&self.store, &self.name, &uuid)?;
 
-                 reader.read_to_end(&mut decrypted).map_err(|err| {
-                     LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-                 })?;
+                if replaced_vault_key {
+                    reseal_payloads(&mut doc, &self.keys, new_keys)?;
+                } else if !reseal_legacy_payloads(&mut doc, &self.keys)? {
+                    continue;
+                }
 
-                 Ok(decrypted)
-             })?;
+                doc.to_bytes()?
+            };
 
-            encrypt_store_doc(&mut incomming_ledger_doc, key)?;
+            stage_doc(&self.store, &self.name, &uuid, &contents)?;
         }
 
-        // successfully drained the stream
-        // loop through docs that were temp stored using the temp_stored_uuids vector
-
-        let temp_docs = decrypt_load_temp_docs::<T>(&self.name, self.pw.expose_secret())?;
-
-        for mut temp_doc in temp_docs.into_iter() {
-            let uuid = Document::<T>::temp_uuid_to_uuid(temp_doc.read_uuid())?;
+        let mut meta_doc = self.meta_doc.clone();
 
-            temp_doc.append_uuid(&uuid);
+        // The old password hash would still let older versions in with the old password
+        meta_doc.update(LocalLedgerMetaData {
+            pw_hash: String::new(),
+            vault_key: Some(new_wrapped_key),
+            ..meta_doc.read_data()?.clone()
+        });
 
-            encrypt_store_doc(&mut temp_doc, self.pw.expose_secret())?;
+        if replaced_vault_key {
+            let sealed_key = new_keys.seal(self.keys.vault_key().expose_secret())?;
+
+            // The identity goes along with the vault key it was sealed with or derived from.
+            // Other devices have to pick up the new vault key, so the meta doc gets a new rev.
+            meta_doc.update(LocalLedgerMetaData {
+                identity: None,
+                random_vault_key: true,
+                legacy_vault_key: Some(STANDARD.encode(sealed_key)),
+                ..meta_doc.read_data()?.clone()
+            });
+            meta_doc.next_rev()?;
         }
 
-        tracing::info!("Merge stream finished.");
-
-        Ok(())
+        stage_doc(
+            &self.store,
+            &self.name,
+            META_DOC_UUID,
+            &meta_doc.to_bytes()?,
+        )
     }
 
-    fn get_doc<'a>(&'a mut self, uuid: &str) -> Result<&'a Document<T>, LocalLedgerError> {
-        //TODO got some dup code with the `read` method
-        //Made this because i needed a method that retrieved the Doc struct.
-        //The read method reads the data inside a Document sturct
-        let doc_is_cached = self.doc_cache.contains(uuid);
-        let key = &self.pw.expose_secret();
+    /// Re-encrypts every doc that still has payloads that were encrypted with the master
+    /// password, from before the ledger had a vault key, or that were sealed with a derived vault
+    /// key that was since replaced, see `change_password`.  Those docs are read just fine, but
+    /// the former cost a full passphrase KDF run per payload to decrypt, and the latter still
+    /// open with the old password.  Docs that can not be read are skipped, see `repair`.
+    /// Returns the number of docs that were rewritten.
+    pub fn upgrade_encryption(&mut self) -> Result<usize, LocalLedgerError> {
+        let mut upgraded = 0;
+
+        for uuid in self.store.list(&self.name)? {
+            // Both are sealed with the vault key from the start
+            if uuid == META_DOC_UUID || uuid == INDEX_DOC_UUID {
+                continue;
+            }
 
-        if doc_is_cached {
-            let mut cached_doc = self.doc_cache.get_mut(uuid).map_or(
-                Err(LocalLedgerError::new("Failed to get doc from cache")),
-                |d| Ok(d),
-            )?;
+            let mut doc = match Document::<T>::load_encrypted(&self.store, &self.name, &uuid) {
+                Ok(doc) => doc,
+                // Left for `repair`, rather than keeping the ledger from opening
+                Err(err) => {
+                    tracing::warn!("Failed to upgrade the encryption of {}: {}", uuid, err);
+                    continue;
+                }
+            };
 
-            if !cached_doc.has_been_decrypted() {
-                decrypt_load_doc(&mut cached_doc, &uuid, &key)?;
+            match reseal_legacy_payloads(&mut doc, &self.keys) {
+                Ok(true) => {
+                    doc.store_as_is()?;
+                    upgraded += 1;
+                }
+                Ok(false) => {}
+                Err(err) => tracing::warn!("Failed to upgrade the encryption of {}: {}", uuid, err),
             }
-
-            return Ok(cached_doc);
         }
 
-        let mut loaded_doc = Document::<T>::new(&self.name);
+        self.doc_cache.clear();
 
-        decrypt_load_doc(&mut loaded_doc, &uuid, &key)?;
+        Ok(upgraded)
+    }
 
-        self.doc_cache.put(uuid.to_string(), loaded_doc);
+    /// Deletes an entry, or an entry in the trash, for good.  A tombstone is left in its place
+    /// so the deletion is synced to other devices instead of the entry coming back on the next
+    /// merge.
+    pub fn remove(&mut self, entry_name: &str) -> Result<(), LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
 
-        let cached_doc = self
-            .doc_cache
-            .get(uuid)
-            .ok_or(LocalLedgerError::new("Failed to get doc from cache"))?;
+        if !self.entry_name_already_in_use(uuid)?
+            && Document::<T>::read_trashed_at(&self.store, &self.name, uuid)?.is_none()
+        {
+            return Err(LocalLedgerError::new("Entry name not found."));
+        }
 
-        Ok(cached_doc)
+        let mut doc = match self.doc_cache.pop(uuid) {
+            Some(cached_doc) => cached_doc,
+            None => Document::<T>::load_encrypted(&self.store, &self.name, uuid)?,
+        };
+
+        doc.delete()?;
+        self.search_index.remove(entry_name);
+
+        Ok(())
     }
 
-    fn entry_name_already_in_use(&self, entry_name: &str) -> Result<bool, LocalLedgerError> {
-        let in_cache = self.doc_cache.contains(entry_name);
+    /// Moves an entry to the trash.  It is left out of the ledger, but keeps its data until it is
+    /// restored or the trash is purged.  Like any other change, the move syncs to other devices.
+    /// Conflicting copies of the entry are dropped, the copy of this device is the one that goes
+    /// to the trash.
+    pub fn trash(&mut self, entry_name: &str) -> Result<(), LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
 
-        if in_cache {
-            return Ok(true);
+        if !self.entry_name_already_in_use(uuid)? {
+            return Err(LocalLedgerError::new("Entry name not found."));
         }
 
-        Document::<T>::doc_exists(&self.name, entry_name)
-    }
-}
+        let mut doc = match self.doc_cache.pop(uuid) {
+            Some(cached_doc) => cached_doc,
+            None => Document::<T>::load_encrypted(&self.store, &self.name, uuid)?,
+        };
+        let conf_uuids = Document::<T>::get_conflict_uuids(&self.store, &self.name, uuid)?;
+
+        // Like in `resolve`, the trashed copy has to supersede the dropped ones, otherwise they
+        // come back as conflicts on the next merge
+        for conf_uuid in &conf_uuids {
+            doc.merge_version(&Document::<T>::load_encrypted(
+                &self.store,
+                &self.name,
+                conf_uuid,
+            )?);
+        }
 
-fn decrypt_load_doc<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
-    curr_doc: &mut Document<T>,
-    uuid: &str,
-    key: &str,
-) -> Result<(), LocalLedgerError> {
-    let loaded_doc = Document::<T>::decrypt_load(curr_doc.label(), uuid, |encrypted_data| {
-        let decryptor = match age::Decryptor::new(&encrypted_data[..]).map_err(|err| {
-            tracing::error!("decryptor error: {:?}", err);
-            LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-        })? {
-            age::Decryptor::Passphrase(d) => Ok(d),
-            _ => Err(LocalLedgerError::new("Failed to decrypt. Received encrypted data that was secured by some means other than a passphrase."))
-        }?;
+        doc.trash()?;
+        self.remove_conflict_docs(&conf_uuids)?;
+        self.search_index.remove(entry_name);
 
-        let mut decrypted = vec![];
-        let mut reader = decryptor
-            .decrypt(&Secret::new(key.to_owned()), None)
-            .map_err(|err| {
-                LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-            })?;
+        Ok(())
+    }
 
-        reader.read_to_end(&mut decrypted).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-        })?;
+    /// Takes an entry back out of the trash
+    pub fn restore_from_trash(&mut self, entry_name: &str) -> Result<(), LocalLedgerError> {
+        let uuid = &self.entry_uuid(entry_name)?;
 
-        Ok(decrypted)
-    })?;
+        if Document::<T>::read_trashed_at(&self.store, &self.name, uuid)?.is_none() {
+            return Err(LocalLedgerError::new("Entry is not in the trash."));
+        }
 
-    let _ = std::mem::replace(curr_doc, loaded_doc);
+        let _ = self.doc_cache.pop_entry(uuid);
 
-    Ok(())
-}
+        Document::<T>::load_encrypted(&self.store, &self.name, uuid)?.restore_from_trash()?;
 
-fn encrypt_store_doc<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
-    doc: &mut Document<T>,
-    key: &str,
-) -> Result<(), LocalLedgerError> {
-    doc.store_encrypted(|data| {
-        let encryptor = age::Encryptor::with_user_passphrase(Secret::new(key.to_owned()));
-        let mut encrypted_data = vec![];
-        let mut writer = encryptor.wrap_output(&mut encrypted_data).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to encrypt doc: {}", err.to_string()))
-        })?;
+        self.reindex_entry(entry_name)
+    }
 
-        writer.write_all(&data).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to encrypt doc: {}", err.to_string()))
-        })?;
+    /// Lists the entries in the trash, sorted by name
+    pub fn list_trash(&self) -> Result<Vec<TrashedEntry>, LocalLedgerError> {
+        let mut trashed_entries = vec![];
+
+        for uuid in self.entry_uuids()? {
+            if let Some(trashed_at) =
+                Document::<T>::read_trashed_at(&self.store, &self.name, &uuid)?
+            {
+                trashed_entries.extend(self.lookup_entry_name(&uuid).map(|entry_name| {
+                    TrashedEntry {
+                        entry_name,
+                        trashed_at,
+                    }
+                }));
+            }
+        }
 
-        writer.finish().map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to encrypt doc: {}", err.to_string()))
-        })?;
+        trashed_entries.sort_by(|a, b| a.entry_name.cmp(&b.entry_name));
 
-        Ok(encrypted_data)
-    })?;
+        Ok(trashed_entries)
+    }
 
-    Ok(())
-}
+    /// Deletes the entries that were moved to the trash longer than `horizon` ago.  They leave
+    /// tombstones like any other deletion.  Returns the number of entries that were deleted.
+    pub fn purge_trash(&mut self, horizon: Duration) -> Result<usize, LocalLedgerError> {
+        let purged =
+            Document::<T>::purge_trash(&self.store, &self.name, &self.entry_uuids()?, horizon)?;
 
-fn decrypt_load_temp_docs<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
-    label: &str,
-    key: &str,
-) -> Result<Vec<Document<T>>, LocalLedgerError> {
-    let temp_uuids = Document::<T>::get_all_temp_uuids(label)?;
-
-    let decrypted_temp_docs: Result<Vec<_>, _>  = temp_uuids.into_iter().map(|uuid| {
-        let loaded_doc = Document::<T>::decrypt_load(label, &uuid, |encrypted_data| {
-            let decryptor = match age::Decryptor::new(&encrypted_data[..]).map_err(|err| {
-                tracing::error!("decryptor error: {:?}", err);
-                LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-            })? {
-                age::Decryptor::Passphrase(d) => Ok(d),
-                _ => Err(LocalLedgerError::new("Failed to decrypt. Received encrypted data that was secured by some means other than a passphrase."))
-            }?;
-
-            let mut decrypted = vec![];
-            let mut reader = decryptor
-                .decrypt(&Secret::new(key.to_owned()), None)
-                .map_err(|err| {
-                    LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-                })?;
+        for uuid in &purged {
+            let _ = self.doc_cache.pop_entry(uuid);
 
-            reader.read_to_end(&mut decrypted).map_err(|err| {
-                LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-            })?;
+            // Entries trashed by older versions kept their conflicting copies
+            let conf_uuids = Document::<T>::get_conflict_uuids(&self.store, &self.name, uuid)?;
 
-            Ok(decrypted)
-        });
+            self.remove_conflict_docs(&conf_uuids)?;
+        }
 
-        loaded_doc
-    }).collect();
+        Ok(purged.len())
+    }
 
-    decrypted_temp_docs
-}
+    /// Removes the tombstones of entries that were deleted longer than `horizon` ago.  Devices
+    /// that have not synced within the horizon can bring those entries back.  Returns the number
+    /// of tombstones that were removed.
+    pub fn purge_tombstones(&mut self, horizon: Duration) -> Result<usize, LocalLedgerError> {
+        let purged = Document::<T>::purge_tombstones(
+            &self.store,
+            &self.name,
+            &self.entry_uuids()?,
+            horizon,
+        )?;
+
+        Ok(purged.len())
+    }
 
-fn decrypt_load_conf<T>(label: &str, key: &str, uuid: &str) -> Result<Document<T>, LocalLedgerError>
-where
-    T: Clone + Serialize + DeserializeOwned + Default + Debug,
-{
-    let loaded_doc = Document::<T>::decrypt_load_conf(label, uuid, |encrypted_data| {
-        let decryptor = match age::Decryptor::new(&encrypted_data[..]).map_err(|err| {
-                LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-            })? {
-                age::Decryptor::Passphrase(d) => Ok(d),
-                _ => Err(LocalLedgerError::new("Failed to decrypt. Received encrypted data that was secured by some means other than a passphrase."))
-            }?;
-
-        let mut decrypted = vec![];
-        let mut reader = decryptor
-            .decrypt(&Secret::new(key.to_owned()), None)
-            .map_err(|err| {
-                LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-            })?;
+    /// Uuids of the entries of the ledger, trashed, deleted and conflicted ones included
+    fn entry_uuids(&self) -> Result<Vec<String>, LocalLedgerError> {
+        let mut entry_uuids = self.unconflicted_entry_uuids()?;
 
-        reader.read_to_end(&mut decrypted).map_err(|err| {
-            LocalLedgerError::new(&format!("Failed to decrypt data: {}", err.to_string()))
-        })?;
+        // Conflicting copies can outlive the entry they belong to
+        for uuid in Document::<T>::get_all_conflict_uuids(&self.store, &self.name)? {
+            if Document::<T>::doc_exists(&self.store, &self.name, &uuid)? {
+                entry_uuids.push(uuid);
+            }
+        }
 
-        Ok(decrypted)
-    });
+        Ok(entry_uuids)
+    }
 
-    loaded_doc
-}
+    /// Like `entry_uuids`, but entries with conflicting copies are left out, see
+    /// `Document::get_all_uuids`
+    fn unconflicted_entry_uuids(&self) -> Result<Vec<String>, LocalLedgerError> {
+        Ok(Document::<T>::get_all_uuids(&self.store, &self.name)?
+            .into_iter()
+            .filter(|uuid| uuid != META_DOC_UUID && uuid != INDEX_DOC_UUID)
+            .collect())
+    }
 
-fn try_load_meta_doc(ledger_name: &str) -> Option<Document<LocalLedgerMetaData>> {
-    match Document::<LocalLedgerMetaData>::load(ledger_name, META_DOC_UUID) {
-        Ok(meta_doc) => Some(meta_doc),
-        Err(_err) => None,
+    fn remove_conflict_docs(&self, conf_uuids: &[String]) -> Result<(), LocalLedgerError> {
+        conf_uuids
+            .iter()
+            .try_for_each(|conf_uuid| Document::<T>::remove_doc(&self.store, &self.name, conf_uuid))
     }
-}
 
-fn create_meta_doc(ledger_name: &str) -> Document<LocalLedgerMetaData> {
-    let mut meta_doc = Document::<LocalLedgerMetaData>::new(ledger_name);
+    /// Lists the names of all entries, sorted.  Entries with conflicting copies are left out, see
+    /// `list_entries_with_conflicts`.
+    pub fn list_entry_labels(&self) -> Result<Vec<String>, LocalLedgerError> {
+        let mut labels: Vec<String> = vec![];
+
+        for uuid in self.unconflicted_entry_uuids()? {
+            match Document::<T>::live_doc_exists(&self.store, &self.name, &uuid) {
+                Ok(true) => labels.extend(self.lookup_entry_name(&uuid)),
+                Ok(false) => {}
+                // Left for `repair`, the other entries are still listed
+                Err(err) => tracing::warn!("Failed to read {}: {}", uuid, err),
+            }
+        }
 
-    meta_doc.append_uuid(META_DOC_UUID);
+        labels.sort();
 
-    meta_doc
-}
+        Ok(labels)
+    }
+
+    /// Lists the names of all entries that have conflicting copies, sorted.  Entries in the
+    /// trash are left out.
+    pub fn list_entries_with_conflicts(&self) -> Result<Vec<String>, LocalLedgerError> {
+        let mut labels: Vec<String> = vec![];
+
+        for uuid in Document::<T>::get_all_conflict_uuids(&self.store, &self.name)? {
+            if Document::<T>::read_trashed_at(&self.store, &self.name, &uuid)?.is_none() {
+                labels.extend(self.lookup_entry_name(&uuid));
+            }
+        }
+
+        labels.sort();
+
+        Ok(labels)
+    }
+
+    /// Searches the names and search terms of all entries, see `Searchable`.  Matching is case
+    /// insensitive and falls back to fuzzy matches.  Best matches come first.
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        self.search_index.search(query)
+    }
+
+    /// Returns the public key, `age1...`, other users share entries with this ledger to.  The
+    /// identity behind it is derived from the vault key, so every synced copy of the ledger has
+    /// the same one.
+    pub fn public_key(&self) -> Result<String, LocalLedgerError> {
+        public_key_of(&self.identity()?)
+    }
+
+    /// Packs the entry `entry_name` up for the holders of `public_keys`, see
+    /// `import_shared_entry`.  The ledger's own public key is added, so the entry can be imported
+    /// back into it or any of its synced copies.
+    pub fn share_entry(
+        &mut self,
+        entry_name: &str,
+        public_keys: &[String],
+    ) -> Result<Vec<u8>, LocalLedgerError> {
+        let data = self.read_by_entry_name(entry_name)?.clone();
+        let shared_entry = SharedEntry {
+            entry_name: entry_name.to_owned(),
+            schema_version: T::SCHEMA_VERSION,
+            data: serde_json::to_value(data).map_err(|err| {
+                LocalLedgerError::new(&format!("Failed to serialize entry: {}", err))
+            })?,
+        };
+        let shared_data = serde_json::to_vec(&shared_entry)
+            .map(Zeroizing::new)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to serialize entry: {}", err)))?;
+        let mut recipients = public_keys.to_vec();
+
+        recipients.push(self.public_key()?);
+
+        encrypt_to(&shared_data, &recipients)
+    }
+
+    /// Adds an entry made by `share_entry` for this ledger.  An entry that already exists under
+    /// the same name is updated.  Returns the name of the entry.
+    pub fn import_shared_entry(&mut self, shared: &[u8]) -> Result<String, LocalLedgerError> {
+        let shared_data = Zeroizing::new(decrypt_with(shared, &self.identity()?)?);
+        let shared_entry: SharedEntry = serde_json::from_slice(&shared_data).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to parse shared entry: {}", err))
+        })?;
+        let payload = serde_json::to_vec(&shared_entry.data).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to parse shared entry: {}", err))
+        })?;
+        let payload = Zeroizing::new(migrate_payload::<T>(shared_entry.schema_version, payload)?);
+        let data: T = serde_json::from_slice(&payload).map_err(|err| {
+            LocalLedgerError::new(&format!("Failed to parse shared entry: {}", err))
+        })?;
+        let entry_name = shared_entry.entry_name;
+        let uuid = self.entry_uuid(&entry_name)?;
+
+        if self.entry_name_already_in_use(&uuid)? {
+            self.update(&entry_name, data)?;
+        } else {
+            self.create(data, &entry_name)?;
+        }
+
+        Ok(entry_name)
+    }
+
+    /// Unseals the identity the ledger was given before identities were derived, or derives it
+    fn identity(&self) -> Result<Secret<String>, LocalLedgerError> {
+        if let Some(sealed_identity) = &self.meta_doc.read_data()?.identity {
+            let sealed_identity = STANDARD.decode(sealed_identity).map_err(|err| {
+                LocalLedgerError::new(&format!("Failed to decode identity: {}", err))
+            })?;
+            let identity = String::from_utf8(self.keys.open(&sealed_identity)?)
+                .map_err(|err| LocalLedgerError::new(&format!("Invalid identity: {}", err)))?;
+
+            return Ok(Secret::new(identity));
+        }
+
+        identity_from_key(&self.keys.identity_key()?)
+    }
+
+    /// Walks every doc of the ledger looking for docs that can not be parsed or decrypted, temp
+    /// docs and conflicting copies that were left behind, broken rev histories and a meta doc
+    /// that does not match the vault key.  Nothing is changed, see `repair`.
+    pub fn verify(&self) -> Result<Vec<IntegrityProblem>, LocalLedgerError> {
+        // Keeps other processes from changing docs while they are being checked
+        let _write_lock = self.store.lock_writes(&self.name)?;
+
+        self.find_problems()
+    }
+
+    /// Checks the ledger like `verify`, then fixes what it safely can.  Broken rev histories are
+    /// fixed in place.  Docs that can not be read, along with left behind temp docs and
+    /// conflicting copies, are moved to the quarantine.  The meta doc and entry index are never
+    /// touched, the ledger can not do without them.  Returns every problem found along with what
+    /// was done about it.
+    pub fn repair(&mut self) -> Result<Vec<RepairedProblem>, LocalLedgerError> {
+        let problems = self.verify()?;
+        // With the wrong vault key every doc looks undecryptable
+        let meta_doc_matches = !problems
+            .iter()
+            .any(|problem| problem.kind == ProblemKind::MetaDocMismatch);
+        let mut repaired = vec![];
+
+        for problem in problems {
+            let outcome = self.repair_problem(&problem, meta_doc_matches)?;
+
+            if outcome != RepairOutcome::NotRepaired {
+                tracing::warn!("{:?} {} ({:?})", outcome, problem.uuid, problem.kind);
+            }
+
+            repaired.push(RepairedProblem { problem, outcome });
+        }
+
+        self.doc_cache.clear();
+        self.rebuild_search_index()?;
+
+        Ok(repaired)
+    }
+
+    fn repair_problem(
+        &self,
+        problem: &IntegrityProblem,
+        meta_doc_matches: bool,
+    ) -> Result<RepairOutcome, LocalLedgerError> {
+        let repairable = match problem.kind {
+            ProblemKind::MetaDocMismatch => false,
+            ProblemKind::Undecryptable => meta_doc_matches,
+            _ => true,
+        };
+
+        if !repairable {
+            return Ok(RepairOutcome::NotRepaired);
+        }
+
+        if problem.kind == ProblemKind::BrokenRevHistory {
+            let repaired = if problem.uuid == INDEX_DOC_UUID {
+                repair_rev_history::<EntryIndex>(&self.store, &self.name, &problem.uuid)?
+            } else {
+                repair_rev_history::<T>(&self.store, &self.name, &problem.uuid)?
+            };
+
+            return Ok(if repaired {
+                RepairOutcome::Fixed
+            } else {
+                RepairOutcome::NotRepaired
+            });
+        }
+
+        if problem.uuid == INDEX_DOC_UUID {
+            return Ok(RepairOutcome::NotRepaired);
+        }
+
+        let _write_lock = self.store.lock_writes(&self.name)?;
+
+        quarantine_doc(&self.store, &self.name, &problem.uuid)?;
+
+        Ok(RepairOutcome::Quarantined)
+    }
+
+    fn find_problems(&self) -> Result<Vec<IntegrityProblem>, LocalLedgerError> {
+        let uuids = self.store.list(&self.name)?;
+        let mut problems = vec![];
+
+        for uuid in &uuids {
+            if uuid == META_DOC_UUID {
+                continue;
+            }
+
+            let found = if Document::<T>::temp_uuid_to_uuid(uuid).is_ok() {
+                Some((
+                    ProblemKind::OrphanedTemp,
+                    "Left behind by a merge that did not finish".to_owned(),
+                ))
+            } else if let Some(parent_uuid) = Document::<T>::conflict_parent_uuid(uuid)
+                .filter(|parent_uuid| !uuids.iter().any(|other| other == parent_uuid))
+            {
+                Some((
+                    ProblemKind::OrphanedConflict,
+                    format!("Conflicting copy of {}, which does not exist", parent_uuid),
+                ))
+            } else if uuid == INDEX_DOC_UUID {
+                check_doc::<EntryIndex>(&self.store, &self.name, uuid, &self.keys)
+            } else {
+                check_doc::<T>(&self.store, &self.name, uuid, &self.keys)
+            };
+
+            problems.extend(found.map(|(kind, detail)| IntegrityProblem {
+                uuid: uuid.clone(),
+                entry_name: self.entry_name_of(uuid),
+                kind,
+                detail,
+            }));
+        }
+
+        problems.extend(self.meta_doc_problem().map(|detail| IntegrityProblem {
+            uuid: META_DOC_UUID.to_owned(),
+            entry_name: None,
+            kind: ProblemKind::MetaDocMismatch,
+            detail,
+        }));
+
+        Ok(problems)
+    }
+
+    /// Checks that the meta doc in the store still holds the vault key the ledger was opened
+    /// with.  Returns what is wrong with it, if anything.
+    fn meta_doc_problem(&self) -> Option<String> {
+        let meta_doc = match try_load_meta_doc(&self.store, &self.name) {
+            Some(meta_doc) => meta_doc,
+            None => return Some("Meta doc is missing or can not be parsed".to_owned()),
+        };
+        let wrapped_key = match meta_doc.read_data() {
+            Ok(LocalLedgerMetaData {
+                vault_key: Some(wrapped_key),
+                ..
+            }) => wrapped_key,
+            _ => return Some("Meta doc has no vault key".to_owned()),
+        };
+
+        match wrapped_key.unwrap(self.keys.composite_key()) {
+            Ok(vault_key) if vault_key.expose_secret() == self.keys.vault_key().expose_secret() => {
+                None
+            }
+            Ok(_) => Some("Meta doc holds a different vault key".to_owned()),
+            Err(err) => Some(format!(
+                "Vault key in the meta doc can not be unwrapped: {}",
+                err
+            )),
+        }
+    }
+
+    /// Returns the name of the entry the doc `uuid` belongs to.  Temp docs and conflicting copies
+    /// belong to the entry they are a copy of.
+    fn entry_name_of(&self, uuid: &str) -> Option<String> {
+        let entry_uuid = Document::<T>::temp_uuid_to_uuid(uuid)
+            .ok()
+            .or_else(|| Document::<T>::conflict_parent_uuid(uuid).map(str::to_owned))
+            .unwrap_or_else(|| uuid.to_owned());
+
+        self.entry_index.entry_name(&entry_uuid).map(str::to_owned)
+    }
+
+    /// Deletes the ledger and every entry in it from its store.  The deletion is not synced,
+    /// other devices keep their copies of the ledger.
+    pub fn destroy(self) -> Result<(), LocalLedgerError> {
+        let _write_lock = self.store.lock_writes(&self.name)?;
+
+        self.store.remove_label(&staging_label(&self.name))?;
+        self.store.remove_label(&quarantine_label(&self.name))?;
+        self.store.remove_label(&self.name)
+    }
+
+    pub fn get_ledger_dir(&self) -> Result<PathBuf, LocalLedgerError> {
+        self.meta_doc.get_data_dir()
+    }
+
+    /// Retrieves all ledger's contents into a Read implementation.  Each doc is separated by a `\n` char
+    pub fn doc_dump(&self) -> Result<LedgerDump, LocalLedgerError> {
+        let src_dir = self.get_ledger_dir()?;
+        let ld = LedgerDump::new(src_dir).map_err(|msg| LocalLedgerError::new(&msg))?;
+
+        Ok(ld)
+    }
+
+    pub async fn merge<S>(&mut self, mut s: S) -> Result<(), LocalLedgerError>
+    where
+        S: Stream<Item = Result<Value, Box<dyn std::error::Error>>> + Unpin,
+    {
+        // This method is pretty beefy.  Probably should clean it up at some point.
+        // I suppose this is an apology to my future self or whoever is dumb enough
+        // to work on this
+        let mut meta_doc_has_been_stored = false;
+        let mut incomming_index_doc: Option<Document<EntryIndex>> = None;
+        let mut index_changed = false;
+        let mut temp_stored_uuids: Vec<String> = vec![];
+        let mut conflict_uuids: Vec<String> = vec![];
+
+        while let Some(item) = s.next().await {
+            let val = item.map_err(|e| LocalLedgerError::new(&e.to_string()))?;
+            let uuid = assert_str(&val["uuid"]).map_err(|e| LocalLedgerError::new(&e))?;
+
+            tracing::info!("merging doc uuid: {}", &uuid);
+
+            if uuid.as_str() == META_DOC_UUID {
+                let mut incomming_meta_doc =
+                    Document::<LocalLedgerMetaData>::from_value(&self.store, val)?;
+                let causality = Document::<LocalLedgerMetaData>::check_for_conflict(
+                    &self.meta_doc,
+                    &incomming_meta_doc,
+                );
+
+                if causality == Causality::Concurrent {
+                    // Conflict in the meta doc probably means that decryption is most likely to
+                    // fail for the new imported docs.  We need to clear everything out and notify
+                    // the user.
+                    tracing::warn!("Meta doc conflict detected.");
+                    self.remove_merged_docs(temp_stored_uuids, conflict_uuids)?;
+
+                    return Err(LocalLedgerError::meta_doc_conflict(
+                        "META_DOC conflict found during merge.",
+                    ));
+                }
+
+                if causality == Causality::Ancestor {
+                    let meta_data = self.meta_doc.read_data()?.clone();
+                    let incomming_meta_data = incomming_meta_doc.read_data()?.clone();
+
+                    // The derived vault key was replaced on the other device, see
+                    // `change_password`.  Nothing else can be read until the ledger is unlocked
+                    // with the new vault key, wrapped with the password set there.
+                    if incomming_meta_data.random_vault_key && !meta_data.random_vault_key {
+                        tracing::warn!("Vault key was replaced on another device.");
+                        // Payloads encrypted with the password could not be read with the new one
+                        self.upgrade_encryption()?;
+                        incomming_meta_doc.store_as_is()?;
+                        self.meta_doc = incomming_meta_doc;
+                        self.remove_merged_docs(temp_stored_uuids, conflict_uuids)?;
+
+                        return Err(LocalLedgerError::new(
+                            "The vault key was replaced on another device. Unlock the ledger with the password set there.",
+                        ));
+                    }
+
+                    // The vault key stays wrapped with the password and keyfile of this device
+                    incomming_meta_doc.update(LocalLedgerMetaData {
+                        pw_hash: meta_data.pw_hash,
+                        vault_key: meta_data.vault_key,
+                        random_vault_key: meta_data.random_vault_key,
+                        legacy_vault_key: meta_data.legacy_vault_key,
+                        // An identity sealed with a vault key that was since replaced here is of
+                        // no use
+                        identity: if incomming_meta_data.random_vault_key
+                            == meta_data.random_vault_key
+                        {
+                            incomming_meta_data.identity
+                        } else {
+                            meta_data.identity
+                        },
+                    });
+                    incomming_meta_doc.store_as_is()?;
+                    self.meta_doc = incomming_meta_doc;
+                }
+
+                meta_doc_has_been_stored = true;
+
+                continue;
+            }
+
+            // Merged once everything else is in, see `merge_entry_index`
+            if uuid.as_str() == INDEX_DOC_UUID {
+                incomming_index_doc = Some(Document::<EntryIndex>::from_value(&self.store, val)?);
+
+                continue;
+            }
+
+            let mut incomming_ledger_doc = Document::<T>::from_value(&self.store, val)?;
+
+            // Docs from devices that have not migrated their entry names yet
+            if incomming_ledger_doc.name_scheme() < NAME_SCHEME {
+                let (entry_name, suffix) =
+                    legacy_uuid_to_entry_name(&uuid, incomming_ledger_doc.name_scheme())?;
+                let entry_uuid = self.keys.entry_id(&entry_name)?;
+
+                index_changed |= self.entry_index.insert(&entry_uuid, &entry_name);
+                incomming_ledger_doc.append_uuid(&format!("{}{}", entry_uuid, suffix));
+                incomming_ledger_doc.set_name_scheme(NAME_SCHEME);
+            }
+
+            let uuid = incomming_ledger_doc.get_uuid();
+
+            // Conflicts that were already resolved here do not come back
+            if let Some(parent_uuid) = Document::<T>::conflict_parent_uuid(&uuid) {
+                if Document::<T>::doc_exists(&self.store, &self.name, parent_uuid)? {
+                    let parent_doc =
+                        Document::<T>::load_encrypted(&self.store, &self.name, parent_uuid)?;
+
+                    if matches!(
+                        parent_doc.check_for_conflict(&incomming_ledger_doc),
+                        Causality::Equal | Causality::Descendant
+                    ) {
+                        tracing::info!("Conflict has already been resolved.");
+                        continue;
+                    }
+                }
+            }
+
+            // Tombstones count as well, so deleted entries do not come back
+            let our_ledger_doc = if Document::<T>::doc_exists(&self.store, &self.name, &uuid)? {
+                Some(Document::<T>::load_encrypted(
+                    &self.store,
+                    &self.name,
+                    &uuid,
+                )?)
+            } else {
+                None
+            };
+            // We have never seen this doc, so anything is newer than what we have
+            let causality = our_ledger_doc
+                .as_ref()
+                .map_or(Causality::Ancestor, |our_doc| {
+                    Document::<T>::check_for_conflict(our_doc, &incomming_ledger_doc)
+                });
+
+            match (causality, our_ledger_doc) {
+                (Causality::Equal | Causality::Descendant, _) => {
+                    tracing::info!("Already up to date.");
+                    continue;
+                }
+
+                (Causality::Concurrent, Some(mut our_doc))
+                    if removal_rank(&our_doc) > 0 || removal_rank(&incomming_ledger_doc) > 0 =>
+                {
+                    // An entry that was removed on one device and edited on another keeps the
+                    // edit, and an entry in the trash wins over a deleted one, so nothing is lost
+                    // that one of the devices still had.  The kept copy takes on both versions,
+                    // so it wins everywhere.
+                    tracing::warn!("Entry was removed and edited concurrently, keeping the edit.");
+
+                    if removal_rank(&incomming_ledger_doc) < removal_rank(&our_doc) {
+                        incomming_ledger_doc.merge_version(&our_doc);
+                    } else {
+                        our_doc.merge_version(&incomming_ledger_doc);
+                        our_doc.store_as_is()?;
+                        let _ = self.doc_cache.pop_entry(&uuid);
+                        continue;
+                    }
+                }
+
+                (Causality::Concurrent, _) => {
+                    // Mark document as conflict
+                    incomming_ledger_doc.conflict_store()?;
+                    conflict_uuids.push(incomming_ledger_doc.get_uuid());
+                    tracing::warn!("Conflict found!");
+                    continue;
+                }
+
+                (Causality::Ancestor, _) => {
+                    tracing::info!("No conflict found.");
+                }
+            }
+
+            if !meta_doc_has_been_stored {
+                incomming_ledger_doc.temp_store()?;
+                temp_stored_uuids.push(incomming_ledger_doc.get_uuid());
+                continue;
+            }
+
+            if !incomming_ledger_doc.is_deleted() {
+                // Make sure the incoming doc can actually be read before adopting it
+                tracing::info!("decrypting data...");
+                decrypt_doc(&mut incomming_ledger_doc, &self.keys)?;
+            }
+
+            incomming_ledger_doc.store_as_is()?;
+            let _ = self.doc_cache.pop_entry(&uuid);
+        }
+
+        // successfully drained the stream
+        // loop through docs that were temp stored using the temp_stored_uuids vector
+
+        let temp_docs = decrypt_load_temp_docs::<T>(&self.store, &self.name, &self.keys)?;
+
+        for mut temp_doc in temp_docs.into_iter() {
+            let temp_uuid = temp_doc.get_uuid();
+            let uuid = Document::<T>::temp_uuid_to_uuid(&temp_uuid)?;
+
+            temp_doc.append_uuid(&uuid);
+            temp_doc.store_as_is()?;
+            Document::<T>::remove_doc(&self.store, &self.name, &temp_uuid)?;
+
+            let _ = self.doc_cache.pop_entry(&uuid);
+        }
+
+        if let Some(incomming_index_doc) = incomming_index_doc {
+            index_changed |= self.merge_entry_index(incomming_index_doc)?;
+        }
+
+        if index_changed {
+            self.store_entry_index()?;
+        }
+
+        // Any entry could have changed
+        self.rebuild_search_index()?;
+
+        tracing::info!("Merge stream finished.");
+
+        Ok(())
+    }
+
+    /// Removes the docs a merge that has to be given up on stored so far
+    fn remove_merged_docs(
+        &self,
+        temp_stored_uuids: Vec<String>,
+        conflict_uuids: Vec<String>,
+    ) -> Result<(), LocalLedgerError> {
+        temp_stored_uuids
+            .into_iter()
+            .chain(conflict_uuids)
+            .try_for_each(|uuid| Document::<T>::remove_doc(&self.store, &self.name, &uuid))
+    }
+
+    /// Works out what `merge` would do with the docs of `s`, without changing anything.  The
+    /// stream is used up, the docs have to be kept or pulled again to merge them.
+    pub async fn merge_preview<S>(&self, mut s: S) -> Result<MergePreview, LocalLedgerError>
+    where
+        S: Stream<Item = Result<Value, Box<dyn std::error::Error>>> + Unpin,
+    {
+        let mut preview = MergePreview::default();
+        // Names of new entries are in the incoming entry index, which can come last
+        let mut entry_index = self.entry_index.clone();
+        let mut changes = vec![];
+
+        while let Some(item) = s.next().await {
+            let val = item.map_err(|e| LocalLedgerError::new(&e.to_string()))?;
+            let uuid = assert_str(&val["uuid"]).map_err(|e| LocalLedgerError::new(&e))?;
+
+            if uuid.as_str() == META_DOC_UUID {
+                let incomming_meta_doc =
+                    Document::<LocalLedgerMetaData>::from_value(&self.store, val)?;
+
+                preview.meta_doc = match self.meta_doc.check_for_conflict(&incomming_meta_doc) {
+                    Causality::Equal | Causality::Descendant => MetaDocCompatibility::Unchanged,
+                    Causality::Ancestor
+                        if incomming_meta_doc.read_data()?.random_vault_key
+                            && !self.meta_doc.read_data()?.random_vault_key =>
+                    {
+                        MetaDocCompatibility::VaultKeyReplaced
+                    }
+                    Causality::Ancestor => MetaDocCompatibility::Updated,
+                    Causality::Concurrent => MetaDocCompatibility::Conflict,
+                };
+
+                continue;
+            }
+
+            if uuid.as_str() == INDEX_DOC_UUID {
+                let mut incomming_index_doc = Document::<EntryIndex>::from_value(&self.store, val)?;
+
+                // Entries whose names can not be read are listed by uuid
+                if decrypt_doc(&mut incomming_index_doc, &self.keys).is_ok() {
+                    entry_index.union(incomming_index_doc.read_data()?);
+                }
+
+                continue;
+            }
+
+            let mut incomming_ledger_doc = Document::<T>::from_value(&self.store, val)?;
+
+            // Docs from devices that have not migrated their entry names yet
+            if incomming_ledger_doc.name_scheme() < NAME_SCHEME {
+                let (entry_name, suffix) =
+                    legacy_uuid_to_entry_name(&uuid, incomming_ledger_doc.name_scheme())?;
+                let entry_uuid = self.keys.entry_id(&entry_name)?;
+
+                entry_index.insert(&entry_uuid, &entry_name);
+                incomming_ledger_doc.append_uuid(&format!("{}{}", entry_uuid, suffix));
+            }
+
+            let uuid = incomming_ledger_doc.get_uuid();
+            let entry_uuid = Document::<T>::conflict_parent_uuid(&uuid)
+                .unwrap_or(&uuid)
+                .to_owned();
+
+            changes.push((entry_uuid, self.preview_doc(&incomming_ledger_doc)?));
+        }
+
+        for (entry_uuid, change) in changes {
+            let entry_name = entry_index
+                .entry_name(&entry_uuid)
+                .map_or(entry_uuid.clone(), str::to_owned);
+
+            preview.add(entry_name, change);
+        }
+
+        Ok(preview.finish())
+    }
+
+    /// Works out what `merge` would do with the entry doc `incomming_doc`
+    fn preview_doc(&self, incomming_doc: &Document<T>) -> Result<EntryChange, LocalLedgerError> {
+        let uuid = incomming_doc.get_uuid();
+        let parent_uuid = Document::<T>::conflict_parent_uuid(&uuid);
+
+        // Conflicts that were already resolved here do not come back
+        if let Some(parent_uuid) = parent_uuid {
+            if Document::<T>::doc_exists(&self.store, &self.name, parent_uuid)? {
+                let parent_doc =
+                    Document::<T>::load_encrypted(&self.store, &self.name, parent_uuid)?;
+
+                if matches!(
+                    parent_doc.check_for_conflict(incomming_doc),
+                    Causality::Equal | Causality::Descendant
+                ) {
+                    return Ok(EntryChange::Unchanged);
+                }
+            }
+        }
+
+        let our_doc = if Document::<T>::doc_exists(&self.store, &self.name, &uuid)? {
+            Some(Document::<T>::load_encrypted(
+                &self.store,
+                &self.name,
+                &uuid,
+            )?)
+        } else {
+            None
+        };
+        let causality = our_doc.as_ref().map_or(Causality::Ancestor, |our_doc| {
+            Document::<T>::check_for_conflict(our_doc, incomming_doc)
+        });
+
+        let change = match (causality, our_doc) {
+            (Causality::Equal | Causality::Descendant, _) => EntryChange::Unchanged,
+            // The edit is kept over the removal, see `merge`
+            (Causality::Concurrent, Some(our_doc))
+                if removal_rank(&our_doc) > 0 || removal_rank(incomming_doc) > 0 =>
+            {
+                if removal_rank(incomming_doc) < removal_rank(&our_doc) {
+                    EntryChange::Updated
+                } else {
+                    EntryChange::Unchanged
+                }
+            }
+            (Causality::Concurrent, _) => EntryChange::Conflicting,
+            // A conflicting copy that is not here yet
+            (Causality::Ancestor, _) if parent_uuid.is_some() => EntryChange::Conflicting,
+            (Causality::Ancestor, Some(_)) => EntryChange::Updated,
+            (Causality::Ancestor, None) => EntryChange::New,
+        };
+
+        Ok(change)
+    }
+
+    fn get_doc<'a>(&'a mut self, uuid: &str) -> Result<&'a Document<T>, LocalLedgerError> {
+        //TODO got some dup code with the `read` method
+        //Made this because i needed a method that retrieved the Doc struct.
+        //The read method reads the data inside a Document sturct
+        let doc_is_cached = self.doc_cache.contains(uuid);
+        let keys = &self.keys;
+
+        if doc_is_cached {
+            let mut cached_doc = self.doc_cache.get_mut(uuid).map_or(
+                Err(LocalLedgerError::new("Failed to get doc from cache")),
+                |d| Ok(d),
+            )?;
+
+            if !cached_doc.has_been_decrypted() {
+                decrypt_load_doc(&mut cached_doc, &uuid, keys)?;
+            }
+
+            return Ok(cached_doc);
+        }
+
+        let mut loaded_doc = Document::<T>::new(&self.name).with_store(&self.store);
+
+        decrypt_load_doc(&mut loaded_doc, &uuid, keys)?;
+
+        self.doc_cache.put(uuid.to_string(), loaded_doc);
+
+        let cached_doc = self
+            .doc_cache
+            .get(uuid)
+            .ok_or(LocalLedgerError::new("Failed to get doc from cache"))?;
+
+        Ok(cached_doc)
+    }
+
+    /// Checks that `entry_name` can be stored and returns the uuid of its doc
+    fn entry_uuid(&self, entry_name: &str) -> Result<String, LocalLedgerError> {
+        entry_name_to_uuid(entry_name, &self.keys)
+    }
+
+    /// Returns the name of the entry stored under `uuid`.  An entry whose name has not been
+    /// synced yet is left out.
+    fn lookup_entry_name(&self, uuid: &str) -> Option<String> {
+        let entry_name = self.entry_index.entry_name(uuid).map(str::to_owned);
+
+        if entry_name.is_none() {
+            tracing::warn!("Entry {} is missing from the entry index", uuid);
+        }
+
+        entry_name
+    }
+
+    /// Indexes the search terms of every entry from scratch.  Entries that can not be read are
+    /// left out of search.
+    fn rebuild_search_index(&mut self) -> Result<(), LocalLedgerError> {
+        let entry_names = self
+            .list_entry_labels()?
+            .into_iter()
+            .chain(self.list_entries_with_conflicts()?);
+
+        self.search_index = SearchIndex::default();
+
+        for entry_name in entry_names {
+            if let Err(err) = self.reindex_entry(&entry_name) {
+                tracing::warn!("Failed to index {} for search: {}", entry_name, err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reindex_entry(&mut self, entry_name: &str) -> Result<(), LocalLedgerError> {
+        let search_terms = self.read_by_entry_name(entry_name)?.search_terms();
+
+        self.search_index.insert(entry_name, search_terms);
+
+        Ok(())
+    }
+
+    fn store_entry_index(&mut self) -> Result<(), LocalLedgerError> {
+        self.index_doc.update(self.entry_index.clone());
+        encrypt_store_doc(&mut self.index_doc, &self.keys, INDEX_RETENTION)
+    }
+
+    /// Folds the entry index of another device into ours.  The index only ever grows, so copies
+    /// that were changed concurrently are settled by taking the names of both.  Returns true if
+    /// our index still has to be stored.
+    fn merge_entry_index(
+        &mut self,
+        mut incomming_index_doc: Document<EntryIndex>,
+    ) -> Result<bool, LocalLedgerError> {
+        decrypt_doc(&mut incomming_index_doc, &self.keys)?;
+
+        let incomming_index = incomming_index_doc.read_data()?.clone();
+        let causality =
+            if Document::<EntryIndex>::doc_exists(&self.store, &self.name, INDEX_DOC_UUID)? {
+                self.index_doc.check_for_conflict(&incomming_index_doc)
+            } else {
+                Causality::Ancestor
+            };
+
+        match causality {
+            Causality::Equal | Causality::Descendant => {
+                Ok(self.entry_index.union(&incomming_index))
+            }
+            Causality::Ancestor => {
+                incomming_index_doc.store_as_is()?;
+                self.index_doc = incomming_index_doc;
+
+                // Names of legacy docs that came in with this merge may still be missing
+                let local_index = std::mem::replace(&mut self.entry_index, incomming_index);
+
+                Ok(self.entry_index.union(&local_index))
+            }
+            Causality::Concurrent => {
+                self.index_doc.merge_version(&incomming_index_doc);
+                self.entry_index.union(&incomming_index);
+
+                Ok(true)
+            }
+        }
+    }
+
+    /// Moves docs written before entry names were hidden to their opaque uuids, recording their
+    /// names in the entry index.  Returns the uuids of the docs that were moved.
+    fn migrate_entry_names(&mut self) -> Result<Vec<String>, LocalLedgerError> {
+        let mut migrated = vec![];
+
+        for legacy_uuid in self.store.list(&self.name)? {
+            if legacy_uuid == META_DOC_UUID || legacy_uuid == INDEX_DOC_UUID {
+                continue;
+            }
+
+            let mut doc = match Document::<T>::load_encrypted(&self.store, &self.name, &legacy_uuid)
+            {
+                Ok(doc) => doc,
+                // Left for `repair`, rather than keeping the ledger from opening
+                Err(err) => {
+                    tracing::warn!("Failed to migrate the name of {}: {}", legacy_uuid, err);
+                    continue;
+                }
+            };
+
+            if doc.name_scheme() >= NAME_SCHEME {
+                continue;
+            }
+
+            let (entry_name, suffix) =
+                match legacy_uuid_to_entry_name(&legacy_uuid, doc.name_scheme()) {
+                    Ok(entry_name) => entry_name,
+                    Err(err) => {
+                        tracing::warn!("Failed to migrate the name of {}: {}", legacy_uuid, err);
+                        continue;
+                    }
+                };
+            // Names that are no longer accepted are kept anyway, rather than losing the entry
+            let entry_uuid = self.keys.entry_id(&entry_name)?;
+
+            self.entry_index.insert(&entry_uuid, &entry_name);
+            doc.append_uuid(&format!("{}{}", entry_uuid, suffix));
+            doc.set_name_scheme(NAME_SCHEME);
+            doc.store_as_is()?;
+            migrated.push(legacy_uuid);
+        }
+
+        if migrated.is_empty() {
+            return Ok(migrated);
+        }
+
+        // The legacy docs are only removed once their names are safe in the index, so an
+        // interrupted migration just picks up where it left off
+        self.store_entry_index()?;
+        migrated.iter().try_for_each(|legacy_uuid| {
+            Document::<T>::remove_doc(&self.store, &self.name, legacy_uuid)
+        })?;
+
+        Ok(migrated)
+    }
+
+    fn entry_name_already_in_use(&self, entry_name: &str) -> Result<bool, LocalLedgerError> {
+        let in_cache = self.doc_cache.contains(entry_name);
+
+        if in_cache {
+            return Ok(true);
+        }
+
+        Document::<T>::live_doc_exists(&self.store, &self.name, entry_name)
+    }
+}
+
+/// Orders the ways an entry can be removed, from live (0) through trashed to deleted for good
+fn removal_rank<T: Clone + Serialize + DeserializeOwned + Default + Debug>(
+    doc: &Document<T>,
+) -> u8 {
+    if doc.is_deleted() {
+        2
+    } else if doc.is_trashed() {
+        1
+    } else {
+        0
+    }
+}
+
+fn decrypt_load_doc<T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate>(
+    curr_doc: &mut Document<T>,
+    uuid: &str,
+    keys: &VaultKeys,
+) -> Result<(), LocalLedgerError> {
+    let store = curr_doc.get_store();
+    let loaded_doc = decrypt_load::<T>(&store, curr_doc.label(), uuid, keys)?;
+
+    let _ = std::mem::replace(curr_doc, loaded_doc);
+
+    Ok(())
+}
+
+fn decrypt_load<T>(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+    keys: &VaultKeys,
+) -> Result<Document<T>, LocalLedgerError>
+where
+    T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate,
+{
+    let mut doc = Document::<T>::load_encrypted(store, label, uuid)?;
+
+    decrypt_doc(&mut doc, keys)?;
+
+    Ok(doc)
+}
+
+/// Decrypts the doc, migrating its payload to the current schema of `T` along the way
+fn decrypt_doc<T>(doc: &mut Document<T>, keys: &VaultKeys) -> Result<(), LocalLedgerError>
+where
+    T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate,
+{
+    let schema_version = doc.schema_version();
+
+    doc.decrypt(|encrypted_data| migrate_payload::<T>(schema_version, keys.open(encrypted_data)?))?;
+
+    Ok(())
+}
+
+/// Looks for what is wrong with the doc `uuid`, see `LocalLedger::verify`
+fn check_doc<D>(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+    keys: &VaultKeys,
+) -> Option<(ProblemKind, String)>
+where
+    D: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate,
+{
+    let mut doc = match Document::<D>::load_encrypted(store, label, uuid) {
+        Ok(doc) => doc,
+        Err(err) => return Some((ProblemKind::CorruptJson, err.message)),
+    };
+
+    // Tombstones have nothing left to decrypt
+    if !doc.is_deleted() {
+        if let Err(err) = decrypt_doc(&mut doc, keys) {
+            return Some((ProblemKind::Undecryptable, err.message));
+        }
+    }
+
+    doc.rev_history_problem()
+        .map(|detail| (ProblemKind::BrokenRevHistory, detail))
+}
+
+/// Fixes the rev history of the doc `uuid` in place.  Returns false if it could not be fixed.
+fn repair_rev_history<D>(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+) -> Result<bool, LocalLedgerError>
+where
+    D: Clone + Serialize + DeserializeOwned + Default + Debug,
+{
+    let mut doc = Document::<D>::load_encrypted(store, label, uuid)?;
+
+    if !doc.repair_rev_history() {
+        return Ok(false);
+    }
+
+    doc.store_as_is()?;
+
+    Ok(true)
+}
+
+/// Seals the payloads of `doc` that were encrypted with the master password, or sealed with the
+/// vault key that was replaced, with the vault key instead.  The doc keeps its rev and version.
+/// Returns false if there was nothing to reseal.
+fn reseal_legacy_payloads<T>(
+    doc: &mut Document<T>,
+    keys: &VaultKeys,
+) -> Result<bool, LocalLedgerError>
+where
+    T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate,
+{
+    let resealed = Cell::new(false);
+
+    doc.reencrypt(|payload| {
+        if !keys.needs_reseal(payload) {
+            return Ok(payload.clone());
+        }
+
+        resealed.set(true);
+        keys.seal(&keys.open(payload)?)
+    })?;
+
+    Ok(resealed.get())
+}
+
+/// Seals every payload of `doc` with `new_keys`.  The doc keeps its rev and version.
+fn reseal_payloads<T>(
+    doc: &mut Document<T>,
+    keys: &VaultKeys,
+    new_keys: &VaultKeys,
+) -> Result<(), LocalLedgerError>
+where
+    T: Clone + Serialize + DeserializeOwned + Default + Debug,
+{
+    doc.reencrypt(|payload| new_keys.seal(&Zeroizing::new(keys.open(payload)?)))?;
+
+    Ok(())
+}
+
+fn encrypt_store_doc<T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate>(
+    doc: &mut Document<T>,
+    keys: &VaultKeys,
+    retention: RetentionPolicy,
+) -> Result<(), LocalLedgerError> {
+    doc.set_retention(retention);
+    doc.set_schema_version(T::SCHEMA_VERSION);
+    doc.set_name_scheme(NAME_SCHEME);
+    doc.store_encrypted(|data| keys.seal(data))?;
+
+    Ok(())
+}
+
+fn decrypt_load_temp_docs<T: Clone + Serialize + DeserializeOwned + Default + Debug + Migrate>(
+    store: &DocumentStoreRef,
+    label: &str,
+    keys: &VaultKeys,
+) -> Result<Vec<Document<T>>, LocalLedgerError> {
+    let temp_uuids = Document::<T>::get_all_temp_uuids(store, label)?;
+
+    temp_uuids
+        .into_iter()
+        .map(
+            |uuid| match Document::<T>::load_tombstone(store, label, &uuid)? {
+                Some(tombstone) => Ok(tombstone),
+                None => decrypt_load::<T>(store, label, &uuid, keys),
+            },
+        )
+        .collect()
+}
+
+/// Loads and decrypts the entry index of the ledger `label`.  A ledger without one gets an empty
+/// index, which is stored once the first name goes into it.
+fn load_entry_index(
+    store: &DocumentStoreRef,
+    label: &str,
+    keys: &VaultKeys,
+) -> Result<(Document<EntryIndex>, EntryIndex), LocalLedgerError> {
+    if Document::<EntryIndex>::doc_exists(store, label, INDEX_DOC_UUID)? {
+        let index_doc = decrypt_load::<EntryIndex>(store, label, INDEX_DOC_UUID, keys)?;
+        let entry_index = index_doc.read_data()?.clone();
+
+        return Ok((index_doc, entry_index));
+    }
+
+    let mut index_doc = Document::<EntryIndex>::new(label).with_store(store);
+
+    index_doc.append_uuid(INDEX_DOC_UUID);
+
+    Ok((index_doc, EntryIndex::default()))
+}
+
+fn try_load_meta_doc(
+    store: &DocumentStoreRef,
+    ledger_name: &str,
+) -> Option<Document<LocalLedgerMetaData>> {
+    match Document::<LocalLedgerMetaData>::load(store, ledger_name, META_DOC_UUID) {
+        Ok(meta_doc) => Some(meta_doc),
+        Err(_err) => None,
+    }
+}
+
+fn create_meta_doc(store: &DocumentStoreRef, ledger_name: &str) -> Document<LocalLedgerMetaData> {
+    let mut meta_doc = Document::<LocalLedgerMetaData>::new(ledger_name).with_store(store);
+
+    meta_doc.append_uuid(META_DOC_UUID);
+
+    meta_doc
+}
+
+fn assert_str(v: &Value) -> Result<String, String> {
+    match v {
+        Value::String(s) => Ok(s.to_owned()),
+
+        _ => Err("Value is not a string".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rekey::{staging_label, COMMIT_MARKER_UUID};
+    use serial_test::serial;
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
+    struct Person {
+        age: i32,
+        name: String,
+    }
+
+    impl Migrate for Person {}
+
+    impl Searchable for Person {}
+
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
+    struct SavedPassword {
+        pw: String,
+        name: String,
+    }
+
+    impl Migrate for SavedPassword {}
+
+    impl Searchable for SavedPassword {
+        fn search_terms(&self) -> Vec<String> {
+            vec![self.name.clone()]
+        }
+    }
+
+    /// `SavedPassword` after `pw` was renamed
+    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
+    struct RenamedPassword {
+        password: String,
+        name: String,
+    }
+
+    impl Searchable for RenamedPassword {}
+
+    impl Migrate for RenamedPassword {
+        const SCHEMA_VERSION: u32 = 1;
+
+        fn migrate(from_version: u32, mut payload: Value) -> Result<Value, LocalLedgerError> {
+            match from_version {
+                0 => {
+                    payload["password"] = payload["pw"].take();
+                    Ok(payload)
+                }
+                _ => Err(LocalLedgerError::new("Unknown schema version")),
+            }
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn should_check_pw_for_existing_ledgers() {
+        let _initial_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+        let open_initial_ledger_attemp =
+            LocalLedger::<Person>::new("Users", "wrong password".to_owned());
+
+        let err = open_initial_ledger_attemp.unwrap_err();
+        let expected_msg = "Incorrect password".to_string();
+
+        assert_eq!(err.to_string(), expected_msg);
+    }
 
-fn assert_str(v: &Value) -> Result<String, String> {
-    match v {
-        Value::String(s) => Ok(s.to_owned()),
+    #[test]
+    #[serial]
+    fn should_be_able_to_write_to_the_ledger() {
+        let person = Person {
+            age: 21,
+            name: "duderino".to_owned(),
+        };
+
+        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+
+        user_ledger
+            .create(person.clone(), "employee-1")
+            .expect("Failed to write to ledger");
+
+        user_ledger.remove("employee-1").unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn should_be_able_to_read_ledger() {
+        let person = Person {
+            age: 21,
+            name: "duderino".to_owned(),
+        };
+
+        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+
+        let uuid = user_ledger
+            .create(person.clone(), "employee-2")
+            .expect("Failed to write to ledger");
+
+        let received_data = user_ledger.read(uuid).unwrap();
+
+        assert_eq!(received_data, &person);
+
+        user_ledger.remove("employee-2").unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn should_be_able_to_read_in_sequence() {
+        let person = Person {
+            age: 21,
+            name: "duderino".to_owned(),
+        };
+
+        let person_1 = Person {
+            name: "walter".to_owned(),
+            ..person
+        };
+
+        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+
+        let uuid_0 = user_ledger
+            .create(person.clone(), "employee-3")
+            .expect("Failed to write to ledger");
+
+        let received_data_0 = user_ledger.read(uuid_0).unwrap();
+
+        assert_eq!(received_data_0, &person);
+
+        let uuid_1 = user_ledger
+            .create(person_1.clone(), "employee-4")
+            .expect("Failed to write to ledger");
+
+        let received_data_1 = user_ledger.read(uuid_1).unwrap();
+
+        assert_eq!(received_data_1, &person_1);
+
+        user_ledger.remove("employee-3").unwrap();
+        user_ledger.remove("employee-4").unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn should_update_doc() {
+        let person = Person {
+            age: 21,
+            name: "duderino".to_owned(),
+        };
+
+        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+
+        let uuid = user_ledger
+            .create(person.clone(), "employee-5")
+            .expect("Failed to write to ledger");
+
+        user_ledger
+            .update(
+                "employee-5",
+                Person {
+                    age: 25,
+                    ..person.clone()
+                },
+            )
+            .expect("Failed to update ledger");
+
+        let received_data = user_ledger.read(uuid).unwrap();
+
+        let expected_data = Person { age: 25, ..person };
+
+        assert_eq!(received_data, &expected_data);
+
+        user_ledger.remove("employee-5").unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn should_retrieve_all_doc_labels() {
+        let s_pw_1 = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password1234".to_owned(),
+        };
+
+        let s_pw_2 = SavedPassword {
+            name: "www.helloworld.com".to_owned(),
+            pw: "abc123".to_owned(),
+        };
+
+        let mut user_ledger =
+            LocalLedger::<SavedPassword>::new("Passwords", "master_password".to_owned()).unwrap();
+
+        user_ledger
+            .create(s_pw_1.clone(), "my example.com password")
+            .expect("Failed to write to ledger.");
+
+        user_ledger
+            .create(s_pw_2.clone(), "my helloworld.com password")
+            .expect("Failed to write to ledger.");
+
+        let received_data = user_ledger.list_entry_labels().unwrap();
+
+        let mut found_entries = 0;
+
+        for label in received_data.into_iter() {
+            if label == "my example.com password" {
+                found_entries += 1;
+            }
+
+            if label == "my helloworld.com password" {
+                found_entries += 1;
+            }
+        }
+
+        assert_eq!(found_entries, 2);
+
+        user_ledger.remove("my example.com password").unwrap();
+
+        user_ledger.remove("my helloworld.com password").unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn should_return_err_if_label_is_blank() {
+        let s_pw_1 = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password1234".to_owned(),
+        };
+
+        let mut user_ledger =
+            LocalLedger::<SavedPassword>::new("Passwords", "master_password".to_owned()).unwrap();
+
+        let err = user_ledger.create(s_pw_1, "").unwrap_err();
+
+        assert_eq!(err, LocalLedgerError::new("Label cannot be empty"));
+    }
+
+    #[test]
+    fn should_be_able_to_run_ledger_in_memory() {
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password1234".to_owned(),
+        };
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "in memory").unwrap();
+
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["in memory"]);
+        assert!(ledger.get_ledger_dir().is_err());
+
+        let mut reopened_ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            reopened_ledger.read_by_entry_name("in memory").unwrap(),
+            &s_pw
+        );
+
+        let err = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "wrong password".to_owned(),
+            store,
+        )
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Incorrect password");
+    }
+
+    #[test]
+    fn should_clean_up_interrupted_writes_on_open() {
+        let mut root = std::env::temp_dir();
+        root.push(format!("fizzgig-test-{}", utility::generate_id()));
+        let store: DocumentStoreRef =
+            std::sync::Arc::new(document::FileSystemStore::new(root.clone()));
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password1234".to_owned(),
+        };
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "survivor").unwrap();
+
+        let ledger_dir = ledger.get_ledger_dir().unwrap();
+        std::fs::write(ledger_dir.join("survivor.json.tmp"), b"{\"uuid\":").unwrap();
+        std::fs::write(ledger_dir.join("half-written.json"), b"{\"uuid\":").unwrap();
+
+        let mut reopened_ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store,
+        )
+        .unwrap();
+
+        assert_eq!(
+            reopened_ledger.list_entry_labels().unwrap(),
+            vec!["survivor"]
+        );
+        assert_eq!(
+            reopened_ledger.read_by_entry_name("survivor").unwrap(),
+            &s_pw
+        );
+        assert!(!ledger_dir.join("survivor.json.tmp").exists());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_be_able_to_restore_prior_entry_revision() {
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let original_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password1234".to_owned(),
+        };
+        let regenerated_pw = SavedPassword {
+            pw: "abc123".to_owned(),
+            ..original_pw.clone()
+        };
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store,
+        )
+        .unwrap();
+
+        ledger.create(original_pw.clone(), "example").unwrap();
+        ledger.update("example", regenerated_pw.clone()).unwrap();
+
+        let history = ledger.entry_history("example").unwrap();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].data, original_pw);
+
+        ledger
+            .restore_entry_revision("example", &history[0].rev)
+            .unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &original_pw);
+
+        let history = ledger.entry_history("example").unwrap();
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].data, regenerated_pw);
+    }
+
+    /// Rewrites a stored doc the way it was laid out before format versions existed
+    fn downgrade_to_legacy_format(store: &DocumentStoreRef, uuid: &str) {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let mut doc_json: Value =
+            serde_json::from_slice(&store.read("Passwords", uuid).unwrap()).unwrap();
+
+        if let Some(encoded) = doc_json["encrypted_data"].as_str() {
+            doc_json["encrypted_data"] = serde_json::json!(STANDARD.decode(encoded).unwrap());
+        }
+
+        doc_json.as_object_mut().unwrap().remove("format");
+        store
+            .write("Passwords", uuid, &serde_json::to_vec(&doc_json).unwrap())
+            .unwrap();
+    }
+
+    #[test]
+    fn should_read_and_upgrade_legacy_ledgers() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        let uuid = ledger.entry_uuid("example").unwrap();
+        downgrade_to_legacy_format(&store, &uuid);
+        downgrade_to_legacy_format(&store, META_DOC_UUID);
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+        assert_eq!(ledger.upgrade_format().unwrap(), 2);
+        assert_eq!(ledger.upgrade_format().unwrap(), 0);
+
+        let doc_json: Value =
+            serde_json::from_slice(&store.read("Passwords", &uuid).unwrap()).unwrap();
+
+        assert_eq!(doc_json["format"], document::CURRENT_FORMAT);
+        assert!(doc_json["encrypted_data"].is_string());
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+
+        ledger
+            .update(
+                "example",
+                SavedPassword {
+                    pw: "new_password".to_owned(),
+                    ..s_pw.clone()
+                },
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn should_migrate_entries_written_with_older_schema() {
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger
+            .create(
+                SavedPassword {
+                    name: "www.example.com".to_owned(),
+                    pw: "password".to_owned(),
+                },
+                "example",
+            )
+            .unwrap();
+
+        let mut ledger = LocalLedger::<RenamedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let expected = RenamedPassword {
+            name: "www.example.com".to_owned(),
+            password: "password".to_owned(),
+        };
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &expected);
+        assert_eq!(ledger.migrate_entries().unwrap(), 1);
+        assert_eq!(ledger.migrate_entries().unwrap(), 0);
+
+        let uuid = ledger.entry_uuid("example").unwrap();
+        let doc_json: Value =
+            serde_json::from_slice(&store.read("Passwords", &uuid).unwrap()).unwrap();
+
+        assert_eq!(doc_json["schema_version"], 1);
+
+        // The entry's history still holds the payload written with the old schema
+        let history = ledger.entry_history("example").unwrap();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].data, expected);
+    }
+
+    #[test]
+    fn should_not_open_ledger_locked_by_another_process() {
+        let mut root = std::env::temp_dir();
+        root.push(format!("fizzgig-test-{}", utility::generate_id()));
+        let store: DocumentStoreRef =
+            std::sync::Arc::new(document::FileSystemStore::new(root.clone()));
+        let open = |lock_mode| {
+            LocalLedger::<SavedPassword>::new_with_lock_mode(
+                "Passwords",
+                "master_password".to_owned(),
+                store.clone(),
+                lock_mode,
+            )
+        };
+
+        let shared_ledger = open(LockMode::Shared).unwrap();
+        let mut other_shared_ledger = open(LockMode::Shared).unwrap();
+        let err = open(LockMode::Exclusive).unwrap_err();
+
+        assert!(err.is_locked_err());
+
+        other_shared_ledger
+            .create(SavedPassword::default(), "example")
+            .unwrap();
+
+        drop(shared_ledger);
+        drop(other_shared_ledger);
+
+        let _exclusive_ledger = open(LockMode::Exclusive).unwrap();
+        let err = open(LockMode::Shared).unwrap_err();
+
+        assert!(err.is_locked_err());
+        assert_eq!(err.message, "Passwords is in use by another process");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    fn dump_values(
+        store: &DocumentStoreRef,
+        label: &str,
+    ) -> Vec<Result<Value, Box<dyn std::error::Error>>> {
+        store
+            .list(label)
+            .unwrap()
+            .into_iter()
+            .map(|uuid| Ok(serde_json::from_slice(&store.read(label, &uuid).unwrap()).unwrap()))
+            .collect()
+    }
+
+    /// Opens a ledger on a new device that shares the meta doc of `origin`
+    fn open_on_new_device(origin: &DocumentStoreRef) -> LocalLedger<SavedPassword> {
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let meta_doc = origin.read("Passwords", META_DOC_UUID).unwrap();
+
+        store.write("Passwords", META_DOC_UUID, &meta_doc).unwrap();
+
+        LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_merge_updates_across_three_devices() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+        let mut ledger_c = open_on_new_device(&store_a);
+
+        ledger_a.create(s_pw("pw_1"), "example").unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        assert_eq!(
+            ledger_b.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_1")
+        );
+
+        ledger_b.update("example", s_pw("pw_2")).unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_c.merge(tokio_stream::iter(dump_b)).await.unwrap();
+        ledger_c.update("example", s_pw("pw_3")).unwrap();
+
+        // A never merged B's update, but C's copy builds on top of it
+        let dump_c = dump_values(&ledger_c.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_c)).await.unwrap();
+
+        assert!(ledger_a.list_entries_with_conflicts().unwrap().is_empty());
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_3")
+        );
+
+        // Merging an older copy changes nothing
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+
+        assert!(ledger_a.list_entries_with_conflicts().unwrap().is_empty());
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_3")
+        );
+
+        // Edits nobody has seen from the other side are a real conflict
+        ledger_a.update("example", s_pw("pw_4")).unwrap();
+        ledger_b.update("example", s_pw("pw_5")).unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+
+        assert_eq!(
+            ledger_a.list_entries_with_conflicts().unwrap(),
+            vec!["example"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_keep_every_conflicting_copy() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+        let mut ledger_c = open_on_new_device(&store_a);
+
+        ledger_a.create(s_pw("pw_1"), "example").unwrap();
+
+        for (ledger, pw) in [(&mut ledger_b, "pw_2"), (&mut ledger_c, "pw_3")] {
+            let dump_a = dump_values(&store_a, "Passwords");
+            ledger.merge(tokio_stream::iter(dump_a)).await.unwrap();
+            ledger.update("example", s_pw(pw)).unwrap();
+        }
+
+        ledger_a.update("example", s_pw("pw_4")).unwrap();
+
+        for ledger in [&ledger_b, &ledger_c] {
+            let dump = dump_values(&ledger.store, "Passwords");
+            ledger_a.merge(tokio_stream::iter(dump)).await.unwrap();
+        }
 
-        _ => Err("Value is not a string".to_string()),
+        let conf_docs = ledger_a.get_conf("example").unwrap();
+        let mut conf_pws: Vec<_> = conf_docs
+            .iter()
+            .map(|conf_doc| conf_doc.read_data().unwrap().pw.clone())
+            .collect();
+        conf_pws.sort();
+
+        assert_eq!(conf_pws, vec!["pw_2", "pw_3"]);
+        assert_eq!(
+            ledger_a.list_entries_with_conflicts().unwrap(),
+            vec!["example"]
+        );
+        assert!(ledger_a
+            .resolve("example", Resolution::Sibling("not a rev".to_owned()))
+            .is_err());
+
+        let kept_doc = conf_docs
+            .iter()
+            .find(|conf_doc| conf_doc.read_data().unwrap().pw == "pw_3")
+            .unwrap();
+        ledger_a
+            .resolve("example", Resolution::Sibling(kept_doc.rev().to_owned()))
+            .unwrap();
+
+        assert!(ledger_a.list_entries_with_conflicts().unwrap().is_empty());
+        assert!(ledger_a.get_conf("example").unwrap().is_empty());
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_3")
+        );
+
+        // The copies that were dropped do not come back, and the resolution wins on the other
+        // devices
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        for ledger in [&mut ledger_a, &mut ledger_b] {
+            assert!(ledger.list_entries_with_conflicts().unwrap().is_empty());
+            assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_3"));
+        }
+
+        // A value merged by hand settles the conflict as well
+        ledger_a.update("example", s_pw("pw_5")).unwrap();
+        ledger_b.update("example", s_pw("pw_6")).unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+        ledger_a
+            .resolve("example", Resolution::Merged(s_pw("pw_7")))
+            .unwrap();
+
+        assert!(ledger_a.list_entries_with_conflicts().unwrap().is_empty());
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_7")
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serial_test::serial;
+    #[test]
+    fn should_keep_deleted_entries_out_of_the_ledger() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        ledger.remove("example").unwrap();
+        let uuid = ledger.entry_uuid("example").unwrap();
+
+        assert!(ledger.list_entry_labels().unwrap().is_empty());
+        assert!(ledger.read_by_entry_name("example").is_err());
+        assert!(ledger.update("example", s_pw.clone()).is_err());
+        assert!(store.exists("Passwords", &uuid).unwrap());
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["example"]);
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+
+        ledger.remove("example").unwrap();
+
+        assert_eq!(
+            ledger.purge_tombstones(Duration::from_secs(3600)).unwrap(),
+            0
+        );
+        assert_eq!(ledger.purge_tombstones(Duration::ZERO).unwrap(), 1);
+        assert!(!store.exists("Passwords", &uuid).unwrap());
+    }
 
-    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
-    struct Person {
-        age: i32,
-        name: String,
+    #[tokio::test]
+    async fn should_keep_search_index_current() {
+        let s_pw = |site: &str| SavedPassword {
+            name: site.to_owned(),
+            pw: "password".to_owned(),
+        };
+        let entry_names = |hits: Vec<SearchHit>| -> Vec<String> {
+            hits.into_iter().map(|hit| hit.entry_name).collect()
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw("www.github.com"), "work").unwrap();
+        ledger.create(s_pw("www.bank.com"), "bank").unwrap();
+
+        assert_eq!(entry_names(ledger.search("GITHUB")), vec!["work"]);
+        assert_eq!(entry_names(ledger.search("bnk")), vec!["bank"]);
+
+        ledger.update("work", s_pw("www.gitlab.com")).unwrap();
+        ledger.remove("bank").unwrap();
+
+        assert!(ledger.search("github").is_empty());
+        assert!(ledger.search("bank").is_empty());
+        assert_eq!(entry_names(ledger.search("gitlab")), vec!["work"]);
+
+        // Built from scratch when the ledger is opened
+        drop(ledger);
+        let ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(entry_names(ledger.search("gitlab")), vec!["work"]);
+
+        let mut other_ledger = open_on_new_device(&store);
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        assert_eq!(entry_names(other_ledger.search("work")), vec!["work"]);
+        assert_eq!(entry_names(other_ledger.search("gitlab")), vec!["work"]);
+        assert!(other_ledger.search("bank").is_empty());
     }
 
-    #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
-    struct SavedPassword {
-        pw: String,
-        name: String,
+    #[tokio::test]
+    async fn should_sync_deletions() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+
+        ledger_a.create(s_pw("pw_1"), "deleted").unwrap();
+        ledger_a.create(s_pw("pw_1"), "edited_on_b").unwrap();
+        ledger_a.create(s_pw("pw_1"), "edited_on_a").unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        ledger_a.remove("deleted").unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        assert!(ledger_b.read_by_entry_name("deleted").is_err());
+
+        // The older copy on B does not bring the entry back
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+
+        assert!(ledger_a.read_by_entry_name("deleted").is_err());
+
+        // Deleting and editing at the same time keeps the edit, whichever side it is on
+        ledger_a.remove("edited_on_b").unwrap();
+        ledger_b.update("edited_on_b", s_pw("pw_2")).unwrap();
+        ledger_a.update("edited_on_a", s_pw("pw_2")).unwrap();
+        ledger_b.remove("edited_on_a").unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        for ledger in [&mut ledger_a, &mut ledger_b] {
+            let mut labels = ledger.list_entry_labels().unwrap();
+            labels.sort();
+
+            assert_eq!(labels, vec!["edited_on_a", "edited_on_b"]);
+            assert!(ledger.list_entries_with_conflicts().unwrap().is_empty());
+            assert_eq!(
+                ledger.read_by_entry_name("edited_on_a").unwrap(),
+                &s_pw("pw_2")
+            );
+            assert_eq!(
+                ledger.read_by_entry_name("edited_on_b").unwrap(),
+                &s_pw("pw_2")
+            );
+        }
     }
 
-    #[test]
-    #[serial]
-    fn should_check_pw_for_existing_ledgers() {
-        let _initial_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
-        let open_initial_ledger_attemp =
-            LocalLedger::<Person>::new("Users", "wrong password".to_owned());
+    #[tokio::test]
+    async fn should_preview_merges_without_changing_the_ledger() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+
+        for entry_name in ["example", "other", "unchanged"] {
+            ledger_a.create(s_pw("pw_1"), entry_name).unwrap();
+        }
 
-        let err = open_initial_ledger_attemp.unwrap_err();
-        let expected_msg = "Incorrect password".to_string();
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+        ledger_b.update("example", s_pw("pw_2")).unwrap();
+        ledger_b.update("other", s_pw("pw_3")).unwrap();
+        ledger_b.create(s_pw("pw_4"), "new").unwrap();
+        ledger_a.update("other", s_pw("pw_5")).unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        let preview = ledger_a
+            .merge_preview(tokio_stream::iter(dump_b))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            preview,
+            MergePreview {
+                new_entries: vec!["new".to_owned()],
+                updated_entries: vec!["example".to_owned()],
+                unchanged_entries: vec!["unchanged".to_owned()],
+                conflicting_entries: vec!["other".to_owned()],
+                meta_doc: MetaDocCompatibility::Unchanged,
+            }
+        );
+        assert_eq!(
+            ledger_a.list_entry_labels().unwrap(),
+            vec!["example", "other", "unchanged"]
+        );
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_1")
+        );
+        assert!(ledger_a.list_entries_with_conflicts().unwrap().is_empty());
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+
+        assert_eq!(
+            ledger_a.list_entry_labels().unwrap(),
+            vec!["example", "new", "unchanged"]
+        );
+        assert_eq!(
+            ledger_a.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_2")
+        );
+        assert_eq!(
+            ledger_a.list_entries_with_conflicts().unwrap(),
+            vec!["other"]
+        );
+
+        // Older versions stored a newer meta doc when they made the identity of the vault
+        ledger_b.meta_doc.store().unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        let preview = ledger_a
+            .merge_preview(tokio_stream::iter(dump_b))
+            .await
+            .unwrap();
+
+        assert_eq!(preview.meta_doc, MetaDocCompatibility::Updated);
+        assert!(preview.new_entries.is_empty());
+        assert!(preview.updated_entries.is_empty());
+    }
 
-        assert_eq!(err.to_string(), expected_msg);
+    #[test]
+    fn should_destroy_ledgers() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut other_ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Other",
+            "other_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        other_ledger.create(s_pw.clone(), "example").unwrap();
+
+        assert!(LocalLedger::<SavedPassword>::exists(&store, "Passwords").unwrap());
+
+        ledger.destroy().unwrap();
+
+        assert!(!LocalLedger::<SavedPassword>::exists(&store, "Passwords").unwrap());
+        assert!(store.list("Passwords").unwrap().is_empty());
+        assert_eq!(other_ledger.read_by_entry_name("example").unwrap(), &s_pw);
+
+        // The name can be used for a new ledger with a password of its own
+        let ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "new_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert!(ledger.list_entry_labels().unwrap().is_empty());
     }
 
     #[test]
-    #[serial]
-    fn should_be_able_to_write_to_the_ledger() {
-        let person = Person {
-            age: 21,
-            name: "duderino".to_owned(),
+    fn should_find_and_repair_damaged_docs() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
         };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut other_ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Other",
+            "other_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        for entry_name in ["good", "corrupt", "undecryptable", "history"] {
+            ledger.create(s_pw.clone(), entry_name).unwrap();
+        }
 
-        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+        other_ledger.create(s_pw.clone(), "undecryptable").unwrap();
+
+        assert!(ledger.verify().unwrap().is_empty());
+
+        let good_uuid = ledger.entry_uuid("good").unwrap();
+        let good_doc = store.read("Passwords", &good_uuid).unwrap();
+        let corrupt_uuid = ledger.entry_uuid("corrupt").unwrap();
+        let undecryptable_uuid = ledger.entry_uuid("undecryptable").unwrap();
+        let history_uuid = ledger.entry_uuid("history").unwrap();
+        let temp_uuid = format!("{}_TEMP", good_uuid);
+        let conflict_uuid = Document::<SavedPassword>::conflict_uuid("missing", "REV");
+
+        store
+            .write("Passwords", &corrupt_uuid, b"{ not json")
+            .unwrap();
+        store
+            .write(
+                "Passwords",
+                &undecryptable_uuid,
+                &store
+                    .read("Other", &other_ledger.entry_uuid("undecryptable").unwrap())
+                    .unwrap(),
+            )
+            .unwrap();
+        store.write("Passwords", &temp_uuid, &good_doc).unwrap();
+        store.write("Passwords", &conflict_uuid, &good_doc).unwrap();
+
+        let mut history_doc: Value =
+            serde_json::from_slice(&store.read("Passwords", &history_uuid).unwrap()).unwrap();
+        let rev = history_doc["rev"].clone();
+        history_doc["rev_history"].as_array_mut().unwrap().push(rev);
+        store
+            .write(
+                "Passwords",
+                &history_uuid,
+                &serde_json::to_vec(&history_doc).unwrap(),
+            )
+            .unwrap();
 
-        user_ledger
-            .create(person.clone(), "employee-1")
-            .expect("Failed to write to ledger");
+        let mut problems: Vec<_> = ledger
+            .verify()
+            .unwrap()
+            .into_iter()
+            .map(|problem| (problem.uuid, problem.entry_name, problem.kind))
+            .collect();
+        problems.sort_by_key(|(_uuid, _entry_name, kind)| *kind as u8);
+
+        assert_eq!(
+            problems,
+            vec![
+                (
+                    corrupt_uuid.clone(),
+                    Some("corrupt".to_owned()),
+                    ProblemKind::CorruptJson
+                ),
+                (
+                    undecryptable_uuid.clone(),
+                    Some("undecryptable".to_owned()),
+                    ProblemKind::Undecryptable
+                ),
+                (
+                    temp_uuid.clone(),
+                    Some("good".to_owned()),
+                    ProblemKind::OrphanedTemp
+                ),
+                (conflict_uuid.clone(), None, ProblemKind::OrphanedConflict),
+                (
+                    history_uuid.clone(),
+                    Some("history".to_owned()),
+                    ProblemKind::BrokenRevHistory
+                ),
+            ]
+        );
+
+        let outcomes: Vec<_> = ledger
+            .repair()
+            .unwrap()
+            .into_iter()
+            .map(|repaired| (repaired.problem.kind, repaired.outcome))
+            .collect();
 
-        user_ledger.remove("employee-1").unwrap();
+        assert_eq!(outcomes.len(), 5);
+        assert!(outcomes.iter().all(|(kind, outcome)| match kind {
+            ProblemKind::BrokenRevHistory => *outcome == RepairOutcome::Fixed,
+            _ => *outcome == RepairOutcome::Quarantined,
+        }));
+        assert!(ledger.verify().unwrap().is_empty());
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["good", "history"]);
+        assert_eq!(ledger.read_by_entry_name("history").unwrap(), &s_pw);
+
+        let mut quarantined = store.list(&quarantine_label("Passwords")).unwrap();
+        quarantined.sort();
+        let mut expected = vec![corrupt_uuid, undecryptable_uuid, temp_uuid, conflict_uuid];
+        expected.sort();
+
+        assert_eq!(quarantined, expected);
+
+        ledger.destroy().unwrap();
+
+        assert!(store
+            .list(&quarantine_label("Passwords"))
+            .unwrap()
+            .is_empty());
     }
 
     #[test]
-    #[serial]
-    fn should_be_able_to_read_ledger() {
-        let person = Person {
-            age: 21,
-            name: "duderino".to_owned(),
+    fn should_share_entries_with_public_keys() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut alice = LocalLedger::<SavedPassword>::new_with_store(
+            "Alice",
+            "alice_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut bob = LocalLedger::<SavedPassword>::new_with_store(
+            "Bob",
+            "bob_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut eve = LocalLedger::<SavedPassword>::new_with_store(
+            "Eve",
+            "eve_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let bob_public_key = bob.public_key().unwrap();
+
+        assert!(bob_public_key.starts_with("age1"));
+        assert_eq!(bob.public_key().unwrap(), bob_public_key);
+
+        alice.create(s_pw.clone(), "example").unwrap();
+
+        let shared = alice.share_entry("example", &[bob_public_key]).unwrap();
+
+        assert_eq!(bob.import_shared_entry(&shared).unwrap(), "example");
+        assert_eq!(bob.read_by_entry_name("example").unwrap(), &s_pw);
+        assert!(eve.import_shared_entry(&shared).is_err());
+
+        // Sharing again updates the copy, and the identity survives reopening the ledger
+        let s_pw_2 = SavedPassword {
+            pw: "password_2".to_owned(),
+            ..s_pw.clone()
         };
 
-        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+        alice.update("example", s_pw_2.clone()).unwrap();
 
-        let uuid = user_ledger
-            .create(person.clone(), "employee-2")
-            .expect("Failed to write to ledger");
+        let shared = alice
+            .share_entry("example", &[bob.public_key().unwrap()])
+            .unwrap();
 
-        let received_data = user_ledger.read(uuid).unwrap();
+        drop(bob);
 
-        assert_eq!(received_data, &person);
+        let mut bob = LocalLedger::<SavedPassword>::new_with_store(
+            "Bob",
+            "bob_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
 
-        user_ledger.remove("employee-2").unwrap();
+        bob.import_shared_entry(&shared).unwrap();
+
+        assert_eq!(bob.read_by_entry_name("example").unwrap(), &s_pw_2);
+        assert_eq!(bob.list_entry_labels().unwrap(), vec!["example"]);
+
+        // The sharer can read it back as well
+        alice.remove("example").unwrap();
+        alice.import_shared_entry(&shared).unwrap();
+
+        assert_eq!(alice.read_by_entry_name("example").unwrap(), &s_pw_2);
+    }
+
+    #[tokio::test]
+    async fn should_keep_the_password_of_each_device_when_merging() {
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+        let store_b = ledger_b.store.clone();
+
+        ledger_a
+            .change_password("master_password", "password_a".to_owned())
+            .unwrap();
+        ledger_a
+            .set_keyfile("password_a", Some(b"keyfile"))
+            .unwrap();
+        ledger_b
+            .change_password("master_password", "password_b".to_owned())
+            .unwrap();
+
+        // Older versions stored a newer meta doc when they made the identity of the vault
+        ledger_a.meta_doc.store().unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        assert!(!ledger_b.uses_keyfile().unwrap());
+        assert!(ledger_b.verify().unwrap().is_empty());
+
+        drop(ledger_b);
+
+        assert!(LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "password_a".to_owned(),
+            store_b.clone(),
+        )
+        .is_err());
+        assert!(LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "password_b".to_owned(),
+            store_b,
+        )
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_have_the_same_public_key_on_every_device() {
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+
+        // Both devices ask for it before they have synced
+        assert_eq!(
+            ledger_a.public_key().unwrap(),
+            ledger_b.public_key().unwrap()
+        );
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
     }
 
     #[test]
-    #[serial]
-    fn should_be_able_to_read_in_sequence() {
-        let person = Person {
-            age: 21,
-            name: "duderino".to_owned(),
+    fn should_move_entries_to_the_trash() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
         };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        ledger
+            .create(
+                SavedPassword {
+                    name: "www.other.com".to_owned(),
+                    pw: "password".to_owned(),
+                },
+                "kept",
+            )
+            .unwrap();
+        ledger.trash("example").unwrap();
 
-        let person_1 = Person {
-            name: "walter".to_owned(),
-            ..person
+        let trash = ledger.list_trash().unwrap();
+
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["kept"]);
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].entry_name, "example");
+        assert!(ledger.search("example").is_empty());
+        assert!(ledger.update("example", s_pw.clone()).is_err());
+        assert!(ledger.trash("example").is_err());
+        assert!(ledger.create(s_pw.clone(), "example").is_err());
+
+        ledger.restore_from_trash("example").unwrap();
+
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["example", "kept"]);
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+        assert_eq!(ledger.search("example").len(), 1);
+        assert!(ledger.list_trash().unwrap().is_empty());
+        assert!(ledger.restore_from_trash("example").is_err());
+
+        ledger.trash("example").unwrap();
+
+        assert_eq!(ledger.purge_trash(Duration::from_secs(3600)).unwrap(), 0);
+        assert_eq!(ledger.purge_trash(Duration::ZERO).unwrap(), 1);
+        assert!(ledger.list_trash().unwrap().is_empty());
+        assert!(ledger.restore_from_trash("example").is_err());
+
+        // Purged entries are deleted like any other, so their name is free again
+        ledger.create(s_pw.clone(), "example").unwrap();
+        ledger.trash("example").unwrap();
+        ledger.remove("example").unwrap();
+
+        assert!(ledger.list_trash().unwrap().is_empty());
+        assert_eq!(ledger.list_entry_labels().unwrap(), vec!["kept"]);
+    }
+
+    #[tokio::test]
+    async fn should_trash_and_purge_conflicted_entries() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
         };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut other_ledger = open_on_new_device(&store);
+
+        ledger.create(s_pw("pw_1"), "example").unwrap();
+        ledger.create(s_pw("pw_1"), "trashed_before").unwrap();
+
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        for entry_name in ["example", "trashed_before"] {
+            other_ledger.update(entry_name, s_pw("pw_2")).unwrap();
+            ledger.update(entry_name, s_pw("pw_3")).unwrap();
+        }
 
-        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+        let dump = dump_values(&other_ledger.store, "Passwords");
+        ledger.merge(tokio_stream::iter(dump)).await.unwrap();
 
-        let uuid_0 = user_ledger
-            .create(person.clone(), "employee-3")
-            .expect("Failed to write to ledger");
+        assert_eq!(
+            ledger.list_entries_with_conflicts().unwrap(),
+            vec!["example", "trashed_before"]
+        );
 
-        let received_data_0 = user_ledger.read(uuid_0).unwrap();
+        ledger.trash("example").unwrap();
 
-        assert_eq!(received_data_0, &person);
+        // Older versions trashed the entry and left its conflicting copies
+        let uuid = ledger.entry_uuid("trashed_before").unwrap();
 
-        let uuid_1 = user_ledger
-            .create(person_1.clone(), "employee-4")
-            .expect("Failed to write to ledger");
+        Document::<SavedPassword>::load_encrypted(&store, "Passwords", &uuid)
+            .unwrap()
+            .trash()
+            .unwrap();
 
-        let received_data_1 = user_ledger.read(uuid_1).unwrap();
+        let trashed_names = |ledger: &LocalLedger<SavedPassword>| -> Vec<String> {
+            ledger
+                .list_trash()
+                .unwrap()
+                .into_iter()
+                .map(|trashed_entry| trashed_entry.entry_name)
+                .collect()
+        };
 
-        assert_eq!(received_data_1, &person_1);
+        assert_eq!(trashed_names(&ledger), vec!["example", "trashed_before"]);
+        assert!(ledger.list_entries_with_conflicts().unwrap().is_empty());
+        assert!(Document::<SavedPassword>::get_conflict_uuids(
+            &store,
+            "Passwords",
+            &ledger.entry_uuid("example").unwrap()
+        )
+        .unwrap()
+        .is_empty());
+
+        // The trashed copy wins over the conflicting one on the other device
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        assert!(trashed_names(&other_ledger).contains(&"example".to_owned()));
+        assert!(other_ledger
+            .list_entries_with_conflicts()
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(ledger.purge_trash(Duration::ZERO).unwrap(), 2);
+        assert!(trashed_names(&ledger).is_empty());
+        assert!(ledger.list_entries_with_conflicts().unwrap().is_empty());
+        assert!(ledger.list_entry_labels().unwrap().is_empty());
+        assert!(
+            Document::<SavedPassword>::get_conflict_uuids(&store, "Passwords", &uuid)
+                .unwrap()
+                .is_empty()
+        );
+    }
 
-        user_ledger.remove("employee-3").unwrap();
-        user_ledger.remove("employee-4").unwrap();
+    #[tokio::test]
+    async fn should_sync_trash() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store_a: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger_a = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store_a.clone(),
+        )
+        .unwrap();
+        let mut ledger_b = open_on_new_device(&store_a);
+
+        ledger_a.create(s_pw("pw_1"), "trashed").unwrap();
+        ledger_a.create(s_pw("pw_1"), "edited_on_b").unwrap();
+        ledger_a.create(s_pw("pw_1"), "deleted_on_b").unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        ledger_a.trash("trashed").unwrap();
+
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        assert_eq!(ledger_b.list_trash().unwrap()[0].entry_name, "trashed");
+
+        // Trashing and editing at the same time keeps the edit, trashing and deleting keeps the
+        // entry in the trash
+        ledger_a.trash("edited_on_b").unwrap();
+        ledger_b.update("edited_on_b", s_pw("pw_2")).unwrap();
+        ledger_a.trash("deleted_on_b").unwrap();
+        ledger_b.remove("deleted_on_b").unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+        let dump_a = dump_values(&store_a, "Passwords");
+        ledger_b.merge(tokio_stream::iter(dump_a)).await.unwrap();
+
+        for ledger in [&mut ledger_a, &mut ledger_b] {
+            let trashed_names: Vec<String> = ledger
+                .list_trash()
+                .unwrap()
+                .into_iter()
+                .map(|trashed_entry| trashed_entry.entry_name)
+                .collect();
+
+            assert_eq!(ledger.list_entry_labels().unwrap(), vec!["edited_on_b"]);
+            assert_eq!(trashed_names, vec!["deleted_on_b", "trashed"]);
+            assert!(ledger.list_entries_with_conflicts().unwrap().is_empty());
+            assert_eq!(
+                ledger.read_by_entry_name("edited_on_b").unwrap(),
+                &s_pw("pw_2")
+            );
+        }
+
+        ledger_b.restore_from_trash("trashed").unwrap();
+
+        let dump_b = dump_values(&ledger_b.store, "Passwords");
+        ledger_a.merge(tokio_stream::iter(dump_b)).await.unwrap();
+
+        assert_eq!(
+            ledger_a.list_entry_labels().unwrap(),
+            vec!["edited_on_b", "trashed"]
+        );
+        assert_eq!(ledger_a.search("trashed").len(), 1);
     }
 
-    #[test]
-    #[serial]
-    fn should_update_doc() {
-        let person = Person {
-            age: 21,
-            name: "duderino".to_owned(),
+    #[tokio::test]
+    async fn should_store_entries_under_any_name() {
+        let names = [
+            "my_bank",
+            "My_bank",
+            "../../etc/passwd",
+            "bank",
+            "Bank",
+            "foo_CONF",
+        ];
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
         };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        for name in names {
+            ledger.create(s_pw(name), name).unwrap();
+        }
 
-        let mut user_ledger = LocalLedger::<Person>::new("Users", "password".to_owned()).unwrap();
+        let mut labels = ledger.list_entry_labels().unwrap();
+        let mut expected = names.to_vec();
+        labels.sort();
+        expected.sort();
 
-        let uuid = user_ledger
-            .create(person.clone(), "employee-5")
-            .expect("Failed to write to ledger");
+        assert_eq!(labels, expected);
+        assert!(ledger.create(s_pw("bank"), "bank").is_err());
+        assert!(ledger.create(s_pw(""), "").is_err());
 
-        user_ledger
+        // Names that used to be mangled by the temp docs of a merge come through intact
+        let mut other_ledger = open_on_new_device(&store);
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        for name in names {
+            assert_eq!(other_ledger.read_by_entry_name(name).unwrap(), &s_pw(name));
+        }
+
+        assert_eq!(other_ledger.list_entry_labels().unwrap(), expected);
+
+        // Nothing on disk gives the names away
+        for uuid in store.list("Passwords").unwrap() {
+            let contents = String::from_utf8(store.read("Passwords", &uuid).unwrap()).unwrap();
+
+            assert!(names
+                .iter()
+                .all(|name| !uuid.contains(name) && !contents.contains(name)));
+        }
+    }
+
+    /// Moves a stored doc to where it was kept before entry names were encoded
+    fn write_legacy_doc(store: &DocumentStoreRef, uuid: &str, legacy_uuid: &str) {
+        let mut doc_json: Value =
+            serde_json::from_slice(&store.read("Passwords", uuid).unwrap()).unwrap();
+
+        doc_json["uuid"] = Value::from(legacy_uuid);
+        doc_json.as_object_mut().unwrap().remove("name_scheme");
+        store
+            .write(
+                "Passwords",
+                legacy_uuid,
+                &serde_json::to_vec(&doc_json).unwrap(),
+            )
+            .unwrap();
+        store.remove("Passwords", uuid).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_migrate_legacy_entry_names() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "bank").unwrap();
+        let bank_uuid = ledger.entry_uuid("bank").unwrap();
+        drop(ledger);
+
+        // The conflicting copy was edited on a third device
+        let mut third_ledger = open_on_new_device(&store);
+        let dump = dump_values(&store, "Passwords");
+        third_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+        third_ledger
             .update(
-                "employee-5",
-                Person {
-                    age: 25,
-                    ..person.clone()
+                "bank",
+                SavedPassword {
+                    pw: "other password".to_owned(),
+                    ..s_pw.clone()
                 },
             )
-            .expect("Failed to update ledger");
+            .unwrap();
+        let conf_doc = third_ledger.store.read("Passwords", &bank_uuid).unwrap();
+        store
+            .write("Passwords", "bank conflict", &conf_doc)
+            .unwrap();
+        write_legacy_doc(&store, &bank_uuid, "my_bank");
+        write_legacy_doc(&store, "bank conflict", "my_bank_CONF");
+        // Ledgers from before entry names were hidden have no index
+        store.remove("Passwords", INDEX_DOC_UUID).unwrap();
+
+        // A device that has not migrated yet shares its docs
+        let mut other_ledger = open_on_new_device(&store);
+        let legacy_dump: Vec<_> = dump_values(&store, "Passwords")
+            .into_iter()
+            .filter(|doc| doc.as_ref().unwrap()["uuid"] != "my_bank_CONF")
+            .collect();
+        other_ledger
+            .merge(tokio_stream::iter(legacy_dump))
+            .await
+            .unwrap();
+
+        assert_eq!(other_ledger.read_by_entry_name("my_bank").unwrap(), &s_pw);
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(ledger.list_entry_labels().unwrap(), Vec::<String>::new());
+        assert_eq!(
+            ledger.list_entries_with_conflicts().unwrap(),
+            vec!["my_bank"]
+        );
+        assert_eq!(ledger.read_by_entry_name("my_bank").unwrap(), &s_pw);
+
+        let uuid = ledger.entry_uuid("my_bank").unwrap();
+        let mut stored_uuids = store.list("Passwords").unwrap();
+        let mut expected = vec![
+            INDEX_DOC_UUID.to_owned(),
+            META_DOC_UUID.to_owned(),
+            format!("{}_CONF", uuid),
+            uuid,
+        ];
+        stored_uuids.sort();
+        expected.sort();
+
+        assert_eq!(stored_uuids, expected);
+
+        // Both devices now agree on the entry and its conflict
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        assert_eq!(other_ledger.read_by_entry_name("my_bank").unwrap(), &s_pw);
+        assert_eq!(
+            other_ledger.list_entries_with_conflicts().unwrap(),
+            vec!["my_bank"]
+        );
+    }
 
-        let received_data = user_ledger.read(uuid).unwrap();
+    #[test]
+    fn should_require_keyfile_once_added() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let keyfile = crate::generate_keyfile();
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let open = |composite_key: CompositeKey| {
+            LocalLedger::<SavedPassword>::new_with_composite_key(
+                "Passwords",
+                composite_key,
+                store.clone(),
+                LockMode::Shared,
+            )
+        };
+        let password_only = || CompositeKey::new("master_password".to_owned());
+        let with_keyfile = || password_only().with_keyfile(&keyfile);
+        let mut ledger = open(password_only()).unwrap();
 
-        let expected_data = Person { age: 25, ..person };
+        ledger.create(s_pw.clone(), "example").unwrap();
 
-        assert_eq!(received_data, &expected_data);
+        assert!(!ledger.uses_keyfile().unwrap());
+        assert!(ledger
+            .set_keyfile("not the password", Some(&keyfile))
+            .is_err());
 
-        user_ledger.remove("employee-5").unwrap();
+        ledger
+            .set_keyfile("master_password", Some(&keyfile))
+            .unwrap();
+
+        assert!(ledger.uses_keyfile().unwrap());
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+        drop(ledger);
+
+        assert_eq!(
+            open(password_only()).unwrap_err().to_string(),
+            "Keyfile required"
+        );
+        assert_eq!(
+            open(password_only().with_keyfile(b"some other file"))
+                .unwrap_err()
+                .to_string(),
+            "Incorrect password or keyfile"
+        );
+
+        // Changing the password keeps the keyfile
+        let mut ledger = open(with_keyfile()).unwrap();
+
+        ledger
+            .change_password("master_password", "new_password".to_owned())
+            .unwrap();
+        drop(ledger);
+
+        let mut ledger =
+            open(CompositeKey::new("new_password".to_owned()).with_keyfile(&keyfile)).unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+
+        ledger.set_keyfile("new_password", None).unwrap();
+        drop(ledger);
+
+        let mut ledger = open(CompositeKey::new("new_password".to_owned())).unwrap();
+
+        assert!(!ledger.uses_keyfile().unwrap());
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
     }
 
-    #[test]
-    #[serial]
-    fn should_retrieve_all_doc_labels() {
-        let s_pw_1 = SavedPassword {
+    #[tokio::test]
+    async fn should_change_password() {
+        let s_pw = |pw: &str| SavedPassword {
             name: "www.example.com".to_owned(),
-            pw: "password1234".to_owned(),
+            pw: pw.to_owned(),
         };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let mut other_ledger = open_on_new_device(&store);
+
+        ledger.create(s_pw("pw_1"), "example").unwrap();
+        ledger.create(s_pw("pw_2"), "deleted").unwrap();
+        ledger.remove("deleted").unwrap();
+
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+        other_ledger.update("example", s_pw("pw_3")).unwrap();
+        ledger.update("example", s_pw("pw_4")).unwrap();
+
+        let dump = dump_values(&other_ledger.store, "Passwords");
+        ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        let err = ledger
+            .change_password("not the password", "new_password".to_owned())
+            .unwrap_err();
 
-        let s_pw_2 = SavedPassword {
-            name: "www.helloworld.com".to_owned(),
-            pw: "abc123".to_owned(),
+        assert_eq!(err.to_string(), "Incorrect password");
+
+        ledger
+            .change_password("master_password", "new_password".to_owned())
+            .unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_4"));
+        assert_eq!(
+            ledger.entry_history("example").unwrap()[0].data,
+            s_pw("pw_1")
+        );
+        assert_eq!(
+            ledger.get_conf("example").unwrap()[0].read_data().unwrap(),
+            &s_pw("pw_3")
+        );
+        assert!(store.list(&staging_label("Passwords")).unwrap().is_empty());
+        drop(ledger);
+
+        let err = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Incorrect password");
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "new_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_4"));
+        assert_eq!(
+            ledger.list_entries_with_conflicts().unwrap(),
+            vec!["example"]
+        );
+    }
+
+    #[tokio::test]
+    async fn should_replace_derived_vault_key_when_changing_password() {
+        let s_pw = |pw: &str| SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let open_ledger = |pw: &str, store: &DocumentStoreRef| {
+            LocalLedger::<SavedPassword>::new_with_store("Passwords", pw.to_owned(), store.clone())
         };
+        let mut ledger = open_ledger("master_password", &store).unwrap();
+
+        ledger.create(s_pw("pw_1"), "example").unwrap();
+        ledger.update("example", s_pw("pw_2")).unwrap();
+        downgrade_to_legacy_encryption(ledger, "master_password");
+
+        let mut ledger = open_ledger("master_password", &store).unwrap();
+        let pw_hash = ledger.meta_doc.read_data().unwrap().pw_hash.clone();
+        let mut other_ledger = open_on_new_device(&store);
+
+        // Both devices derived the same vault key, and have a conflict between them
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+        other_ledger.update("example", s_pw("pw_3")).unwrap();
+        ledger.update("example", s_pw("pw_4")).unwrap();
+
+        let dump = dump_values(&other_ledger.store, "Passwords");
+        ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        let public_key = ledger.public_key().unwrap();
+
+        ledger
+            .change_password("master_password", "new_password".to_owned())
+            .unwrap();
+
+        // The entry and its conflict, with two revisions each, and the entry index
+        assert_eq!(
+            count_payloads_legacy_keys_can_not_open(&store, "master_password", &pw_hash),
+            7
+        );
+        assert_ne!(ledger.public_key().unwrap(), public_key);
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_4"));
+        assert!(store.list(&staging_label("Passwords")).unwrap().is_empty());
+        drop(ledger);
+
+        let mut ledger = open_ledger("new_password", &store).unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_4"));
+        assert_eq!(
+            ledger.entry_history("example").unwrap()[0].data,
+            s_pw("pw_1")
+        );
+        assert_eq!(
+            ledger.get_conf("example").unwrap()[0].read_data().unwrap(),
+            &s_pw("pw_3")
+        );
+
+        // The other device takes the new vault key in, and has to be unlocked again
+        let dump = dump_values(&store, "Passwords");
+        let preview = other_ledger
+            .merge_preview(tokio_stream::iter(dump))
+            .await
+            .unwrap();
+        let dump = dump_values(&store, "Passwords");
+
+        assert_eq!(preview.meta_doc, MetaDocCompatibility::VaultKeyReplaced);
+        assert_eq!(
+            other_ledger
+                .merge(tokio_stream::iter(dump))
+                .await
+                .unwrap_err()
+                .to_string(),
+            "The vault key was replaced on another device. Unlock the ledger with the password set there."
+        );
+
+        let other_store = other_ledger.store.clone();
+
+        drop(other_ledger);
+
+        assert_eq!(
+            open_ledger("master_password", &other_store)
+                .unwrap_err()
+                .to_string(),
+            "Incorrect password"
+        );
+
+        let mut other_ledger = open_ledger("new_password", &other_store).unwrap();
+
+        // What it sealed with the derived vault key can still be read
+        assert_eq!(
+            other_ledger.read_by_entry_name("example").unwrap(),
+            &s_pw("pw_3")
+        );
+        assert_eq!(
+            other_ledger.entry_history("example").unwrap()[0].data,
+            s_pw("pw_1")
+        );
+
+        let dump = dump_values(&store, "Passwords");
+        other_ledger.merge(tokio_stream::iter(dump)).await.unwrap();
+
+        assert_eq!(
+            other_ledger.get_conf("example").unwrap()[0]
+                .read_data()
+                .unwrap(),
+            &s_pw("pw_4")
+        );
+        assert!(other_ledger.upgrade_encryption().unwrap() > 0);
+        assert_eq!(other_ledger.upgrade_encryption().unwrap(), 0);
+    }
 
-        let mut user_ledger =
-            LocalLedger::<SavedPassword>::new("Passwords", "master_password".to_owned()).unwrap();
+    /// Checks that the vault key `password` and `pw_hash`, from an old copy of the meta doc,
+    /// derive opens none of the payloads in `store`.  Returns the number of payloads.
+    fn count_payloads_legacy_keys_can_not_open(
+        store: &DocumentStoreRef,
+        password: &str,
+        pw_hash: &str,
+    ) -> usize {
+        let (_, legacy_vault_key) = WrappedVaultKey::from_legacy(password, pw_hash).unwrap();
+        let legacy_keys = VaultKeys::new(legacy_vault_key, CompositeKey::new(password.to_owned()));
+        let payloads = Cell::new(0);
+
+        for uuid in store.list("Passwords").unwrap() {
+            if uuid == META_DOC_UUID {
+                continue;
+            }
 
-        user_ledger
-            .create(s_pw_1.clone(), "my example.com password")
-            .expect("Failed to write to ledger.");
+            let mut doc = Document::<Value>::load_encrypted(store, "Passwords", &uuid).unwrap();
 
-        user_ledger
-            .create(s_pw_2.clone(), "my helloworld.com password")
-            .expect("Failed to write to ledger.");
+            doc.reencrypt(|payload| {
+                assert!(legacy_keys.open(payload).is_err());
+                payloads.set(payloads.get() + 1);
+                Ok(payload.clone())
+            })
+            .unwrap();
+        }
 
-        let received_data = user_ledger.list_entry_labels().unwrap();
+        payloads.get()
+    }
 
-        let mut found_entries = 0;
+    #[test]
+    fn should_replace_derived_vault_key_when_adding_keyfile() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let keyfile = crate::generate_keyfile();
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        downgrade_to_legacy_encryption(ledger, "master_password");
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_store(
+            "Passwords",
+            "master_password".to_owned(),
+            store.clone(),
+        )
+        .unwrap();
+        let pw_hash = ledger.meta_doc.read_data().unwrap().pw_hash.clone();
+
+        ledger
+            .set_keyfile("master_password", Some(&keyfile))
+            .unwrap();
+
+        // The password and an old copy of the meta doc, from before the keyfile, open nothing
+        assert_eq!(
+            count_payloads_legacy_keys_can_not_open(&store, "master_password", &pw_hash),
+            2
+        );
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+        drop(ledger);
+
+        let mut ledger = LocalLedger::<SavedPassword>::new_with_composite_key(
+            "Passwords",
+            CompositeKey::new("master_password".to_owned()).with_keyfile(&keyfile),
+            store.clone(),
+            LockMode::Shared,
+        )
+        .unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+    }
 
-        for label in received_data.into_iter() {
-            if label == "my example.com password" {
-                found_entries += 1;
-            }
+    fn age_encrypt(data: &[u8], password: &str) -> Vec<u8> {
+        let encryptor =
+            age::Encryptor::with_user_passphrase(age::secrecy::Secret::new(password.to_owned()));
+        let mut encrypted_data = vec![];
+        let mut writer = encryptor.wrap_output(&mut encrypted_data).unwrap();
 
-            if label == "my helloworld.com password" {
-                found_entries += 1;
+        std::io::Write::write_all(&mut writer, data).unwrap();
+        writer.finish().unwrap();
+
+        encrypted_data
+    }
+
+    /// Turns the ledger back into one from before ledgers had a vault key, with every payload
+    /// encrypted with the password by age and every entry stored under its name
+    fn downgrade_to_legacy_encryption(ledger: LocalLedger<SavedPassword>, password: &str) {
+        for uuid in ledger.store.list("Passwords").unwrap() {
+            if uuid == META_DOC_UUID || uuid == INDEX_DOC_UUID {
+                continue;
             }
+
+            let mut doc =
+                Document::<SavedPassword>::load_encrypted(&ledger.store, "Passwords", &uuid)
+                    .unwrap();
+            let (entry_uuid, suffix) = uuid.split_at(uuid.find('_').unwrap_or(uuid.len()));
+            let entry_name = ledger.entry_index.entry_name(entry_uuid).unwrap();
+
+            doc.reencrypt(|payload| Ok(age_encrypt(&ledger.keys.open(payload)?, password)))
+                .unwrap();
+            doc.append_uuid(&format!("{}{}", entry_name, suffix));
+            doc.set_name_scheme(0);
+            doc.store_as_is().unwrap();
+            ledger.store.remove("Passwords", &uuid).unwrap();
         }
 
-        assert_eq!(found_entries, 2);
+        ledger.store.remove("Passwords", INDEX_DOC_UUID).unwrap();
 
-        user_ledger.remove("my example.com password").unwrap();
+        let mut meta_doc = ledger.meta_doc.clone();
 
-        user_ledger.remove("my helloworld.com password").unwrap();
+        meta_doc.update(LocalLedgerMetaData {
+            pw_hash: bcrypt::hash(password).unwrap(),
+            ..Default::default()
+        });
+        meta_doc.store_as_is().unwrap();
     }
 
     #[test]
-    #[serial]
-    fn should_return_err_if_label_is_blank() {
-        let s_pw_1 = SavedPassword {
+    fn should_add_vault_key_to_legacy_ledgers() {
+        let s_pw = |pw: &str| SavedPassword {
             name: "www.example.com".to_owned(),
-            pw: "password1234".to_owned(),
+            pw: pw.to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let open_ledger = |pw: &str| {
+            LocalLedger::<SavedPassword>::new_with_store("Passwords", pw.to_owned(), store.clone())
         };
+        let mut ledger = open_ledger("master_password").unwrap();
 
-        let mut user_ledger =
-            LocalLedger::<SavedPassword>::new("Passwords", "master_password".to_owned()).unwrap();
+        ledger.create(s_pw("pw_1"), "example").unwrap();
+        ledger.update("example", s_pw("pw_2")).unwrap();
+        downgrade_to_legacy_encryption(ledger, "master_password");
 
-        let err = user_ledger.create(s_pw_1, "").unwrap_err();
+        let meta_doc_json = |store: &DocumentStoreRef| -> Value {
+            serde_json::from_slice(&store.read("Passwords", META_DOC_UUID).unwrap()).unwrap()
+        };
+        let legacy_meta_doc = meta_doc_json(&store);
+
+        assert_eq!(
+            open_ledger("not the password").unwrap_err().to_string(),
+            "Incorrect password"
+        );
+
+        let mut ledger = open_ledger("master_password").unwrap();
+        let meta_doc = meta_doc_json(&store);
+
+        // Adding the vault key is not an update other devices need to hear about
+        assert!(meta_doc["data"]["vault_key"].is_object());
+        assert_eq!(meta_doc["version"], legacy_meta_doc["version"]);
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_2"));
+        assert_eq!(ledger.upgrade_encryption().unwrap(), 1);
+        assert_eq!(ledger.upgrade_encryption().unwrap(), 0);
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw("pw_2"));
+        assert_eq!(
+            ledger.entry_history("example").unwrap()[0].data,
+            s_pw("pw_1")
+        );
+        drop(ledger);
+
+        assert_eq!(
+            open_ledger("not the password").unwrap_err().to_string(),
+            "Incorrect password"
+        );
+        assert!(open_ledger("master_password").is_ok());
+    }
 
-        assert_eq!(err, LocalLedgerError::new("Label cannot be empty"));
+    #[test]
+    fn should_recover_interrupted_password_change() {
+        let s_pw = SavedPassword {
+            name: "www.example.com".to_owned(),
+            pw: "password".to_owned(),
+        };
+        let store: DocumentStoreRef = std::sync::Arc::new(document::MemoryStore::new());
+        let open_ledger = |pw: &str| {
+            LocalLedger::<SavedPassword>::new_with_store("Passwords", pw.to_owned(), store.clone())
+        };
+        let mut ledger = open_ledger("master_password").unwrap();
+
+        ledger.create(s_pw.clone(), "example").unwrap();
+        // Entries only need to be rewritten while they can only be read with the old password
+        downgrade_to_legacy_encryption(ledger, "master_password");
+
+        let ledger = open_ledger("master_password").unwrap();
+        let new_keys = ledger
+            .keys
+            .with_composite_key(CompositeKey::new("new_password".to_owned()));
+        let new_wrapped_key =
+            WrappedVaultKey::rewrap(new_keys.composite_key(), new_keys.vault_key()).unwrap();
+
+        // Interrupted before every doc was staged
+        ledger
+            .stage_rekey(&new_keys, new_wrapped_key.clone())
+            .unwrap();
+        drop(ledger);
+
+        let ledger = open_ledger("master_password").unwrap();
+
+        assert!(store.list(&staging_label("Passwords")).unwrap().is_empty());
+
+        // Interrupted while moving the staged docs into place
+        ledger.stage_rekey(&new_keys, new_wrapped_key).unwrap();
+        drop(ledger);
+
+        let staging_label = staging_label("Passwords");
+        let meta_doc = store.read(&staging_label, META_DOC_UUID).unwrap();
+
+        store
+            .write(&staging_label, COMMIT_MARKER_UUID, b"{}")
+            .unwrap();
+        store.write("Passwords", META_DOC_UUID, &meta_doc).unwrap();
+        store.remove(&staging_label, META_DOC_UUID).unwrap();
+
+        let mut ledger = open_ledger("new_password").unwrap();
+
+        assert_eq!(ledger.read_by_entry_name("example").unwrap(), &s_pw);
+        assert!(store.list(&staging_label).unwrap().is_empty());
     }
 
     #[test]
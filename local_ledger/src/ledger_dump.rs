@@ -16,16 +16,23 @@ pub struct LedgerDump {
 impl LedgerDump {
     pub fn new(path: PathBuf) -> Result<Self, String> {
         let mut entries = vec![];
-        let path_is_dir = fs::metadata(&path).map_err(|e| e.to_string())?.is_dir();
+        // Make sure the path exists before walking it
+        let _ = fs::metadata(&path).map_err(|e| e.to_string())?;
 
         for entry in walkdir::WalkDir::new(path) {
-            entries.push(entry);
-        }
+            // Only docs are part of the dump.  Skip anything else that ends up in the ledger
+            // dir, like the temp files docs are written to before being renamed into place.
+            let is_doc = match &entry {
+                Ok(e) => {
+                    e.file_type().is_file()
+                        && e.path().extension().and_then(|ext| ext.to_str()) == Some("json")
+                }
+                Err(_) => true,
+            };
 
-        if path_is_dir {
-            // if path is a dir the walkdir places it as the first entry
-            // so we need to remove it since we want a list of files only
-            let _r = entries.remove(0);
+            if is_doc {
+                entries.push(entry);
+            }
         }
 
         Ok(Self {
@@ -1,5 +1,22 @@
+mod entry_index;
+mod entry_name;
+mod integrity;
 mod ledger;
 mod ledger_dump;
+mod memory_protection;
+mod merge_preview;
+mod migrate;
+mod rekey;
+mod search;
+mod sharing;
+mod vault_key;
 
-pub use ledger::LocalLedger;
+pub use document::{default_store, DocumentStoreRef, FileSystemStore, LockMode};
+pub use integrity::{IntegrityProblem, ProblemKind, RepairOutcome, RepairedProblem};
+pub use ledger::{LocalLedger, Resolution, TrashedEntry};
 pub use ledger_dump::*;
+pub use memory_protection::set_core_dumps;
+pub use merge_preview::{MergePreview, MetaDocCompatibility};
+pub use migrate::Migrate;
+pub use search::{SearchHit, Searchable};
+pub use vault_key::{generate_keyfile, CompositeKey};
@@ -0,0 +1,65 @@
+use utility::LocalLedgerError;
+
+/// Keeps the pages holding `secret` in RAM, so it never ends up in swap.  Only done on Linux with
+/// the `memory-protection` feature.  Failures are logged and otherwise ignored, as unprivileged
+/// processes may only lock a little memory.  The pages are never unlocked again, other secrets
+/// may share them.
+pub(crate) fn lock_memory(secret: &[u8]) {
+    #[cfg(all(feature = "memory-protection", target_os = "linux"))]
+    {
+        // SAFETY: the range is that of a live slice, mlock does not touch its contents
+        let result = unsafe { libc::mlock(secret.as_ptr().cast(), secret.len()) };
+
+        if result != 0 {
+            tracing::warn!(
+                "Failed to lock key material in memory: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    #[cfg(not(all(feature = "memory-protection", target_os = "linux")))]
+    let _ = secret;
+}
+
+/// Turns core dumps of this process off, or back on.  A core dump taken while a ledger is open
+/// would hold its keys and decrypted entries.  Only does something on Linux with the
+/// `memory-protection` feature.
+pub fn set_core_dumps(enabled: bool) -> Result<(), LocalLedgerError> {
+    #[cfg(all(feature = "memory-protection", target_os = "linux"))]
+    {
+        // SAFETY: PR_SET_DUMPABLE only reads its integer argument
+        let result =
+            unsafe { libc::prctl(libc::PR_SET_DUMPABLE, libc::c_ulong::from(enabled), 0, 0, 0) };
+
+        if result != 0 {
+            return Err(LocalLedgerError::new(&format!(
+                "Failed to change core dump setting: {}",
+                std::io::Error::last_os_error()
+            )));
+        }
+    }
+
+    #[cfg(not(all(feature = "memory-protection", target_os = "linux")))]
+    let _ = enabled;
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "memory-protection", target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_turn_core_dumps_off_and_on() {
+        let is_dumpable = || unsafe { libc::prctl(libc::PR_GET_DUMPABLE, 0, 0, 0, 0) };
+
+        set_core_dumps(false).unwrap();
+
+        assert_eq!(is_dumpable(), 0);
+
+        set_core_dumps(true).unwrap();
+
+        assert_eq!(is_dumpable(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+/// How the meta doc that came with a merge relates to the one of the ledger
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetaDocCompatibility {
+    /// No meta doc came with the merge
+    #[default]
+    Missing,
+    /// The meta doc is the same as, or older than, the one of the ledger
+    Unchanged,
+    /// The meta doc was stored again on the other device since the last sync, as older versions
+    /// did when they made the identity of the vault.  It would replace the one of the ledger,
+    /// keeping the vault key as it is wrapped on this device.
+    Updated,
+    /// The meta doc was changed here and on the other device.  The merge would fail.
+    Conflict,
+    /// The vault key was replaced on the other device when its password was changed.  The merge
+    /// would stop once it took the new vault key in, and the ledger would have to be unlocked
+    /// with the password set there.
+    VaultKeyReplaced,
+}
+
+/// What merging a stream of docs would do to the ledger, see `LocalLedger::merge_preview`.
+/// Entries are listed by name, sorted.  Entries whose names have not been synced yet are listed
+/// by their uuid.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergePreview {
+    /// Entries the ledger does not have yet
+    pub new_entries: Vec<String>,
+    /// Entries that would be replaced by a newer copy, deletions and moves to the trash included
+    pub updated_entries: Vec<String>,
+    /// Entries the ledger already has the same or a newer copy of
+    pub unchanged_entries: Vec<String>,
+    /// Entries that were changed here and on the other device.  They would get a conflicting
+    /// copy that has to be resolved.
+    pub conflicting_entries: Vec<String>,
+    pub meta_doc: MetaDocCompatibility,
+}
+
+/// What merging a doc would do to the entry it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryChange {
+    New,
+    Updated,
+    Unchanged,
+    Conflicting,
+}
+
+impl MergePreview {
+    /// Files `entry_name` under `change`
+    pub(crate) fn add(&mut self, entry_name: String, change: EntryChange) {
+        let entries = match change {
+            EntryChange::New => &mut self.new_entries,
+            EntryChange::Updated => &mut self.updated_entries,
+            EntryChange::Unchanged => &mut self.unchanged_entries,
+            EntryChange::Conflicting => &mut self.conflicting_entries,
+        };
+
+        entries.push(entry_name);
+    }
+
+    /// Sorts the entries and drops duplicates, an entry can have several conflicting copies
+    pub(crate) fn finish(mut self) -> Self {
+        for entries in [
+            &mut self.new_entries,
+            &mut self.updated_entries,
+            &mut self.unchanged_entries,
+            &mut self.conflicting_entries,
+        ] {
+            entries.sort();
+            entries.dedup();
+        }
+
+        self
+    }
+}
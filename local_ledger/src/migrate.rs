@@ -0,0 +1,98 @@
+use serde_json::Value;
+use utility::LocalLedgerError;
+use zeroize::Zeroizing;
+
+/// Upgrades ledger entries that were written with an older schema of the entry type.
+///
+/// Every entry is stored with the `SCHEMA_VERSION` its type had when the entry was written.  When
+/// an entry with an older version is decrypted, `migrate` is called once per version step.  An
+/// entry at version 1 of a type at version 3 goes through `migrate(1, ..)` and then
+/// `migrate(2, ..)` before it is parsed.
+pub trait Migrate {
+    /// Current schema version of the type.  Bump it whenever the shape of the type changes in a
+    /// way serde defaults can not cover.  Entries written before schema versions were tracked
+    /// are at version 0.
+    const SCHEMA_VERSION: u32 = 0;
+
+    /// Upgrades `payload` from schema version `from_version` to `from_version + 1`
+    fn migrate(from_version: u32, payload: Value) -> Result<Value, LocalLedgerError> {
+        let _ = from_version;
+
+        Ok(payload)
+    }
+}
+
+/// Brings a decrypted payload written with schema `from_version` up to the current schema of `T`
+pub(crate) fn migrate_payload<T: Migrate>(
+    from_version: u32,
+    payload: Vec<u8>,
+) -> Result<Vec<u8>, LocalLedgerError> {
+    if from_version >= T::SCHEMA_VERSION {
+        return Ok(payload);
+    }
+
+    // The payload is plaintext, it is wiped once it has been parsed
+    let payload = Zeroizing::new(payload);
+    let mut payload_json: Value = serde_json::from_slice(&payload).map_err(|err| {
+        LocalLedgerError::new(&format!("Failed to parse payload for migration: {}", err))
+    })?;
+
+    for version in from_version..T::SCHEMA_VERSION {
+        payload_json = T::migrate(version, payload_json).map_err(|err| {
+            LocalLedgerError::new(&format!(
+                "Failed to migrate payload from schema version {}: {}",
+                version, err.message
+            ))
+        })?;
+    }
+
+    serde_json::to_vec(&payload_json).map_err(|err| {
+        LocalLedgerError::new(&format!("Failed to serialize migrated payload: {}", err))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Renamed;
+
+    impl Migrate for Renamed {
+        const SCHEMA_VERSION: u32 = 2;
+
+        fn migrate(from_version: u32, mut payload: Value) -> Result<Value, LocalLedgerError> {
+            match from_version {
+                0 => {
+                    payload["password"] = payload["pw"].take();
+                    Ok(payload)
+                }
+                1 => {
+                    payload["notes"] = json!("");
+                    Ok(payload)
+                }
+                _ => Err(LocalLedgerError::new("Unknown schema version")),
+            }
+        }
+    }
+
+    #[test]
+    fn should_migrate_payload_one_version_at_a_time() {
+        let payload = serde_json::to_vec(&json!({ "pw": "hunter2" })).unwrap();
+        let migrated: Value =
+            serde_json::from_slice(&migrate_payload::<Renamed>(0, payload).unwrap()).unwrap();
+
+        assert_eq!(migrated["password"], "hunter2");
+        assert_eq!(migrated["notes"], "");
+    }
+
+    #[test]
+    fn should_leave_current_payloads_untouched() {
+        let payload = b"not even json".to_vec();
+
+        assert_eq!(
+            migrate_payload::<Renamed>(2, payload.clone()).unwrap(),
+            payload
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use document::DocumentStoreRef;
+use utility::LocalLedgerError;
+
+/// Written to the staging label once every re-encrypted doc has been staged.  A staged re-key
+/// with the marker gets finished, one without it gets thrown away.
+pub(crate) const COMMIT_MARKER_UUID: &str = "REKEY_COMMIT";
+
+/// Label the re-encrypted docs of the ledger `label` are staged under until they are moved into
+/// place
+pub(crate) fn staging_label(label: &str) -> String {
+    format!("{}.rekey", label)
+}
+
+/// Stages the re-encrypted contents of the doc `uuid`
+pub(crate) fn stage_doc(
+    store: &DocumentStoreRef,
+    label: &str,
+    uuid: &str,
+    contents: &[u8],
+) -> Result<(), LocalLedgerError> {
+    store.write(&staging_label(label), uuid, contents)
+}
+
+/// Throws away everything that has been staged for `label`
+pub(crate) fn discard_staged_docs(
+    store: &DocumentStoreRef,
+    label: &str,
+) -> Result<(), LocalLedgerError> {
+    let staging_label = staging_label(label);
+
+    for uuid in store.list(&staging_label)? {
+        store.remove(&staging_label, &uuid)?;
+    }
+
+    Ok(())
+}
+
+/// Marks the staged docs of `label` as complete, then moves them into place
+pub(crate) fn commit_staged_docs(
+    store: &DocumentStoreRef,
+    label: &str,
+) -> Result<(), LocalLedgerError> {
+    store.write(&staging_label(label), COMMIT_MARKER_UUID, b"{}")?;
+
+    move_staged_docs(store, label)
+}
+
+/// Finishes a re-key of `label` that was interrupted after it was committed, or rolls back one
+/// that was interrupted before
+pub(crate) fn recover_rekey(store: &DocumentStoreRef, label: &str) -> Result<(), LocalLedgerError> {
+    let staging_label = staging_label(label);
+
+    store.recover(&staging_label)?;
+
+    let staged_uuids = store.list(&staging_label)?;
+
+    if staged_uuids.is_empty() {
+        return Ok(());
+    }
+
+    if staged_uuids.iter().any(|uuid| uuid == COMMIT_MARKER_UUID) {
+        tracing::warn!("Finishing interrupted password change of {}", label);
+        move_staged_docs(store, label)?;
+    } else {
+        tracing::warn!("Rolling back interrupted password change of {}", label);
+        discard_staged_docs(store, label)?;
+    }
+
+    Ok(())
+}
+
+fn move_staged_docs(store: &DocumentStoreRef, label: &str) -> Result<(), LocalLedgerError> {
+    let staging_label = staging_label(label);
+
+    for uuid in store.list(&staging_label)? {
+        if uuid == COMMIT_MARKER_UUID {
+            continue;
+        }
+
+        let contents = store.read(&staging_label, &uuid)?;
+
+        store.write(label, &uuid, &contents)?;
+        store.remove(&staging_label, &uuid)?;
+    }
+
+    // Removed last, so a move that gets interrupted is picked up again on the next open
+    store.remove(&staging_label, COMMIT_MARKER_UUID)
+}
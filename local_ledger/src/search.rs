@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+
+/// Lets `LocalLedger::search` look into ledger entries.
+///
+/// Entries are always searched by name.  `search_terms` adds whatever else should be found, like
+/// usernames, URLs, notes or tags.  Secrets such as passwords do not belong here.
+pub trait Searchable {
+    /// Text of the entry that search matches against, besides its name
+    fn search_terms(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// An entry that matched a search
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SearchHit {
+    pub entry_name: String,
+    /// How well the entry matched, higher is better
+    pub score: u32,
+}
+
+// Scores of the ways a query can match.  Matches on the entry name rank above matches on its
+// other terms.
+const EXACT_SCORE: u32 = 100;
+const PREFIX_SCORE: u32 = 80;
+const SUBSTRING_SCORE: u32 = 60;
+const FUZZY_SCORE: u32 = 30;
+const NAME_BONUS: u32 = 10;
+
+/// Lowercased search terms of every entry, kept in memory while the ledger is open
+#[derive(Debug, Default)]
+pub(crate) struct SearchIndex {
+    entries: BTreeMap<String, Vec<String>>,
+}
+
+impl SearchIndex {
+    /// Adds `entry_name`, replacing whatever terms it had before
+    pub(crate) fn insert(&mut self, entry_name: &str, search_terms: Vec<String>) {
+        let terms = search_terms
+            .iter()
+            .map(|term| term.to_lowercase())
+            .filter(|term| !term.is_empty())
+            .collect();
+
+        self.entries.insert(entry_name.to_owned(), terms);
+    }
+
+    pub(crate) fn remove(&mut self, entry_name: &str) {
+        self.entries.remove(entry_name);
+    }
+
+    /// Finds the entries matching `query`, case insensitively.  Terms containing the query rank
+    /// above terms that only contain its characters in order.  Best matches come first.
+    pub(crate) fn search(&self, query: &str) -> Vec<SearchHit> {
+        let query = query.trim().to_lowercase();
+
+        if query.is_empty() {
+            return vec![];
+        }
+
+        let mut hits: Vec<SearchHit> = self
+            .entries
+            .iter()
+            .filter_map(|(entry_name, terms)| {
+                let name_score =
+                    match_score(&entry_name.to_lowercase(), &query).map(|score| score + NAME_BONUS);
+                let terms_score = terms
+                    .iter()
+                    .filter_map(|term| match_score(term, &query))
+                    .max();
+                let score = name_score.max(terms_score)?;
+
+                Some(SearchHit {
+                    entry_name: entry_name.clone(),
+                    score,
+                })
+            })
+            .collect();
+
+        // Ties keep the order of the names
+        hits.sort_by_key(|hit| std::cmp::Reverse(hit.score));
+
+        hits
+    }
+}
+
+/// Scores how well `query` matches `term`.  None if it does not match at all.
+fn match_score(term: &str, query: &str) -> Option<u32> {
+    if term == query {
+        return Some(EXACT_SCORE);
+    }
+
+    if term.starts_with(query) {
+        return Some(PREFIX_SCORE);
+    }
+
+    if term.contains(query) {
+        return Some(SUBSTRING_SCORE);
+    }
+
+    fuzzy_gaps(term, query).map(|gaps| FUZZY_SCORE.saturating_sub(gaps).max(1))
+}
+
+/// Matches the characters of `query` against `term` in order.  Returns the number of characters
+/// skipped between the first and the last matched one, or None if `query` is not a subsequence
+/// of `term`.
+fn fuzzy_gaps(term: &str, query: &str) -> Option<u32> {
+    let mut query_chars = query.chars().peekable();
+    let mut gaps = 0;
+    let mut started = false;
+
+    for term_char in term.chars() {
+        match query_chars.peek() {
+            None => break,
+            Some(&query_char) if query_char == term_char => {
+                started = true;
+                query_chars.next();
+            }
+            Some(_) if started => gaps += 1,
+            Some(_) => {}
+        }
+    }
+
+    query_chars.peek().is_none().then_some(gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> SearchIndex {
+        let mut index = SearchIndex::default();
+
+        index.insert(
+            "GitHub",
+            vec!["octocat".to_owned(), "https://github.com".to_owned()],
+        );
+        index.insert("Bank", vec!["Savings account".to_owned()]);
+        index.insert("Git Bash", vec![]);
+
+        index
+    }
+
+    fn entry_names(hits: Vec<SearchHit>) -> Vec<String> {
+        hits.into_iter().map(|hit| hit.entry_name).collect()
+    }
+
+    #[test]
+    fn should_match_substrings_case_insensitively() {
+        let index = index();
+
+        assert_eq!(entry_names(index.search("github")), vec!["GitHub"]);
+        assert_eq!(entry_names(index.search("OCTO")), vec!["GitHub"]);
+        assert_eq!(entry_names(index.search("  savings ")), vec!["Bank"]);
+        assert_eq!(entry_names(index.search("git")), vec!["Git Bash", "GitHub"]);
+        assert!(index.search("").is_empty());
+        assert!(index.search("nothing like it").is_empty());
+    }
+
+    #[test]
+    fn should_rank_fuzzy_matches_below_substrings() {
+        let mut index = index();
+
+        assert_eq!(entry_names(index.search("gthb")), vec!["GitHub"]);
+        assert_eq!(entry_names(index.search("bsh")), vec!["Git Bash"]);
+        // A prefix beats a substring
+        assert_eq!(entry_names(index.search("ba")), vec!["Bank", "Git Bash"]);
+
+        let hits = index.search("gitbash");
+
+        assert_eq!(entry_names(hits.clone()), vec!["Git Bash"]);
+        assert!(hits[0].score < SUBSTRING_SCORE);
+
+        index.remove("Git Bash");
+
+        assert!(index.search("gitbash").is_empty());
+    }
+}
@@ -0,0 +1,138 @@
+use age::{
+    secrecy::{ExposeSecret, Secret},
+    x25519,
+};
+use bech32::{ToBase32, Variant};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    io::{Read, Write},
+    str::FromStr,
+};
+use utility::LocalLedgerError;
+use zeroize::Zeroizing;
+
+/// Human readable part of the bech32 encoding of age identities
+const SECRET_KEY_PREFIX: &str = "age-secret-key-";
+
+/// An entry as it is handed to other users.  The payload is kept as plain JSON along with its
+/// schema version, so a recipient running a newer version can migrate it.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SharedEntry {
+    pub entry_name: String,
+    pub schema_version: u32,
+    pub data: Value,
+}
+
+/// Returns the age X25519 identity with the secret key `key`, in its `AGE-SECRET-KEY-1...`
+/// encoding
+pub(crate) fn identity_from_key(
+    key: &Secret<[u8; 32]>,
+) -> Result<Secret<String>, LocalLedgerError> {
+    let encoded = Zeroizing::new(
+        bech32::encode(
+            SECRET_KEY_PREFIX,
+            key.expose_secret().to_base32(),
+            Variant::Bech32,
+        )
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to encode identity: {}", err)))?,
+    );
+
+    Ok(Secret::new(encoded.to_uppercase()))
+}
+
+/// Returns the public key, `age1...`, that goes with `identity`
+pub(crate) fn public_key_of(identity: &Secret<String>) -> Result<String, LocalLedgerError> {
+    Ok(parse_identity(identity)?.to_public().to_string())
+}
+
+/// Encrypts `data` so that any of the holders of `public_keys` can decrypt it
+pub(crate) fn encrypt_to(data: &[u8], public_keys: &[String]) -> Result<Vec<u8>, LocalLedgerError> {
+    let recipients = public_keys
+        .iter()
+        .map(|public_key| {
+            x25519::Recipient::from_str(public_key.trim())
+                .map(|recipient| Box::new(recipient) as Box<dyn age::Recipient + Send>)
+                .map_err(|err| {
+                    LocalLedgerError::new(&format!("Invalid public key {}: {}", public_key, err))
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let encryptor = age::Encryptor::with_recipients(recipients)
+        .ok_or(LocalLedgerError::new("No recipients to share with"))?;
+    let mut encrypted_data = vec![];
+    let mut writer = encryptor
+        .wrap_output(&mut encrypted_data)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to encrypt data: {}", err)))?;
+
+    writer
+        .write_all(data)
+        .and_then(|_| writer.finish())
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to encrypt data: {}", err)))?;
+
+    Ok(encrypted_data)
+}
+
+/// Decrypts data that was encrypted to the public key of `identity`
+pub(crate) fn decrypt_with(
+    encrypted_data: &[u8],
+    identity: &Secret<String>,
+) -> Result<Vec<u8>, LocalLedgerError> {
+    let identity = parse_identity(identity)?;
+    let decryptor = match age::Decryptor::new(encrypted_data)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)))?
+    {
+        age::Decryptor::Recipients(d) => Ok(d),
+        _ => Err(LocalLedgerError::new(
+            "Failed to decrypt. Received encrypted data that was not secured with a public key.",
+        )),
+    }?;
+
+    let mut decrypted = vec![];
+    let mut reader = decryptor
+        .decrypt(std::iter::once(&identity as &dyn age::Identity))
+        .map_err(|err| match err {
+            age::DecryptError::NoMatchingKeys => {
+                LocalLedgerError::new("This was not shared with this vault")
+            }
+            err => LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)),
+        })?;
+
+    reader
+        .read_to_end(&mut decrypted)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)))?;
+
+    Ok(decrypted)
+}
+
+fn parse_identity(identity: &Secret<String>) -> Result<x25519::Identity, LocalLedgerError> {
+    x25519::Identity::from_str(identity.expose_secret())
+        .map_err(|err| LocalLedgerError::new(&format!("Invalid identity: {}", err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_identity() -> Secret<String> {
+        x25519::Identity::generate().to_string()
+    }
+
+    #[test]
+    fn should_only_decrypt_with_identities_of_recipients() {
+        let alice = generate_identity();
+        let bob = generate_identity();
+        let eve = generate_identity();
+        let public_keys = vec![public_key_of(&alice).unwrap(), public_key_of(&bob).unwrap()];
+        let encrypted_data = encrypt_to(b"secret", &public_keys).unwrap();
+
+        assert_eq!(decrypt_with(&encrypted_data, &alice).unwrap(), b"secret");
+        assert_eq!(decrypt_with(&encrypted_data, &bob).unwrap(), b"secret");
+        assert_eq!(
+            decrypt_with(&encrypted_data, &eve).unwrap_err().message,
+            "This was not shared with this vault"
+        );
+        assert!(encrypt_to(b"secret", &["not a key".to_owned()]).is_err());
+        assert!(encrypt_to(b"secret", &[]).is_err());
+    }
+}
@@ -0,0 +1,674 @@
+use age::secrecy::{ExposeSecret, Secret};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use hkdf::{
+    hmac::{Hmac, Mac},
+    Hkdf,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use utility::LocalLedgerError;
+use zeroize::Zeroizing;
+
+use crate::memory_protection::lock_memory;
+
+/// Marks payloads sealed with a key derived from the vault key.  Anything else is a payload that
+/// was encrypted with the master password by age, before ledgers had a vault key.
+const SEALED_MAGIC: &[u8] = b"FZG1";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+const DOC_KEY_INFO: &[u8] = b"fizzgig document key";
+const LEGACY_VAULT_KEY_INFO: &[u8] = b"fizzgig legacy vault key";
+const ENTRY_ID_INFO: &[u8] = b"fizzgig entry id";
+const KEYFILE_KEY_INFO: &[u8] = b"fizzgig keyfile key";
+const IDENTITY_KEY_INFO: &[u8] = b"fizzgig identity key";
+/// Random bytes in a keyfile made by `generate_keyfile`, before they are hex encoded
+const KEYFILE_LEN: usize = 32;
+/// Length of entry ids, in bytes, before they are hex encoded
+const ENTRY_ID_LEN: usize = 16;
+
+/// What unlocks a ledger: the master password, and for ledgers that require one, the contents of
+/// a keyfile.  Both go into the key that wraps the vault key, so a ledger that requires a keyfile
+/// can not be opened with the password alone.  Copies of the ledger from before the keyfile was
+/// added still can, see `LocalLedger::set_keyfile`.
+#[derive(Debug)]
+pub struct CompositeKey {
+    password: Secret<String>,
+    /// SHA-256 of the keyfile contents.  Any file will do as a keyfile, as long as it does not
+    /// change.
+    keyfile_digest: Option<Secret<[u8; KEY_LEN]>>,
+}
+
+impl CompositeKey {
+    /// A key made from the master password only
+    pub fn new(password: String) -> Self {
+        Self {
+            password: Secret::new(password),
+            keyfile_digest: None,
+        }
+    }
+
+    /// Adds the contents of a keyfile to the key
+    pub fn with_keyfile(mut self, keyfile_contents: &[u8]) -> Self {
+        self.keyfile_digest = Some(Secret::new(Sha256::digest(keyfile_contents).into()));
+        self
+    }
+
+    pub fn password(&self) -> &str {
+        self.password.expose_secret()
+    }
+
+    pub fn has_keyfile(&self) -> bool {
+        self.keyfile_digest.is_some()
+    }
+
+    /// The same keyfile with another password
+    pub(crate) fn with_password(&self, password: String) -> Self {
+        Self {
+            password: Secret::new(password),
+            keyfile_digest: self.copy_keyfile_digest(),
+        }
+    }
+
+    /// The same password with another keyfile, or none
+    pub(crate) fn with_keyfile_contents(&self, keyfile_contents: Option<&[u8]>) -> Self {
+        let key = Self::new(self.password().to_owned());
+
+        match keyfile_contents {
+            Some(keyfile_contents) => key.with_keyfile(keyfile_contents),
+            None => key,
+        }
+    }
+
+    fn copy_keyfile_digest(&self) -> Option<Secret<[u8; KEY_LEN]>> {
+        self.keyfile_digest
+            .as_ref()
+            .map(|digest| Secret::new(*digest.expose_secret()))
+    }
+}
+
+/// Makes the contents of a new keyfile: random bytes, hex encoded so the keyfile can be printed
+/// or copied by hand
+pub fn generate_keyfile() -> Vec<u8> {
+    let mut keyfile = [0u8; KEYFILE_LEN];
+
+    OsRng.fill_bytes(&mut keyfile);
+
+    let mut contents: String = keyfile.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    contents.push('\n');
+    contents.into_bytes()
+}
+
+/// Argon2id parameters the master password is stretched with.  Stored along with the wrapped
+/// vault key, so they can be raised for new ledgers without locking out existing ones.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct KdfParams {
+    /// Memory cost in KiB
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+}
+
+impl KdfParams {
+    fn with_salt(salt: &[u8]) -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            salt: STANDARD.encode(salt),
+        }
+    }
+
+    fn generate() -> Self {
+        let mut salt = [0u8; SALT_LEN];
+
+        OsRng.fill_bytes(&mut salt);
+
+        Self::with_salt(&salt)
+    }
+
+    /// Derives the key that wraps the vault key from the master password and the keyfile, if
+    /// there is one
+    fn derive_key(&self, key: &CompositeKey) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+        let password_key = self.derive_password_key(key.password())?;
+
+        match &key.keyfile_digest {
+            None => Ok(password_key),
+            Some(keyfile_digest) => {
+                let mut composite_key = [0u8; KEY_LEN];
+
+                Hkdf::<Sha256>::new(
+                    Some(keyfile_digest.expose_secret()),
+                    password_key.expose_secret(),
+                )
+                .expand(KEYFILE_KEY_INFO, &mut composite_key)
+                .map_err(|err| LocalLedgerError::new(&format!("Failed to derive key: {}", err)))?;
+
+                Ok(Secret::new(composite_key))
+            }
+        }
+    }
+
+    /// Stretches the master password
+    fn derive_password_key(
+        &self,
+        password: &str,
+    ) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+        let salt = STANDARD
+            .decode(&self.salt)
+            .map_err(|err| LocalLedgerError::new(&format!("Invalid KDF salt: {}", err)))?;
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_LEN))
+            .map_err(|err| LocalLedgerError::new(&format!("Invalid KDF params: {}", err)))?;
+        let mut key = [0u8; KEY_LEN];
+
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password.as_bytes(), &salt, &mut key)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to derive key: {}", err)))?;
+
+        Ok(Secret::new(key))
+    }
+}
+
+/// The vault key, sealed with a key derived from the master password, and the keyfile if the
+/// ledger requires one.  Lives in the meta doc.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct WrappedVaultKey {
+    kdf: KdfParams,
+    sealed_key: String,
+    /// Whether the vault key was wrapped with a keyfile.  Only there to tell users which of the
+    /// two is missing, the keyfile is needed either way.
+    #[serde(default)]
+    keyfile: bool,
+}
+
+impl WrappedVaultKey {
+    /// Creates a random vault key for a new ledger
+    pub(crate) fn generate(
+        key: &CompositeKey,
+    ) -> Result<(Self, Secret<[u8; KEY_LEN]>), LocalLedgerError> {
+        let mut vault_key = [0u8; KEY_LEN];
+
+        OsRng.fill_bytes(&mut vault_key);
+
+        let vault_key = Secret::new(vault_key);
+        let wrapped_key = Self::wrap(key, &vault_key, KdfParams::generate())?;
+
+        Ok((wrapped_key, vault_key))
+    }
+
+    /// Creates the vault key of a ledger that was made before ledgers had one.  The key is
+    /// derived from the password and the ledger's password hash, which are the same on every
+    /// device, so devices that upgrade independently end up with the same key.  Those ledgers
+    /// never had a keyfile.
+    pub(crate) fn from_legacy(
+        password: &str,
+        pw_hash: &str,
+    ) -> Result<(Self, Secret<[u8; KEY_LEN]>), LocalLedgerError> {
+        let salt = Sha256::digest(pw_hash.as_bytes());
+        let kdf = KdfParams::with_salt(&salt[..SALT_LEN]);
+        let key = CompositeKey::new(password.to_owned());
+        let master_key = kdf.derive_key(&key)?;
+        let mut vault_key = [0u8; KEY_LEN];
+
+        Hkdf::<Sha256>::new(None, master_key.expose_secret())
+            .expand(LEGACY_VAULT_KEY_INFO, &mut vault_key)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to derive key: {}", err)))?;
+
+        let vault_key = Secret::new(vault_key);
+        let wrapped_key = Self::wrap(&key, &vault_key, kdf)?;
+
+        Ok((wrapped_key, vault_key))
+    }
+
+    /// Seals `vault_key` with the key `kdf` derives from `key`
+    fn wrap(
+        key: &CompositeKey,
+        vault_key: &Secret<[u8; KEY_LEN]>,
+        kdf: KdfParams,
+    ) -> Result<Self, LocalLedgerError> {
+        let master_key = kdf.derive_key(key)?;
+        let sealed_key = seal_with(master_key.expose_secret(), vault_key.expose_secret())?;
+
+        Ok(Self {
+            kdf,
+            sealed_key: STANDARD.encode(sealed_key),
+            keyfile: key.has_keyfile(),
+        })
+    }
+
+    /// Seals the same vault key with a new password or keyfile
+    pub(crate) fn rewrap(
+        key: &CompositeKey,
+        vault_key: &Secret<[u8; KEY_LEN]>,
+    ) -> Result<Self, LocalLedgerError> {
+        Self::wrap(key, vault_key, KdfParams::generate())
+    }
+
+    pub(crate) fn requires_keyfile(&self) -> bool {
+        self.keyfile
+    }
+
+    /// Opens the vault key.  Fails with "Incorrect password" if `key` is not the one the vault
+    /// key was wrapped with.
+    pub(crate) fn unwrap(
+        &self,
+        key: &CompositeKey,
+    ) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+        match (self.keyfile, key.has_keyfile()) {
+            (true, false) => return Err(LocalLedgerError::new("Keyfile required")),
+            (false, true) => return Err(LocalLedgerError::new("Ledger does not use a keyfile")),
+            _ => {}
+        }
+
+        let master_key = self.kdf.derive_key(key)?;
+        let sealed_key = STANDARD
+            .decode(&self.sealed_key)
+            .map_err(|err| LocalLedgerError::new(&format!("Invalid vault key: {}", err)))?;
+        let vault_key = open_with(master_key.expose_secret(), &sealed_key).map_err(|_| {
+            LocalLedgerError::new(if self.keyfile {
+                "Incorrect password or keyfile"
+            } else {
+                "Incorrect password"
+            })
+        })?;
+        let vault_key = Zeroizing::new(vault_key);
+
+        <[u8; KEY_LEN]>::try_from(vault_key.as_slice())
+            .map(Secret::new)
+            .map_err(|_| LocalLedgerError::new("Invalid vault key"))
+    }
+}
+
+/// Everything needed to read and write the payloads of a ledger
+#[derive(Debug)]
+pub(crate) struct VaultKeys {
+    // Boxed so it stays put in memory that is locked, see `lock_memory`
+    vault_key: Box<Secret<[u8; KEY_LEN]>>,
+    // The vault key that was derived from the password, once it has been replaced by a random
+    // one.  Entry ids stay keyed with it, and payloads that other devices sealed with it before
+    // they picked up the new key can still be read.
+    legacy_vault_key: Option<Box<Secret<[u8; KEY_LEN]>>>,
+    // Payloads written before the ledger had a vault key can only be read with the password.
+    // The keyfile is kept along with it so the vault key can be wrapped again.
+    composite_key: CompositeKey,
+}
+
+impl VaultKeys {
+    pub(crate) fn new(vault_key: Secret<[u8; KEY_LEN]>, composite_key: CompositeKey) -> Self {
+        let vault_key = Box::new(vault_key);
+
+        lock_memory(vault_key.expose_secret());
+
+        Self {
+            vault_key,
+            legacy_vault_key: None,
+            composite_key,
+        }
+    }
+
+    /// The same keys, unlocked with `composite_key`
+    pub(crate) fn with_composite_key(&self, composite_key: CompositeKey) -> Self {
+        let mut keys = Self::new(copy_key(&self.vault_key), composite_key);
+
+        if let Some(legacy_vault_key) = &self.legacy_vault_key {
+            keys.set_legacy_vault_key(copy_key(legacy_vault_key));
+        }
+
+        keys
+    }
+
+    pub(crate) fn vault_key(&self) -> &Secret<[u8; KEY_LEN]> {
+        &self.vault_key
+    }
+
+    /// Keys with a new, random vault key, wrapped with `composite_key`.  Meant to replace a vault
+    /// key that was derived from the password, which is kept, see `set_legacy_vault_key`.
+    pub(crate) fn with_random_vault_key(
+        &self,
+        composite_key: CompositeKey,
+    ) -> Result<(WrappedVaultKey, Self), LocalLedgerError> {
+        let (wrapped_key, vault_key) = WrappedVaultKey::generate(&composite_key)?;
+        let mut keys = Self::new(vault_key, composite_key);
+
+        keys.set_legacy_vault_key(copy_key(&self.vault_key));
+
+        Ok((wrapped_key, keys))
+    }
+
+    /// Keeps the derived vault key `legacy_vault_key` around after it was replaced
+    pub(crate) fn set_legacy_vault_key(&mut self, legacy_vault_key: Secret<[u8; KEY_LEN]>) {
+        let legacy_vault_key = Box::new(legacy_vault_key);
+
+        lock_memory(legacy_vault_key.expose_secret());
+        self.legacy_vault_key = Some(legacy_vault_key);
+    }
+
+    pub(crate) fn composite_key(&self) -> &CompositeKey {
+        &self.composite_key
+    }
+
+    /// Encrypts `data` with a key of its own, derived from the vault key
+    pub(crate) fn seal(&self, data: &[u8]) -> Result<Vec<u8>, LocalLedgerError> {
+        let mut salt = [0u8; SALT_LEN];
+
+        OsRng.fill_bytes(&mut salt);
+
+        let doc_key = derive_doc_key(&self.vault_key, &salt)?;
+        let mut payload = [SEALED_MAGIC, &salt].concat();
+
+        payload.extend(seal_with(doc_key.expose_secret(), data)?);
+
+        Ok(payload)
+    }
+
+    /// Decrypts a payload written by `seal`, or by age before the ledger had a vault key
+    pub(crate) fn open(&self, payload: &[u8]) -> Result<Vec<u8>, LocalLedgerError> {
+        if !is_sealed(payload) {
+            return open_legacy(payload, self.composite_key.password());
+        }
+
+        match (
+            open_sealed(&self.vault_key, payload),
+            &self.legacy_vault_key,
+        ) {
+            (Err(_), Some(legacy_vault_key)) => open_sealed(legacy_vault_key, payload),
+            (opened, _) => opened,
+        }
+    }
+
+    /// Decrypts a vault key that was sealed with `seal`
+    pub(crate) fn open_key(
+        &self,
+        payload: &[u8],
+    ) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+        let key = Zeroizing::new(self.open(payload)?);
+
+        <[u8; KEY_LEN]>::try_from(key.as_slice())
+            .map(Secret::new)
+            .map_err(|_| LocalLedgerError::new("Invalid vault key"))
+    }
+
+    /// Whether `payload` has to be sealed again to be sealed with the vault key, because it was
+    /// encrypted with the password, or sealed with the vault key that was replaced
+    pub(crate) fn needs_reseal(&self, payload: &[u8]) -> bool {
+        !is_sealed(payload)
+            || (self.legacy_vault_key.is_some() && open_sealed(&self.vault_key, payload).is_err())
+    }
+
+    /// Returns the opaque id the entry `entry_name` is stored under.  The id is keyed with the
+    /// vault key, so it gives nothing about the name away, yet every device holding the vault
+    /// key comes up with the same id for the same entry.  Entries keep their ids when a derived
+    /// vault key is replaced.
+    pub(crate) fn entry_id(&self, entry_name: &str) -> Result<String, LocalLedgerError> {
+        let id_key = self.legacy_vault_key.as_ref().unwrap_or(&self.vault_key);
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(id_key.expose_secret())
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to derive entry id: {}", err)))?;
+
+        mac.update(ENTRY_ID_INFO);
+        mac.update(entry_name.as_bytes());
+
+        Ok(mac.finalize().into_bytes()[..ENTRY_ID_LEN]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// Secret key of the age identity of the vault.  Every device derives the same one, so the
+    /// identity does not have to be stored or synced.
+    pub(crate) fn identity_key(&self) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+        let mut identity_key = [0u8; KEY_LEN];
+
+        Hkdf::<Sha256>::new(None, self.vault_key.expose_secret())
+            .expand(IDENTITY_KEY_INFO, &mut identity_key)
+            .map_err(|err| LocalLedgerError::new(&format!("Failed to derive key: {}", err)))?;
+
+        Ok(Secret::new(identity_key))
+    }
+}
+
+/// Derives the key of a single payload from the vault key and the salt of the payload
+fn derive_doc_key(
+    vault_key: &Secret<[u8; KEY_LEN]>,
+    salt: &[u8],
+) -> Result<Secret<[u8; KEY_LEN]>, LocalLedgerError> {
+    let mut doc_key = [0u8; KEY_LEN];
+
+    Hkdf::<Sha256>::new(Some(salt), vault_key.expose_secret())
+        .expand(DOC_KEY_INFO, &mut doc_key)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to derive key: {}", err)))?;
+
+    Ok(Secret::new(doc_key))
+}
+
+/// Decrypts a payload written by `VaultKeys::seal` with `vault_key`
+fn open_sealed(
+    vault_key: &Secret<[u8; KEY_LEN]>,
+    payload: &[u8],
+) -> Result<Vec<u8>, LocalLedgerError> {
+    let sealed = &payload[SEALED_MAGIC.len()..];
+
+    if sealed.len() < SALT_LEN {
+        return Err(LocalLedgerError::new("Failed to decrypt data: truncated"));
+    }
+
+    let (salt, sealed) = sealed.split_at(SALT_LEN);
+    let doc_key = derive_doc_key(vault_key, salt)?;
+
+    open_with(doc_key.expose_secret(), sealed)
+}
+
+fn copy_key(key: &Secret<[u8; KEY_LEN]>) -> Secret<[u8; KEY_LEN]> {
+    Secret::new(*key.expose_secret())
+}
+
+/// Returns true if `payload` was written by `VaultKeys::seal`
+pub(crate) fn is_sealed(payload: &[u8]) -> bool {
+    payload.starts_with(SEALED_MAGIC)
+}
+
+fn seal_with(key: &[u8; KEY_LEN], data: &[u8]) -> Result<Vec<u8>, LocalLedgerError> {
+    let cipher = XChaCha20Poly1305::new(key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, data)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to encrypt data: {}", err)))?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn open_with(key: &[u8; KEY_LEN], sealed: &[u8]) -> Result<Vec<u8>, LocalLedgerError> {
+    if sealed.len() < NONCE_LEN {
+        return Err(LocalLedgerError::new("Failed to decrypt data: truncated"));
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let cipher = XChaCha20Poly1305::new(key.into());
+
+    cipher
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)))
+}
+
+fn open_legacy(encrypted_data: &[u8], password: &str) -> Result<Vec<u8>, LocalLedgerError> {
+    let decryptor = match age::Decryptor::new(encrypted_data).map_err(|err| {
+        tracing::error!("decryptor error: {:?}", err);
+        LocalLedgerError::new(&format!("Failed to decrypt data: {}", err))
+    })? {
+        age::Decryptor::Passphrase(d) => Ok(d),
+        _ => Err(LocalLedgerError::new("Failed to decrypt. Received encrypted data that was secured by some means other than a passphrase."))
+    }?;
+
+    let mut decrypted = vec![];
+    let mut reader = decryptor
+        .decrypt(&Secret::new(password.to_owned()), None)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)))?;
+
+    reader
+        .read_to_end(&mut decrypted)
+        .map_err(|err| LocalLedgerError::new(&format!("Failed to decrypt data: {}", err)))?;
+
+    Ok(decrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_only_unwrap_vault_key_with_its_password() {
+        let (wrapped_key, vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+
+        assert_eq!(
+            wrapped_key
+                .unwrap(&CompositeKey::new("master_password".to_owned()))
+                .unwrap()
+                .expose_secret(),
+            vault_key.expose_secret()
+        );
+        assert_eq!(
+            wrapped_key
+                .unwrap(&CompositeKey::new("not the password".to_owned()))
+                .unwrap_err()
+                .message,
+            "Incorrect password"
+        );
+
+        let rewrapped_key =
+            WrappedVaultKey::rewrap(&CompositeKey::new("new_password".to_owned()), &vault_key)
+                .unwrap();
+
+        assert_ne!(rewrapped_key.kdf, wrapped_key.kdf);
+        assert_eq!(
+            rewrapped_key
+                .unwrap(&CompositeKey::new("new_password".to_owned()))
+                .unwrap()
+                .expose_secret(),
+            vault_key.expose_secret()
+        );
+    }
+
+    #[test]
+    fn should_require_password_and_keyfile_once_wrapped_with_both() {
+        let keyfile = generate_keyfile();
+        let key = CompositeKey::new("master_password".to_owned()).with_keyfile(&keyfile);
+        let (wrapped_key, vault_key) = WrappedVaultKey::generate(&key).unwrap();
+        let unwrap_err = |key: CompositeKey| wrapped_key.unwrap(&key).unwrap_err().message;
+
+        assert!(wrapped_key.requires_keyfile());
+        assert_eq!(keyfile.len(), KEYFILE_LEN * 2 + 1);
+        assert_ne!(generate_keyfile(), keyfile);
+        assert_eq!(
+            wrapped_key.unwrap(&key).unwrap().expose_secret(),
+            vault_key.expose_secret()
+        );
+        assert_eq!(
+            unwrap_err(CompositeKey::new("master_password".to_owned())),
+            "Keyfile required"
+        );
+        assert_eq!(
+            unwrap_err(
+                CompositeKey::new("master_password".to_owned()).with_keyfile(&generate_keyfile())
+            ),
+            "Incorrect password or keyfile"
+        );
+        assert_eq!(
+            unwrap_err(CompositeKey::new("not the password".to_owned()).with_keyfile(&keyfile)),
+            "Incorrect password or keyfile"
+        );
+
+        let rewrapped_key =
+            WrappedVaultKey::rewrap(&key.with_keyfile_contents(None), &vault_key).unwrap();
+
+        assert!(!rewrapped_key.requires_keyfile());
+        assert_eq!(
+            rewrapped_key.unwrap(&key).unwrap_err().message,
+            "Ledger does not use a keyfile"
+        );
+    }
+
+    #[test]
+    fn should_derive_the_same_legacy_vault_key_on_every_device() {
+        let (wrapped_key_a, vault_key_a) =
+            WrappedVaultKey::from_legacy("master_password", "PW_HASH").unwrap();
+        let (wrapped_key_b, vault_key_b) =
+            WrappedVaultKey::from_legacy("master_password", "PW_HASH").unwrap();
+
+        assert_eq!(vault_key_a.expose_secret(), vault_key_b.expose_secret());
+        assert_eq!(wrapped_key_a.kdf, wrapped_key_b.kdf);
+    }
+
+    #[test]
+    fn should_derive_opaque_entry_ids() {
+        let (_, vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+        let keys = VaultKeys::new(vault_key, CompositeKey::new("master_password".to_owned()));
+        let (_, other_vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+        let other_keys = VaultKeys::new(
+            other_vault_key,
+            CompositeKey::new("master_password".to_owned()),
+        );
+        let entry_id = keys.entry_id("www.example.com").unwrap();
+
+        assert_eq!(entry_id.len(), ENTRY_ID_LEN * 2);
+        assert!(entry_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(keys.entry_id("www.example.com").unwrap(), entry_id);
+        assert_ne!(keys.entry_id("www.Example.com").unwrap(), entry_id);
+        assert_ne!(other_keys.entry_id("www.example.com").unwrap(), entry_id);
+    }
+
+    #[test]
+    fn should_seal_every_payload_with_its_own_key() {
+        let (_, vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+        let keys = VaultKeys::new(vault_key, CompositeKey::new("master_password".to_owned()));
+        let payload_0 = keys.seal(b"hunter2").unwrap();
+        let payload_1 = keys.seal(b"hunter2").unwrap();
+
+        assert!(is_sealed(&payload_0));
+        assert_ne!(payload_0, payload_1);
+        assert_eq!(keys.open(&payload_0).unwrap(), b"hunter2");
+
+        let mut tampered = payload_0.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+
+        assert!(keys.open(&tampered).is_err());
+    }
+
+    #[test]
+    fn should_read_payloads_of_the_replaced_vault_key() {
+        let (_, legacy_vault_key) =
+            WrappedVaultKey::from_legacy("master_password", "PW_HASH").unwrap();
+        let legacy_keys = VaultKeys::new(
+            copy_key(&legacy_vault_key),
+            CompositeKey::new("master_password".to_owned()),
+        );
+        let (_, vault_key) =
+            WrappedVaultKey::generate(&CompositeKey::new("master_password".to_owned())).unwrap();
+        let mut keys = VaultKeys::new(vault_key, CompositeKey::new("master_password".to_owned()));
+
+        keys.set_legacy_vault_key(legacy_vault_key);
+
+        let legacy_payload = legacy_keys.seal(b"hunter2").unwrap();
+        let payload = keys.seal(b"hunter2").unwrap();
+
+        assert_eq!(keys.open(&legacy_payload).unwrap(), b"hunter2");
+        assert!(keys.needs_reseal(&legacy_payload));
+        assert!(!keys.needs_reseal(&payload));
+        assert!(legacy_keys.open(&payload).is_err());
+        assert_eq!(
+            keys.entry_id("www.example.com").unwrap(),
+            legacy_keys.entry_id("www.example.com").unwrap()
+        );
+    }
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use utility::SensitiveString;
+
+use crate::app_state::AppState;
+
+/// Requires the keyfile at `keyfile_path`, on top of the master password, to open the active vault
+#[tauri::command]
+pub async fn add_keyfile<'a>(
+    master_pw: SensitiveString,
+    keyfile_path: PathBuf,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .set_keyfile(&master_pw, Some(&keyfile_path))
+}
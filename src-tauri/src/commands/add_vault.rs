@@ -0,0 +1,12 @@
+use std::path::PathBuf;
+
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn add_vault<'a>(
+    name: String,
+    path: PathBuf,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.add_vault(&name, &path)
+}
@@ -0,0 +1,7 @@
+use crate::app_state::AppState;
+
+/// Merges the docs downloaded by the last `pull` into the vault
+#[tauri::command]
+pub async fn apply_pull<'a>(app_state: tauri::State<'a, AppState>) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.apply_pending_merge().await
+}
@@ -0,0 +1,16 @@
+use utility::SensitiveString;
+
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn change_password<'a>(
+    old_pw: SensitiveString,
+    new_pw: SensitiveString,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .change_password(&old_pw, &new_pw)
+}
@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn close_vault<'a>(
+    name: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.close_vault(&name)
+}
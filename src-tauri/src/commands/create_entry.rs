@@ -0,0 +1,15 @@
+use crate::app_state::AppState;
+use crate::commands::EntryDetails;
+
+#[tauri::command]
+pub async fn create_entry<'a>(
+    entry_name: String,
+    details: EntryDetails,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .create_entry(&entry_name, details)
+}
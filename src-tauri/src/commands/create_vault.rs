@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use utility::SensitiveString;
+
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn create_vault<'a>(
+    name: String,
+    master_pw: SensitiveString,
+    path: Option<PathBuf>,
+    keyfile_path: Option<PathBuf>,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.create_vault(
+        &name,
+        &master_pw,
+        path.as_deref(),
+        keyfile_path.as_deref(),
+    )
+}
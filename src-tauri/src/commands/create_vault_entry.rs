@@ -0,0 +1,15 @@
+use crate::app_state::AppState;
+use crate::commands::VaultEntry;
+
+#[tauri::command]
+pub async fn create_vault_entry<'a>(
+    entry_name: String,
+    entry: VaultEntry,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .create_vault_entry(&entry_name, entry)
+}
@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use utility::SensitiveString;
+
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn delete_vault<'a>(
+    name: String,
+    master_pw: SensitiveString,
+    keyfile_path: Option<PathBuf>,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .delete_vault(&name, &master_pw, keyfile_path.as_deref())
+}
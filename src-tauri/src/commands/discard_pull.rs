@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+
+/// Throws away the docs downloaded by the last `pull`
+#[tauri::command]
+pub async fn discard_pull<'a>(app_state: tauri::State<'a, AppState>) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.discard_pending_merge();
+
+    Ok(())
+}
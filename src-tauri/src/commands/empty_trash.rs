@@ -0,0 +1,6 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn empty_trash<'a>(app_state: tauri::State<'a, AppState>) -> Result<usize, String> {
+    app_state.pw_ledger.lock().await.empty_trash()
+}
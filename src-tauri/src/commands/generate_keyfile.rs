@@ -0,0 +1,15 @@
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+
+/// Writes a new keyfile to `path`.  An existing file is never overwritten, it could be the
+/// keyfile of a vault.
+#[tauri::command]
+pub async fn generate_keyfile(path: PathBuf) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| format!("Failed to create keyfile: {}", e))?;
+
+    file.write_all(&local_ledger::generate_keyfile())
+        .map_err(|e| format!("Failed to write keyfile: {}", e))
+}
@@ -1,3 +1,5 @@
+use utility::SensitiveString;
+
 use crate::AppState;
 
 #[tauri::command]
@@ -6,16 +8,34 @@ pub async fn get_conf_pair<'a>(
     app_state: tauri::State<'a, AppState>,
 ) -> Result<ConfPair, String> {
     let mut pw_ledger = app_state.pw_ledger.lock().await;
-    let pair = pw_ledger.get_conf_pair(&entry_name)?;
+    let (local_pw, remote_pws) = pw_ledger.get_conf_pair(&entry_name)?;
+    let remote_pws: Vec<RemotePw> = remote_pws
+        .into_iter()
+        .map(|(rev, pw)| RemotePw { rev, pw })
+        .collect();
+    let remote_pw = remote_pws
+        .first()
+        .map(|remote_pw| remote_pw.pw.clone())
+        .ok_or("Entry has no conflicts.".to_string())?;
 
     Ok(ConfPair {
-        local_pw: pair.0,
-        remote_pw: pair.1,
+        local_pw,
+        remote_pw,
+        remote_pws,
     })
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ConfPair {
-    pub local_pw: String,
-    pub remote_pw: String,
+    pub local_pw: SensitiveString,
+    /// Password of the first conflicting copy
+    pub remote_pw: SensitiveString,
+    /// Passwords of every conflicting copy
+    pub remote_pws: Vec<RemotePw>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RemotePw {
+    pub rev: String,
+    pub pw: SensitiveString,
 }
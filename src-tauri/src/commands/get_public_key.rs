@@ -0,0 +1,7 @@
+use crate::app_state::AppState;
+
+/// Public key of the active vault, for other users to share entries with it
+#[tauri::command]
+pub async fn get_public_key<'a>(app_state: tauri::State<'a, AppState>) -> Result<String, String> {
+    app_state.pw_ledger.lock().await.public_key()
+}
@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn import_shared_entry<'a>(
+    path: PathBuf,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<String, String> {
+    app_state.pw_ledger.lock().await.import_shared_entry(&path)
+}
@@ -0,0 +1,6 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn is_locked<'a>(app_state: tauri::State<'a, AppState>) -> Result<bool, String> {
+    Ok(app_state.pw_ledger.lock().await.is_locked())
+}
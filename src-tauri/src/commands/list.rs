@@ -1,7 +1,15 @@
 use crate::app_state::AppState;
-use crate::EntryMetaData;
+use crate::{EntryFilter, EntryMetaData};
 
 #[tauri::command]
-pub async fn list<'a>(app_state: tauri::State<'a, AppState>) -> Result<Vec<EntryMetaData>, String> {
-    app_state.pw_ledger.lock().await.list_entry_meta_data()
+pub async fn list<'a>(
+    folder: Option<String>,
+    tag: Option<String>,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<EntryMetaData>, String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .list_entry_meta_data(&EntryFilter { folder, tag })
 }
@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+use local_ledger::TrashedEntry;
+
+#[tauri::command]
+pub async fn list_trash<'a>(
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<TrashedEntry>, String> {
+    app_state.pw_ledger.lock().await.list_trash()
+}
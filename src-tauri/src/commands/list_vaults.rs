@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+use crate::password_ledger_handler::VaultInfo;
+
+#[tauri::command]
+pub async fn list_vaults<'a>(
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<VaultInfo>, String> {
+    app_state.pw_ledger.lock().await.list_vaults()
+}
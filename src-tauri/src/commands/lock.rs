@@ -0,0 +1,21 @@
+use tauri::Manager;
+
+use crate::app_state::AppState;
+
+/// Event sent to the frontend whenever the vaults get locked, by hand or after being idle
+pub const VAULT_LOCKED_EVENT: &str = "vault-locked";
+
+/// Locks every open vault, see `open_collection` to unlock them again
+#[tauri::command]
+pub async fn lock<'a>(
+    app_handle: tauri::AppHandle,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    if app_state.pw_ledger.lock().await.lock() {
+        app_handle
+            .emit_all(VAULT_LOCKED_EVENT, ())
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
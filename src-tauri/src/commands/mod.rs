@@ -1,32 +1,106 @@
 mod add_entry;
+mod add_keyfile;
+mod add_vault;
+mod apply_pull;
+mod change_password;
+mod close_vault;
+mod copy_entry_to_vault;
+mod create_entry;
+mod create_vault;
+mod create_vault_entry;
+mod delete_vault;
+mod discard_pull;
+mod edit_entry;
+mod edit_vault_entry;
+mod empty_trash;
 mod export_ledger;
+mod generate_keyfile;
 mod generate_pw;
 mod get_conf_pair;
+mod get_public_key;
 mod greet;
+mod import_shared_entry;
+mod is_locked;
 mod list;
+mod list_folders;
+mod list_trash;
+mod list_vaults;
+mod lock;
+mod move_entry;
+mod move_entry_to_vault;
 mod open_collection;
 mod pull;
 mod push;
 mod push_s;
 mod read_entry;
+mod read_entry_details;
+mod read_vault_entry;
 mod regen_pw;
 mod remove_entry;
+mod remove_keyfile;
+mod rename_folder;
+mod rename_vault;
+mod repair_vault;
 mod resolve_conflict;
+mod restore_entry;
 mod saved_password;
+mod search_entries;
+mod set_auto_lock;
+mod set_trash_retention;
+mod share_entry;
+mod vault_entry;
+mod verify_vault;
 
 pub use add_entry::add_entry;
+pub use add_keyfile::add_keyfile;
+pub use add_vault::add_vault;
+pub use apply_pull::apply_pull;
+pub use change_password::change_password;
+pub use close_vault::close_vault;
+pub use copy_entry_to_vault::copy_entry_to_vault;
+pub use create_entry::create_entry;
+pub use create_vault::create_vault;
+pub use create_vault_entry::create_vault_entry;
+pub use delete_vault::delete_vault;
+pub use discard_pull::discard_pull;
+pub use edit_entry::edit_entry;
+pub use edit_vault_entry::edit_vault_entry;
+pub use empty_trash::empty_trash;
 pub use export_ledger::*;
+pub use generate_keyfile::generate_keyfile;
 pub use generate_pw::*;
 pub use get_conf_pair::*;
+pub use get_public_key::get_public_key;
 pub use greet::greet;
+pub use import_shared_entry::import_shared_entry;
+pub use is_locked::is_locked;
 pub use list::list;
+pub use list_folders::list_folders;
+pub use list_trash::list_trash;
+pub use list_vaults::list_vaults;
+pub use lock::{lock, VAULT_LOCKED_EVENT};
+pub use move_entry::move_entry;
+pub use move_entry_to_vault::move_entry_to_vault;
 pub use open_collection::open_collection;
 pub use pull::pull;
 pub use push::push;
 pub use push_s::push_s;
 pub use read_entry::read_entry;
+pub use read_entry_details::read_entry_details;
+pub use read_vault_entry::read_vault_entry;
 pub use regen_pw::regen_pw;
 pub use remove_entry::remove_entry;
+pub use remove_keyfile::remove_keyfile;
+pub use rename_folder::rename_folder;
+pub use rename_vault::rename_vault;
+pub use repair_vault::repair_vault;
 pub use resolve_conflict::*;
+pub use restore_entry::restore_entry;
+pub use search_entries::search_entries;
+pub use set_auto_lock::set_auto_lock;
+pub use set_trash_retention::set_trash_retention;
+pub use share_entry::share_entry;
+pub use verify_vault::verify_vault;
 
-pub use saved_password::SavedPassword;
+pub use saved_password::{EntryDetails, SavedPassword};
+pub use vault_entry::{is_in_folder, normalize_folder, EntryKind, VaultEntry};
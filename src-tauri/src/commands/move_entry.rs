@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn move_entry<'a>(
+    entry_name: String,
+    folder: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .move_entry(&entry_name, &folder)
+}
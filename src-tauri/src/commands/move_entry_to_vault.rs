@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn move_entry_to_vault<'a>(
+    entry_name: String,
+    vault: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .move_entry_to_vault(&entry_name, &vault)
+}
@@ -1,11 +1,29 @@
+use std::path::PathBuf;
+
+use utility::SensitiveString;
+
 use crate::app_state::AppState;
 
+/// Opens the vault `vault`, or the default vault, and makes it the vault entry commands work on
 #[tauri::command]
 pub async fn open_collection<'a>(
-    master_pw: String,
+    master_pw: SensitiveString,
+    vault: Option<String>,
+    keyfile_path: Option<PathBuf>,
+    trash_retention_days: Option<u64>,
     app_state: tauri::State<'a, AppState>,
 ) -> Result<(), String> {
     let mut ledger = app_state.pw_ledger.lock().await;
 
-    ledger.start(&master_pw)
+    // Set before opening, the trash is purged on open
+    if let Some(days) = trash_retention_days {
+        ledger.set_trash_retention(days)?;
+    }
+
+    let vault = match vault {
+        Some(vault) => vault,
+        None => ledger.default_vault_name()?,
+    };
+
+    ledger.open_vault(&vault, &master_pw, keyfile_path.as_deref())
 }
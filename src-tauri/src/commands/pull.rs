@@ -1,18 +1,20 @@
 use crate::app_state::AppState;
+use local_ledger::MergePreview;
 
+/// Downloads the docs pushed under `pin` and returns what merging them into the active vault
+/// would do.  Nothing changes until `apply_pull` is called.
 #[tauri::command]
 pub async fn pull<'a>(
     temp_pw: String,
     pin: String,
     app_state: tauri::State<'a, AppState>,
-) -> Result<(), String> {
+) -> Result<MergePreview, String> {
     let ps = app_state
         .land_strider
         .pull_s(&pin, &temp_pw)
         .await
         .map_err(|e| e.to_string())?;
     let mut pw_ledger = app_state.pw_ledger.lock().await;
-    pw_ledger.merge(ps).await?;
 
-    Ok(())
+    pw_ledger.preview_merge(ps).await
 }
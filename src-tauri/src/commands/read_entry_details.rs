@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+use crate::commands::SavedPassword;
+
+#[tauri::command]
+pub async fn read_entry_details<'a>(
+    entry_name: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<SavedPassword, String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .read_entry_details(&entry_name)
+}
@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+use crate::commands::VaultEntry;
+
+#[tauri::command]
+pub async fn read_vault_entry<'a>(
+    entry_name: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<VaultEntry, String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .read_vault_entry(&entry_name)
+}
@@ -0,0 +1,16 @@
+use utility::SensitiveString;
+
+use crate::app_state::AppState;
+
+/// Lets the active vault be opened with its master password alone again
+#[tauri::command]
+pub async fn remove_keyfile<'a>(
+    master_pw: SensitiveString,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .set_keyfile(&master_pw, None)
+}
@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn rename_folder<'a>(
+    folder: String,
+    new_folder: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<usize, String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .rename_folder(&folder, &new_folder)
+}
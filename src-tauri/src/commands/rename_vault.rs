@@ -0,0 +1,14 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn rename_vault<'a>(
+    name: String,
+    new_name: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .rename_vault(&name, &new_name)
+}
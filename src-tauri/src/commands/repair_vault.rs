@@ -0,0 +1,10 @@
+use crate::app_state::AppState;
+use local_ledger::RepairedProblem;
+
+/// Fixes what it safely can of the damaged docs of the active vault, moving the rest out of it
+#[tauri::command]
+pub async fn repair_vault<'a>(
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<RepairedProblem>, String> {
+    app_state.pw_ledger.lock().await.repair_vault()
+}
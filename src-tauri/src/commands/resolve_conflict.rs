@@ -4,11 +4,12 @@ use crate::AppState;
 pub async fn resolve_conflict<'a>(
     entry_name: String,
     keep_original: bool,
+    remote_rev: Option<String>,
     app_state: tauri::State<'a, AppState>,
 ) -> Result<(), String> {
     app_state
         .pw_ledger
         .lock()
         .await
-        .resolve(&entry_name, keep_original)
+        .resolve(&entry_name, keep_original, remote_rev)
 }
@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn restore_entry<'a>(
+    entry_name: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.restore_entry(&entry_name)
+}
@@ -1,5 +1,192 @@
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+use utility::{LocalLedgerError, SensitiveString};
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
 pub struct SavedPassword {
-    pub pw: String,
+    pub pw: SensitiveString,
     pub name: String,
+    pub username: String,
+    pub urls: Vec<String>,
+    pub notes: String,
+    pub custom_fields: Vec<CustomField>,
+    pub tags: Vec<String>,
+    /// Folder the entry is filed under, like `Work/Servers`.  Empty for the top level.
+    #[serde(default)]
+    pub folder: String,
+    /// Unix timestamp (seconds).  None for entries saved before it was tracked.
+    pub created_at: Option<u64>,
+    /// Unix timestamp (seconds) of the last edit.  None for entries saved before it was tracked.
+    pub modified_at: Option<u64>,
+}
+
+/// A key/value pair the user added to an entry.  Hidden fields are masked like the password.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
+pub struct CustomField {
+    pub key: String,
+    pub value: SensitiveString,
+    pub hidden: bool,
+}
+
+/// The parts of a saved password the user can edit
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, Default, PartialEq)]
+pub struct EntryDetails {
+    pub pw: SensitiveString,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub notes: String,
+    #[serde(default)]
+    pub custom_fields: Vec<CustomField>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl SavedPassword {
+    pub fn new(name: &str, details: EntryDetails) -> Self {
+        let mut saved_password = Self {
+            name: name.to_owned(),
+            created_at: Some(unix_now()),
+            ..Default::default()
+        };
+
+        saved_password.edit(details);
+        saved_password
+    }
+
+    /// Replaces every editable field with `details`
+    pub fn edit(&mut self, details: EntryDetails) {
+        self.pw = details.pw;
+        self.username = details.username;
+        self.urls = details.urls;
+        self.notes = details.notes;
+        self.custom_fields = details.custom_fields;
+        self.tags = details.tags;
+        self.modified_at = Some(unix_now());
+    }
+
+    pub fn details(&self) -> EntryDetails {
+        EntryDetails {
+            pw: self.pw.clone(),
+            username: self.username.clone(),
+            urls: self.urls.clone(),
+            notes: self.notes.clone(),
+            custom_fields: self.custom_fields.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+impl local_ledger::Migrate for SavedPassword {
+    const SCHEMA_VERSION: u32 = 1;
+
+    fn migrate(from_version: u32, mut payload: Value) -> Result<Value, LocalLedgerError> {
+        match from_version {
+            // Entries used to only hold a name and a password
+            0 => {
+                let fields = payload
+                    .as_object_mut()
+                    .ok_or(LocalLedgerError::new("Saved password is not an object"))?;
+
+                fields.insert("username".to_owned(), json!(""));
+                fields.insert("urls".to_owned(), json!([]));
+                fields.insert("notes".to_owned(), json!(""));
+                fields.insert("custom_fields".to_owned(), json!([]));
+                fields.insert("tags".to_owned(), json!([]));
+                fields.insert("created_at".to_owned(), Value::Null);
+                fields.insert("modified_at".to_owned(), Value::Null);
+
+                Ok(payload)
+            }
+            _ => Err(LocalLedgerError::new(&format!(
+                "Unknown saved password schema version: {}",
+                from_version
+            ))),
+        }
+    }
+}
+
+impl local_ledger::Searchable for SavedPassword {
+    fn search_terms(&self) -> Vec<String> {
+        let custom_fields = self.custom_fields.iter().flat_map(|field| {
+            // Hidden values are as secret as the password
+            let value = (!field.hidden).then(|| field.value.to_string());
+
+            std::iter::once(field.key.clone()).chain(value)
+        });
+
+        [
+            self.name.clone(),
+            self.username.clone(),
+            self.notes.clone(),
+            self.folder.clone(),
+        ]
+        .into_iter()
+        .chain(self.urls.iter().cloned())
+        .chain(self.tags.iter().cloned())
+        .chain(custom_fields)
+        .collect()
+    }
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use local_ledger::{Migrate, Searchable};
+
+    #[test]
+    fn should_migrate_saved_passwords_with_only_a_name_and_password() {
+        let payload = json!({ "pw": "hunter2", "name": "bank" });
+        let migrated = SavedPassword::migrate(0, payload).unwrap();
+        let saved_password: SavedPassword = serde_json::from_value(migrated).unwrap();
+
+        assert_eq!(
+            saved_password,
+            SavedPassword {
+                pw: "hunter2".into(),
+                name: "bank".to_owned(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn should_keep_secrets_out_of_search() {
+        let saved_password = SavedPassword::new(
+            "bank",
+            EntryDetails {
+                pw: "hunter2".into(),
+                username: "me@example.com".to_owned(),
+                custom_fields: vec![
+                    CustomField {
+                        key: "pin".to_owned(),
+                        value: "1234".into(),
+                        hidden: true,
+                    },
+                    CustomField {
+                        key: "branch".to_owned(),
+                        value: "downtown".into(),
+                        hidden: false,
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+        let search_terms = saved_password.search_terms();
+
+        assert!(search_terms.contains(&"me@example.com".to_owned()));
+        assert!(search_terms.contains(&"downtown".to_owned()));
+        assert!(search_terms.contains(&"pin".to_owned()));
+        assert!(!search_terms.contains(&"1234".to_owned()));
+        assert!(!search_terms.contains(&"hunter2".to_owned()));
+    }
 }
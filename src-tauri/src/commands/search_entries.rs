@@ -0,0 +1,10 @@
+use crate::app_state::AppState;
+use local_ledger::SearchHit;
+
+#[tauri::command]
+pub async fn search_entries<'a>(
+    query: String,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<SearchHit>, String> {
+    app_state.pw_ledger.lock().await.search_entries(&query)
+}
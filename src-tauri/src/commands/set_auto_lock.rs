@@ -0,0 +1,21 @@
+use std::time::Duration;
+
+use crate::app_state::AppState;
+
+/// Locks the vaults once they have not been used for `idle_minutes`.  Without it, the vaults
+/// stay unlocked until they are locked by hand.
+#[tauri::command]
+pub async fn set_auto_lock<'a>(
+    idle_minutes: Option<u64>,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    let idle_timeout = idle_minutes.map(|minutes| Duration::from_secs(minutes.saturating_mul(60)));
+
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .set_idle_timeout(idle_timeout);
+
+    Ok(())
+}
@@ -0,0 +1,9 @@
+use crate::app_state::AppState;
+
+#[tauri::command]
+pub async fn set_trash_retention<'a>(
+    days: u64,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state.pw_ledger.lock().await.set_trash_retention(days)
+}
@@ -0,0 +1,18 @@
+use std::path::PathBuf;
+
+use crate::app_state::AppState;
+
+/// Writes the entry to `path`, encrypted to the public keys of the users it is shared with
+#[tauri::command]
+pub async fn share_entry<'a>(
+    entry_name: String,
+    public_keys: Vec<String>,
+    path: PathBuf,
+    app_state: tauri::State<'a, AppState>,
+) -> Result<(), String> {
+    app_state
+        .pw_ledger
+        .lock()
+        .await
+        .share_entry(&entry_name, &public_keys, &path)
+}
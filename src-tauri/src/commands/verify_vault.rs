@@ -0,0 +1,10 @@
+use crate::app_state::AppState;
+use local_ledger::IntegrityProblem;
+
+/// Checks the active vault for damaged docs.  Nothing is changed, see `repair_vault`.
+#[tauri::command]
+pub async fn verify_vault<'a>(
+    app_state: tauri::State<'a, AppState>,
+) -> Result<Vec<IntegrityProblem>, String> {
+    app_state.pw_ledger.lock().await.verify_vault()
+}
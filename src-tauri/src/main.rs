@@ -6,14 +6,20 @@
 mod app_state;
 mod commands;
 mod password_ledger_handler;
+mod vault_registry;
 
 use app_state::*;
 use commands::*;
 use land_strider_sdk::*;
 use password_ledger_handler::*;
+use std::time::Duration;
+use tauri::Manager;
 use tokio::sync::Mutex;
 use tracing_subscriber::{fmt, layer::SubscriberExt, EnvFilter};
 
+/// How often the vaults are checked for being idle, see `PasswordLedgerHandler::lock_if_idle`
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
 #[tokio::main]
 async fn main() {
     let filter = EnvFilter::new("info");
@@ -36,20 +42,80 @@ async fn main() {
         .invoke_handler(tauri::generate_handler![
             greet,
             add_entry,
+            add_vault,
+            close_vault,
+            copy_entry_to_vault,
+            create_vault,
+            delete_vault,
+            list_vaults,
+            move_entry_to_vault,
+            rename_vault,
+            create_entry,
+            create_vault_entry,
+            edit_entry,
+            edit_vault_entry,
+            empty_trash,
             list,
+            list_folders,
+            list_trash,
+            move_entry,
+            rename_folder,
             read_entry,
+            read_entry_details,
+            read_vault_entry,
             open_collection,
             generate_pw,
             regen_pw,
             remove_entry,
+            restore_entry,
+            set_trash_retention,
+            change_password,
+            add_keyfile,
+            remove_keyfile,
+            generate_keyfile,
+            get_public_key,
+            share_entry,
+            import_shared_entry,
+            lock,
+            is_locked,
+            set_auto_lock,
             export_ledger,
             push,
             pull,
+            apply_pull,
+            discard_pull,
             push_s,
             get_conf_pair,
-            resolve_conflict
+            resolve_conflict,
+            search_entries,
+            verify_vault,
+            repair_vault
         ])
-        .setup(|_app| Ok(()))
+        .setup(|app| {
+            watch_for_idle(app.handle());
+
+            Ok(())
+        })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+/// Checks every so often whether the vaults have been idle for long enough to be locked
+fn watch_for_idle(app_handle: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            let app_state = app_handle.state::<AppState>();
+            let locked = app_state.pw_ledger.lock().await.lock_if_idle();
+
+            if locked {
+                if let Err(err) = app_handle.emit_all(VAULT_LOCKED_EVENT, ()) {
+                    tracing::error!("Failed to send vault locked event: {}", err);
+                }
+            }
+        }
+    });
+}
@@ -1,151 +1,773 @@
-use std::path::PathBuf;
+use std::{
+    cell::Cell,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 
-use crate::commands::SavedPassword;
-use local_ledger::{LedgerDump, LocalLedger};
+use crate::commands::{
+    is_in_folder, normalize_folder, EntryDetails, EntryKind, SavedPassword, VaultEntry,
+};
+use crate::vault_registry::{
+    validate_vault_name, VaultLocation, VaultRegistry, DEFAULT_VAULT_NAME,
+};
+use local_ledger::{
+    CompositeKey, IntegrityProblem, LedgerDump, LocalLedger, LockMode, MergePreview,
+    RepairedProblem, Resolution, SearchHit, TrashedEntry,
+};
 use serde_json::Value;
-use tokio_stream::Stream;
+use tokio_stream::{Stream, StreamExt};
+use utility::SensitiveString;
 
-const PASSWORD_LEDGER_NAME: &str = "Password_Ledger";
+/// How long removed entries stay in the trash unless told otherwise
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// How long the vaults stay unlocked without being used unless told otherwise
+const DEFAULT_IDLE_TIMEOUT_MINUTES: u64 = 15;
 
 #[derive(Debug)]
 pub struct PasswordLedgerHandler {
-    ledger: Option<LocalLedger<SavedPassword>>,
+    registry: Result<VaultRegistry, String>,
+    /// Vaults that have been opened, by name
+    vaults: BTreeMap<String, LocalLedger<VaultEntry>>,
+    /// Vault that entry commands work on.  Always one of the open vaults.
+    active_vault: Option<String>,
+    /// Entries that have been in the trash for longer are deleted for good
+    trash_retention: Duration,
+    /// Vaults are locked once they have not been used for this long.  None keeps them unlocked.
+    idle_timeout: Option<Duration>,
+    /// When a vault was last opened or an entry command last ran
+    last_used: Cell<Instant>,
+    /// Docs that were pulled, along with the name of the vault they were previewed against, until
+    /// they are merged, see `preview_merge`
+    pending_merge: Option<(String, Vec<Value>)>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct EntryMetaData {
     pub label: String,
     pub has_conflict: bool,
+    pub kind: EntryKind,
+    pub folder: String,
+    pub tags: Vec<String>,
+}
+
+/// Narrows down `list_entry_meta_data`.  Filters that are not set match every entry.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct EntryFilter {
+    /// Only entries in this folder or its subfolders
+    pub folder: Option<String>,
+    /// Only entries with this tag, ignoring case
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct VaultInfo {
+    pub name: String,
+    /// Directory the entries of the vault live in
+    pub path: PathBuf,
+    pub is_open: bool,
+    /// Whether entry commands work on this vault
+    pub is_active: bool,
 }
 
 impl PasswordLedgerHandler {
+    /// Creates a handler for the vaults listed in `~/.fizzgig`
     pub fn new() -> Self {
-        Self { ledger: None }
+        Self::with_registry(VaultRegistry::load_home())
+    }
+
+    fn with_registry(registry: Result<VaultRegistry, String>) -> Self {
+        if let Err(err) = &registry {
+            tracing::warn!("Vaults are unavailable: {}", err);
+        }
+
+        Self {
+            registry,
+            vaults: BTreeMap::new(),
+            active_vault: None,
+            trash_retention: Duration::from_secs(DEFAULT_TRASH_RETENTION_DAYS * SECONDS_PER_DAY),
+            idle_timeout: Some(Duration::from_secs(DEFAULT_IDLE_TIMEOUT_MINUTES * 60)),
+            last_used: Cell::new(Instant::now()),
+            pending_merge: None,
+        }
     }
 
+    /// Opens the default vault
     pub fn start(&mut self, master_pw: &str) -> Result<(), String> {
-        if self.ledger.is_some() {
+        let name = self.default_vault_name()?;
+
+        self.open_vault(&name, master_pw, None)
+    }
+
+    /// Name of the default vault, which it keeps when it is renamed
+    pub fn default_vault_name(&self) -> Result<String, String> {
+        Ok(self
+            .registry()?
+            .default_vault_name()
+            .unwrap_or(DEFAULT_VAULT_NAME)
+            .to_string())
+    }
+
+    /// Opens the vault `name` and makes it the active vault.  Vaults that require a keyfile take
+    /// its path as well.  A vault that is already open just becomes the active vault.
+    pub fn open_vault(
+        &mut self,
+        name: &str,
+        master_pw: &str,
+        keyfile: Option<&Path>,
+    ) -> Result<(), String> {
+        self.last_used.set(Instant::now());
+
+        if self.vaults.contains_key(name) {
+            self.active_vault = Some(name.to_string());
+
             return Ok(());
         }
 
-        let password_ledger =
-            LocalLedger::<SavedPassword>::new(PASSWORD_LEDGER_NAME, master_pw.to_string())
-                .map_err(|e| e.to_string())?;
+        let location = self.registry()?.location(name).ok_or("Vault not found.")?;
+
+        // Only the default vault gets created by opening it, a missing vault is more likely to
+        // be on a drive that is not plugged in
+        if name != DEFAULT_VAULT_NAME
+            && !LocalLedger::<VaultEntry>::exists(&location.store(), &location.label)
+                .map_err(|e| e.to_string())?
+        {
+            return Err(format!("No vault found at {}", location.path().display()));
+        }
+
+        let password_ledger = self.start_ledger(&location, composite_key(master_pw, keyfile)?)?;
+
+        if !self.registry()?.vaults().contains_key(name) {
+            self.registry_mut()?.add(name, location)?;
+        }
+
+        self.vaults.insert(name.to_string(), password_ledger);
+        self.active_vault = Some(name.to_string());
+        self.update_core_dumps();
+
+        Ok(())
+    }
+
+    /// Creates the vault `name` with a master password, and optionally a keyfile, of its own,
+    /// then opens it.  Without a `path` the vault is kept next to the default vault.
+    pub fn create_vault(
+        &mut self,
+        name: &str,
+        master_pw: &str,
+        path: Option<&Path>,
+        keyfile: Option<&Path>,
+    ) -> Result<(), String> {
+        // Checked up front, the vault would be left behind if the registry refused its name
+        let name = validate_vault_name(name)?;
+        let location = self.registry()?.new_location(path)?;
+
+        if self.registry()?.vaults().contains_key(name) {
+            return Err("Vault names must be unique".to_string());
+        }
+
+        if LocalLedger::<VaultEntry>::exists(&location.store(), &location.label)
+            .map_err(|e| e.to_string())?
+        {
+            return Err(format!(
+                "A vault already exists at {}",
+                location.path().display()
+            ));
+        }
+
+        let password_ledger = self.start_ledger(&location, composite_key(master_pw, keyfile)?)?;
+
+        self.registry_mut()?.add(name, location)?;
+        self.vaults.insert(name.to_string(), password_ledger);
+        self.active_vault = Some(name.to_string());
+        self.update_core_dumps();
+
+        Ok(())
+    }
+
+    /// Adds the vault in the directory `path` to the vaults of this installation, like a vault
+    /// on an external drive that was created elsewhere
+    pub fn add_vault(&mut self, name: &str, path: &Path) -> Result<(), String> {
+        let location = VaultLocation::at_path(path)?;
+
+        if !LocalLedger::<VaultEntry>::exists(&location.store(), &location.label)
+            .map_err(|e| e.to_string())?
+        {
+            return Err(format!("No vault found at {}", path.display()));
+        }
+
+        self.registry_mut()?.add(name, location)
+    }
+
+    pub fn list_vaults(&self) -> Result<Vec<VaultInfo>, String> {
+        Ok(self
+            .registry()?
+            .vaults()
+            .iter()
+            .map(|(name, location)| VaultInfo {
+                name: name.clone(),
+                path: location.path(),
+                is_open: self.vaults.contains_key(name),
+                is_active: self.active_vault.as_deref() == Some(name.as_str()),
+            })
+            .collect())
+    }
+
+    /// Closes every open vault.  The vault keys and decrypted entries go with them, so the
+    /// master password is needed again before any entry can be read.  Returns false if no vault
+    /// was open.
+    pub fn lock(&mut self) -> bool {
+        let was_unlocked = !self.vaults.is_empty();
+
+        self.vaults.clear();
+        self.active_vault = None;
+        self.pending_merge = None;
+        self.update_core_dumps();
+
+        if was_unlocked {
+            tracing::info!("Locked vaults");
+        }
+
+        was_unlocked
+    }
+
+    /// Locks the vaults if they have not been used for longer than the idle timeout.  Returns
+    /// true if they were locked.
+    pub fn lock_if_idle(&mut self) -> bool {
+        match self.idle_timeout {
+            Some(idle_timeout) if self.last_used.get().elapsed() >= idle_timeout => self.lock(),
+            _ => false,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.vaults.is_empty()
+    }
+
+    /// Sets how long the vaults stay unlocked without being used.  None keeps them unlocked
+    /// until they are locked by hand.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Option<Duration>) {
+        self.idle_timeout = idle_timeout;
+    }
 
-        self.ledger = Some(password_ledger);
+    /// Closes the vault `name`, releasing its lock.  Opening it again takes its master password.
+    pub fn close_vault(&mut self, name: &str) -> Result<(), String> {
+        self.vaults.remove(name).ok_or("Vault is not open.")?;
+
+        if self.active_vault.as_deref() == Some(name) {
+            self.active_vault = None;
+        }
+
+        self.update_core_dumps();
+
+        Ok(())
+    }
+
+    pub fn rename_vault(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        self.registry_mut()?.rename(name, new_name)?;
+
+        let new_name = new_name.trim().to_string();
+
+        if let Some(password_ledger) = self.vaults.remove(name) {
+            self.vaults.insert(new_name.clone(), password_ledger);
+        }
+
+        if self.active_vault.as_deref() == Some(name) {
+            self.active_vault = Some(new_name);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the vault `name` and every entry in it.  Takes the master password, and keyfile,
+    /// of the vault, and closes the vault if it is open.  Copies synced to other devices are
+    /// kept.
+    pub fn delete_vault(
+        &mut self,
+        name: &str,
+        master_pw: &str,
+        keyfile: Option<&Path>,
+    ) -> Result<(), String> {
+        let composite_key = composite_key(master_pw, keyfile)?;
+        let location = self.registry()?.location(name).ok_or("Vault not found.")?;
+        let store = location.store();
+
+        if self.vaults.contains_key(name) {
+            self.close_vault(name)?;
+        }
+
+        if LocalLedger::<VaultEntry>::exists(&store, &location.label).map_err(|e| e.to_string())? {
+            // Opening checks the master password
+            LocalLedger::<VaultEntry>::new_with_composite_key(
+                &location.label,
+                composite_key,
+                store,
+                LockMode::Exclusive,
+            )
+            .and_then(|password_ledger| password_ledger.destroy())
+            .map_err(|e| e.to_string())?;
+        }
+
+        if self.registry()?.vaults().contains_key(name) {
+            self.registry_mut()?.remove(name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copies `entry_name` from the active vault to the open vault `vault`
+    pub fn copy_entry_to_vault(&mut self, entry_name: &str, vault: &str) -> Result<(), String> {
+        let entry = self.read_vault_entry(entry_name)?;
+
+        if self.active_vault.as_deref() == Some(vault) {
+            return Err("Entry is already in this vault".to_string());
+        }
+
+        let target_ledger = self.vaults.get_mut(vault).ok_or("Vault is not open.")?;
+
+        target_ledger
+            .create(entry, entry_name)
+            .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+
+    /// Moves `entry_name` from the active vault to the open vault `vault`.  The entry is left in
+    /// the trash of the active vault.
+    pub fn move_entry_to_vault(&mut self, entry_name: &str, vault: &str) -> Result<(), String> {
+        self.copy_entry_to_vault(entry_name, vault)?;
+        self.remove_entry(entry_name)
+    }
+
+    /// Opens the ledger of the vault at `location` and brings its entries up to date
+    fn start_ledger(
+        &self,
+        location: &VaultLocation,
+        composite_key: CompositeKey,
+    ) -> Result<LocalLedger<VaultEntry>, String> {
+        // Keep other app instances and scripts out while the vault is open here
+        let mut password_ledger = LocalLedger::<VaultEntry>::new_with_composite_key(
+            &location.label,
+            composite_key,
+            location.store(),
+            LockMode::Exclusive,
+        )
+        .map_err(|e| e.to_string())?;
+
+        // Entries from before the ledger had a vault key are slow to read, move them over once
+        let upgraded = password_ledger
+            .upgrade_encryption()
+            .map_err(|e| e.to_string())?;
+
+        if upgraded > 0 {
+            tracing::info!("Upgraded the encryption of {} docs", upgraded);
+        }
+
+        let migrated = password_ledger
+            .migrate_entries()
+            .map_err(|e| e.to_string())?;
+
+        if migrated > 0 {
+            tracing::info!("Migrated {} entries to the current entry model", migrated);
+        }
+
+        purge_trash(&mut password_ledger, self.trash_retention)?;
+
+        Ok(password_ledger)
+    }
+
+    /// Core dumps are off while any vault is open, they would hold its keys and entries
+    fn update_core_dumps(&self) {
+        if let Err(err) = local_ledger::set_core_dumps(self.vaults.is_empty()) {
+            tracing::warn!("{}", err);
+        }
+    }
+
+    fn registry(&self) -> Result<&VaultRegistry, String> {
+        self.registry.as_ref().map_err(|err| err.clone())
+    }
+
+    fn registry_mut(&mut self) -> Result<&mut VaultRegistry, String> {
+        self.registry.as_mut().map_err(|err| err.clone())
+    }
+
+    fn active_ledger(&self) -> Result<&LocalLedger<VaultEntry>, String> {
+        self.last_used.set(Instant::now());
+        self.active_vault
+            .as_ref()
+            .and_then(|name| self.vaults.get(name))
+            .ok_or("Ledger has not been started".to_string())
+    }
+
+    fn active_ledger_mut(&mut self) -> Result<&mut LocalLedger<VaultEntry>, String> {
+        self.last_used.set(Instant::now());
+        self.active_vault
+            .as_ref()
+            .and_then(|name| self.vaults.get_mut(name))
+            .ok_or("Ledger has not been started".to_string())
+    }
+
+    /// Sets how many days removed entries stay in the trash.  Entries that have been in the trash
+    /// for longer are purged from every open vault right away, and from other vaults once they
+    /// are opened.
+    pub fn set_trash_retention(&mut self, days: u64) -> Result<(), String> {
+        self.trash_retention = Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY));
+
+        for password_ledger in self.vaults.values_mut() {
+            purge_trash(password_ledger, self.trash_retention)?;
+        }
 
         Ok(())
     }
 
     pub fn add_entry(&mut self, entry_name: &str, pw: &str) -> Result<(), String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
-
-        let saved_password = SavedPassword {
-            name: entry_name.to_string(),
-            pw: pw.to_string(),
-        };
+        self.create_entry(
+            entry_name,
+            EntryDetails {
+                pw: pw.into(),
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn create_entry(&mut self, entry_name: &str, details: EntryDetails) -> Result<(), String> {
+        let saved_password = SavedPassword::new(entry_name, details);
+
+        self.create_vault_entry(entry_name, VaultEntry::Login(saved_password))
+    }
+
+    /// Stores a new entry of any kind
+    pub fn create_vault_entry(
+        &mut self,
+        entry_name: &str,
+        mut entry: VaultEntry,
+    ) -> Result<(), String> {
+        let password_ledger = self.active_ledger_mut()?;
+
+        entry.set_folder(normalize_folder(entry.folder())?);
+        entry.validate()?;
+        entry.stamp(None);
+
+        if let VaultEntry::Login(login) = &mut entry {
+            login.name = entry_name.to_string();
+        }
 
         let _ = password_ledger
-            .create(saved_password, entry_name)
+            .create(entry, entry_name)
             .map_err(|e| e.to_string())?;
 
         Ok(())
     }
 
+    /// Replaces the password of `entry_name`, keeping everything else about the entry
     pub fn update_entry(&mut self, entry_name: &str, pw: &str) -> Result<(), String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
-
-        let saved_password = SavedPassword {
-            name: entry_name.to_string(),
-            pw: pw.to_string(),
+        let details = EntryDetails {
+            pw: pw.into(),
+            ..self.read_entry_details(entry_name)?.details()
         };
 
+        self.edit_entry(entry_name, details)
+    }
+
+    /// Replaces every editable field of the login `entry_name` with `details`
+    pub fn edit_entry(&mut self, entry_name: &str, details: EntryDetails) -> Result<(), String> {
+        let mut saved_password = self.read_entry_details(entry_name)?;
+
+        saved_password.edit(details);
+
+        self.edit_vault_entry(entry_name, VaultEntry::Login(saved_password))
+    }
+
+    /// Replaces the entry `entry_name` with `entry`.  The kind of an entry can not be changed.
+    pub fn edit_vault_entry(
+        &mut self,
+        entry_name: &str,
+        mut entry: VaultEntry,
+    ) -> Result<(), String> {
+        let stored_entry = self.read_vault_entry(entry_name)?;
+
+        if stored_entry.kind() != entry.kind() {
+            return Err("Entry kind cannot be changed".to_string());
+        }
+
+        entry.set_folder(normalize_folder(entry.folder())?);
+        entry.validate()?;
+        entry.stamp(stored_entry.created_at());
+
+        if let VaultEntry::Login(login) = &mut entry {
+            login.name = entry_name.to_string();
+        }
+
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger
+            .update(entry_name, entry)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Reads the login `entry_name`.  Fails for entries of other kinds.
+    pub fn read_entry_details(&mut self, entry_name: &str) -> Result<SavedPassword, String> {
+        match self.read_vault_entry(entry_name)? {
+            VaultEntry::Login(saved_password) => Ok(saved_password),
+            _ => Err("Entry is not a login".to_string()),
+        }
+    }
+
+    pub fn read_vault_entry(&mut self, entry_name: &str) -> Result<VaultEntry, String> {
+        let password_ledger = self.active_ledger_mut()?;
+
         password_ledger
-            .update(entry_name, saved_password)
+            .read_by_entry_name(entry_name)
+            .cloned()
             .map_err(|e| e.to_string())
     }
 
+    /// Moves the entry to the trash, see `restore_entry`
     pub fn remove_entry(&mut self, entry_name: &str) -> Result<(), String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger.trash(entry_name).map_err(|e| e.to_string())
+    }
+
+    pub fn list_trash(&self) -> Result<Vec<TrashedEntry>, String> {
+        let password_ledger = self.active_ledger()?;
+
+        password_ledger.list_trash().map_err(|e| e.to_string())
+    }
+
+    /// Takes the entry back out of the trash
+    pub fn restore_entry(&mut self, entry_name: &str) -> Result<(), String> {
+        let password_ledger = self.active_ledger_mut()?;
 
         password_ledger
-            .remove(entry_name)
+            .restore_from_trash(entry_name)
             .map_err(|e| e.to_string())
     }
 
-    pub fn list_entry_meta_data(&self) -> Result<Vec<EntryMetaData>, String> {
-        let password_ledger = self
-            .ledger
-            .as_ref()
-            .ok_or("Ledger has not been started".to_string())?;
-        let entries_with_conflict: Vec<_> = password_ledger
+    /// Deletes every entry in the trash for good.  Returns the number of entries deleted.
+    pub fn empty_trash(&mut self) -> Result<usize, String> {
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger
+            .purge_trash(Duration::ZERO)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Public key of the active vault, for other users to share entries with it
+    pub fn public_key(&self) -> Result<String, String> {
+        let password_ledger = self.active_ledger()?;
+
+        password_ledger.public_key().map_err(|e| e.to_string())
+    }
+
+    /// Writes the entry `entry_name` to `path`, encrypted to `public_keys`, for their holders to
+    /// import into their own vaults
+    pub fn share_entry(
+        &mut self,
+        entry_name: &str,
+        public_keys: &[String],
+        path: &Path,
+    ) -> Result<(), String> {
+        let password_ledger = self.active_ledger_mut()?;
+        let shared = password_ledger
+            .share_entry(entry_name, public_keys)
+            .map_err(|e| e.to_string())?;
+
+        std::fs::write(path, shared).map_err(|e| format!("Failed to write shared entry: {}", e))
+    }
+
+    /// Imports an entry shared with the active vault from `path`.  Returns the name of the entry.
+    pub fn import_shared_entry(&mut self, path: &Path) -> Result<String, String> {
+        let shared =
+            std::fs::read(path).map_err(|e| format!("Failed to read shared entry: {}", e))?;
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger
+            .import_shared_entry(&shared)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Checks the active vault for damaged docs, without changing anything
+    pub fn verify_vault(&self) -> Result<Vec<IntegrityProblem>, String> {
+        let password_ledger = self.active_ledger()?;
+
+        password_ledger.verify().map_err(|e| e.to_string())
+    }
+
+    /// Fixes or quarantines the damaged docs of the active vault, see `LocalLedger::repair`
+    pub fn repair_vault(&mut self) -> Result<Vec<RepairedProblem>, String> {
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger.repair().map_err(|e| e.to_string())
+    }
+
+    pub fn change_password(&mut self, old_pw: &str, new_pw: &str) -> Result<(), String> {
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger
+            .change_password(old_pw, new_pw.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Makes the keyfile at `keyfile` required to open the active vault, on top of its master
+    /// password.  Without a keyfile, the vault goes back to the master password alone.
+    pub fn set_keyfile(&mut self, master_pw: &str, keyfile: Option<&Path>) -> Result<(), String> {
+        let keyfile_contents = keyfile.map(read_keyfile).transpose()?;
+        let password_ledger = self.active_ledger_mut()?;
+
+        password_ledger
+            .set_keyfile(master_pw, keyfile_contents.as_deref())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Moves `entry_name` to `folder`.  Like any other edit, the move is synced by `merge`.
+    pub fn move_entry(&mut self, entry_name: &str, folder: &str) -> Result<(), String> {
+        let mut entry = self.read_vault_entry(entry_name)?;
+
+        entry.set_folder(folder.to_string());
+
+        self.edit_vault_entry(entry_name, entry)
+    }
+
+    /// Renames the folder `folder`, subfolders included, by moving every entry in it.  Entries
+    /// filed under the old name on another device stay there until they are moved as well.
+    /// Returns the number of entries that were moved.
+    pub fn rename_folder(&mut self, folder: &str, new_folder: &str) -> Result<usize, String> {
+        let folder = normalize_folder(folder)?;
+        let new_folder = normalize_folder(new_folder)?;
+
+        if folder.is_empty() {
+            return Err("The top level folder cannot be renamed".to_string());
+        }
+
+        let mut moved = 0;
+
+        for (entry_name, _) in self.list_entry_names()? {
+            let entry = self.read_vault_entry(&entry_name)?;
+
+            if !is_in_folder(entry.folder(), &folder) {
+                continue;
+            }
+
+            let subfolder = &entry.folder()[folder.len()..];
+            let moved_folder = format!("{}{}", new_folder, subfolder);
+
+            self.move_entry(&entry_name, &moved_folder)?;
+            moved += 1;
+        }
+
+        if moved == 0 {
+            return Err("Folder not found.".to_string());
+        }
+
+        Ok(moved)
+    }
+
+    /// Lists every folder that has entries in it, along with its parent folders.  Sorted, so
+    /// subfolders come right after their parent.
+    pub fn list_folders(&mut self) -> Result<Vec<String>, String> {
+        let mut folders = std::collections::BTreeSet::new();
+
+        for (entry_name, _) in self.list_entry_names()? {
+            let entry = self.read_vault_entry(&entry_name)?;
+            let mut folder = entry.folder();
+
+            while !folder.is_empty() {
+                folders.insert(folder.to_string());
+                folder = folder.rsplit_once('/').map_or("", |(parent, _)| parent);
+            }
+        }
+
+        Ok(folders.into_iter().collect())
+    }
+
+    pub fn list_entry_meta_data(
+        &mut self,
+        filter: &EntryFilter,
+    ) -> Result<Vec<EntryMetaData>, String> {
+        let folder = filter.folder.as_deref().map(normalize_folder).transpose()?;
+        let tag = filter.tag.as_deref().map(|tag| tag.trim().to_lowercase());
+        let mut entries = vec![];
+
+        for (label, has_conflict) in self.list_entry_names()? {
+            let entry = self.read_vault_entry(&label)?;
+
+            if let Some(folder) = &folder {
+                if !is_in_folder(entry.folder(), folder) {
+                    continue;
+                }
+            }
+
+            if let Some(tag) = &tag {
+                if !entry
+                    .tags()
+                    .iter()
+                    .any(|entry_tag| entry_tag.trim().to_lowercase() == *tag)
+                {
+                    continue;
+                }
+            }
+
+            entries.push(EntryMetaData {
+                label,
+                has_conflict,
+                kind: entry.kind(),
+                folder: entry.folder().to_string(),
+                tags: entry.tags().to_vec(),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    /// Names of all entries, along with whether they have conflicts
+    fn list_entry_names(&self) -> Result<Vec<(String, bool)>, String> {
+        let password_ledger = self.active_ledger()?;
+        let entries_with_conflict = password_ledger
             .list_entries_with_conflicts()
-            .map(|entries| {
-                entries
-                    .into_iter()
-                    .map(|label| EntryMetaData {
-                        label,
-                        has_conflict: true,
-                    })
-                    .collect()
-            })
             .map_err(|e| e.to_string())?;
-        let entries: Vec<_> = password_ledger
+        let entries = password_ledger
             .list_entry_labels()
-            .map(|entries| {
-                entries
-                    .into_iter()
-                    .map(|label| EntryMetaData {
-                        label,
-                        has_conflict: false,
-                    })
-                    .collect()
-            })
             .map_err(|e| e.to_string())?;
 
         Ok(entries
             .into_iter()
-            .chain(entries_with_conflict.into_iter())
+            .map(|label| (label, false))
+            .chain(entries_with_conflict.into_iter().map(|label| (label, true)))
             .collect())
     }
 
-    pub fn get_pw(&mut self, entry_name: &str) -> Result<String, String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
+    pub fn search_entries(&self, query: &str) -> Result<Vec<SearchHit>, String> {
+        let password_ledger = self.active_ledger()?;
+
+        Ok(password_ledger.search(query))
+    }
+
+    pub fn get_pw(&mut self, entry_name: &str) -> Result<SensitiveString, String> {
+        let password_ledger = self.active_ledger_mut()?;
 
         tracing::info!("entry_name: {}", entry_name);
 
-        password_ledger
+        let entry = password_ledger
             .read_by_entry_name(entry_name)
-            .map(|saved_pw| saved_pw.pw.clone())
-            .map_err(|e| e.to_string())
+            .map_err(|e| e.to_string())?;
+
+        entry
+            .secret()
+            .map(SensitiveString::from)
+            .ok_or("Entry has no password".to_string())
     }
 
     pub fn get_ledger_dir(&self) -> Result<PathBuf, String> {
-        let password_ledger = self
-            .ledger
-            .as_ref()
-            .ok_or("Ledger has not been started".to_string())?;
+        let password_ledger = self.active_ledger()?;
 
         password_ledger.get_ledger_dir().map_err(|e| e.to_string())
     }
 
     pub fn get_doc_dump(&self) -> Result<LedgerDump, String> {
-        let password_ledger = self
-            .ledger
-            .as_ref()
-            .ok_or("Ledger has not been started".to_string())?;
+        let password_ledger = self.active_ledger()?;
 
         password_ledger.doc_dump().map_err(|e| e.to_string())
     }
@@ -154,49 +776,159 @@ impl PasswordLedgerHandler {
     where
         S: Stream<Item = Result<Value, Box<dyn std::error::Error>>> + Unpin + Send,
     {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
+        let password_ledger = self.active_ledger_mut()?;
 
         password_ledger.merge(s).await.map_err(|e| e.to_string())?;
 
         Ok(())
     }
 
-    /// Get conf tuple (original_password, remote_password)
-    pub fn get_conf_pair(&mut self, entry_name: &str) -> Result<(String, String), String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
-        let conf_doc = password_ledger
+    /// Works out what merging the docs of `s` into the active vault would do.  The docs are kept
+    /// until they are merged with `apply_pending_merge`, or thrown away.
+    pub async fn preview_merge<S>(&mut self, mut s: S) -> Result<MergePreview, String>
+    where
+        S: Stream<Item = Result<Value, Box<dyn std::error::Error>>> + Unpin + Send,
+    {
+        let vault_name = self.active_vault.clone().ok_or("No vault is open.")?;
+        let mut docs = vec![];
+
+        while let Some(item) = s.next().await {
+            docs.push(item.map_err(|e| e.to_string())?);
+        }
+
+        let password_ledger = self.active_ledger_mut()?;
+        let preview = password_ledger
+            .merge_preview(tokio_stream::iter(docs.clone().into_iter().map(Ok)))
+            .await
+            .map_err(|e| e.to_string())?;
+
+        self.pending_merge = Some((vault_name, docs));
+
+        Ok(preview)
+    }
+
+    /// Merges the docs kept by `preview_merge` into the vault they were previewed against
+    pub async fn apply_pending_merge(&mut self) -> Result<(), String> {
+        let (vault_name, _docs) = self
+            .pending_merge
+            .as_ref()
+            .ok_or("There are no pulled changes to merge.")?;
+
+        // The changes are kept until their vault is active again
+        if self.active_vault.as_ref() != Some(vault_name) {
+            return Err(format!("The pulled changes are for {}.", vault_name));
+        }
+
+        let docs = self
+            .pending_merge
+            .take()
+            .map(|(_vault_name, docs)| docs)
+            .unwrap_or_default();
+
+        self.merge(tokio_stream::iter(docs.into_iter().map(Ok)))
+            .await
+    }
+
+    /// Throws away the docs kept by `preview_merge`
+    pub fn discard_pending_merge(&mut self) {
+        self.pending_merge = None;
+    }
+
+    /// Get conf tuple (original_password, [(remote_rev, remote_password)]).  There is one remote
+    /// password per conflicting copy.
+    pub fn get_conf_pair(
+        &mut self,
+        entry_name: &str,
+    ) -> Result<(SensitiveString, Vec<(String, SensitiveString)>), String> {
+        let password_ledger = self.active_ledger_mut()?;
+        let conf_docs = password_ledger
             .get_conf(entry_name)
             .map_err(|e| e.to_string())?;
-        let conf_data = conf_doc.read_data().map_err(|e| e.to_string())?;
+        let remote_pws = conf_docs
+            .iter()
+            .map(|conf_doc| {
+                let conf_data = conf_doc.read_data().map_err(|e| e.to_string())?;
+
+                Ok((
+                    conf_doc.rev().to_owned(),
+                    SensitiveString::from(conf_data.secret().unwrap_or_default()),
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
         let original_doc = password_ledger
             .read_by_entry_name(entry_name)
             .map_err(|e| e.to_string())?;
-        let pair = (original_doc.pw.clone(), conf_data.pw.to_string());
 
-        Ok(pair)
+        Ok((
+            SensitiveString::from(original_doc.secret().unwrap_or_default()),
+            remote_pws,
+        ))
     }
 
-    pub fn resolve(&mut self, entry_name: &str, keep_original: bool) -> Result<(), String> {
-        let password_ledger = self
-            .ledger
-            .as_mut()
-            .ok_or("Ledger has not been started".to_string())?;
+    /// Settles the conflicts of `entry_name`.  Unless the original is kept, the conflicting copy
+    /// at `remote_rev` wins, or the first one if no rev is given.
+    pub fn resolve(
+        &mut self,
+        entry_name: &str,
+        keep_original: bool,
+        remote_rev: Option<String>,
+    ) -> Result<(), String> {
+        let password_ledger = self.active_ledger_mut()?;
+        let resolution = match (keep_original, remote_rev) {
+            (true, _) => Resolution::Original,
+            (false, Some(rev)) => Resolution::Sibling(rev),
+            (false, None) => {
+                let conf_docs = password_ledger
+                    .get_conf(entry_name)
+                    .map_err(|e| e.to_string())?;
+                let conf_doc = conf_docs
+                    .first()
+                    .ok_or("Entry has no conflicts.".to_string())?;
+
+                Resolution::Sibling(conf_doc.rev().to_owned())
+            }
+        };
 
         password_ledger
-            .resolve(entry_name, keep_original)
+            .resolve(entry_name, resolution)
             .map_err(|e| e.to_string())
     }
 }
 
+/// Builds the key a vault is unlocked with from its master password and keyfile
+fn composite_key(master_pw: &str, keyfile: Option<&Path>) -> Result<CompositeKey, String> {
+    let composite_key = CompositeKey::new(master_pw.to_string());
+
+    match keyfile {
+        Some(keyfile) => Ok(composite_key.with_keyfile(&read_keyfile(keyfile)?)),
+        None => Ok(composite_key),
+    }
+}
+
+fn read_keyfile(keyfile: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(keyfile).map_err(|e| format!("Failed to read keyfile: {}", e))
+}
+
+/// Deletes the entries that have been in the trash longer than `retention`
+fn purge_trash(
+    password_ledger: &mut LocalLedger<VaultEntry>,
+    retention: Duration,
+) -> Result<(), String> {
+    let purged = password_ledger
+        .purge_trash(retention)
+        .map_err(|e| e.to_string())?;
+
+    if purged > 0 {
+        tracing::info!("Purged {} entries from the trash", purged);
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vault_registry::VaultRegistry;
     use crate::{LandStrider, LandStriderConfig};
     use land_strider::startup::land_strider_app;
     use utility::generate_id;
@@ -232,4 +964,226 @@ mod tests {
 
         pl.merge(ps).await.unwrap();
     }
+
+    #[test]
+    fn should_lock_when_idle() {
+        let root = std::env::temp_dir().join(format!("fizzgig-test-{}", generate_id()));
+        let mut pl = PasswordLedgerHandler::with_registry(VaultRegistry::load(root.clone()));
+
+        pl.start("password").unwrap();
+        pl.add_entry("bank", "test1234").unwrap();
+
+        assert!(!pl.lock_if_idle());
+
+        pl.set_idle_timeout(Some(Duration::ZERO));
+
+        assert!(pl.lock_if_idle());
+        assert!(pl.is_locked());
+        assert!(pl.get_pw("bank").is_err());
+        assert!(!pl.lock());
+
+        pl.set_idle_timeout(None);
+        pl.start("password").unwrap();
+
+        assert!(!pl.lock_if_idle());
+        assert_eq!(pl.get_pw("bank").unwrap(), "test1234");
+        assert!(pl.lock());
+        assert!(pl.get_pw("bank").is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_keep_vaults_apart() {
+        let root = std::env::temp_dir().join(format!("fizzgig-test-{}", generate_id()));
+        let mut pl = PasswordLedgerHandler::with_registry(VaultRegistry::load(root.clone()));
+        let usb_path = root.join("usb").join("vault");
+
+        pl.start("password").unwrap();
+        pl.add_entry("bank", "test1234").unwrap();
+        pl.create_vault("Work", "work_password", None, None)
+            .unwrap();
+        pl.create_vault("USB", "usb_password", Some(&usb_path), None)
+            .unwrap();
+
+        assert!(pl
+            .create_vault("Work", "work_password", None, None)
+            .is_err());
+
+        let root_files = std::fs::read_dir(&root).unwrap().count();
+
+        assert!(pl
+            .create_vault(" Work ", "work_password", None, None)
+            .is_err());
+        assert!(pl.create_vault(" ", "password", None, None).is_err());
+        // Neither left a vault behind
+        assert_eq!(std::fs::read_dir(&root).unwrap().count(), root_files);
+        assert!(pl.list_entry_names().unwrap().is_empty());
+
+        pl.open_vault(DEFAULT_VAULT_NAME, "password", None).unwrap();
+        pl.copy_entry_to_vault("bank", "Work").unwrap();
+        pl.move_entry_to_vault("bank", "USB").unwrap();
+
+        assert!(pl.list_entry_names().unwrap().is_empty());
+        assert_eq!(pl.list_trash().unwrap()[0].entry_name, "bank");
+
+        pl.close_vault("Work").unwrap();
+
+        // Vaults keep their own master password
+        assert!(pl.open_vault("Work", "password", None).is_err());
+
+        pl.rename_vault("Work", "Office").unwrap();
+        pl.open_vault("Office", "work_password", None).unwrap();
+
+        assert_eq!(pl.get_pw("bank").unwrap(), "test1234");
+
+        pl.open_vault("USB", "usb_password", None).unwrap();
+
+        assert_eq!(pl.get_pw("bank").unwrap(), "test1234");
+
+        // Entries can be shared between vaults that do not know each other's password
+        let shared_path = root.join("bank.age");
+        let usb_public_key = pl.public_key().unwrap();
+
+        pl.open_vault(DEFAULT_VAULT_NAME, "password", None).unwrap();
+        pl.add_entry("shop", "test5678").unwrap();
+        pl.share_entry("shop", &[usb_public_key], &shared_path)
+            .unwrap();
+        pl.open_vault("USB", "usb_password", None).unwrap();
+
+        assert_eq!(pl.import_shared_entry(&shared_path).unwrap(), "shop");
+        assert_eq!(pl.get_pw("shop").unwrap(), "test5678");
+
+        let keyfile_path = root.join("usb.key");
+
+        std::fs::write(&keyfile_path, local_ledger::generate_keyfile()).unwrap();
+        pl.set_keyfile("usb_password", Some(&keyfile_path)).unwrap();
+        pl.close_vault("USB").unwrap();
+
+        // Once added, the vault cannot be opened without the keyfile
+        assert!(pl.open_vault("USB", "usb_password", None).is_err());
+
+        pl.open_vault("USB", "usb_password", Some(&keyfile_path))
+            .unwrap();
+
+        let vaults = pl.list_vaults().unwrap();
+        let names: Vec<&str> = vaults.iter().map(|vault| vault.name.as_str()).collect();
+
+        assert_eq!(names, vec![DEFAULT_VAULT_NAME, "Office", "USB"]);
+        assert!(vaults.iter().all(|vault| vault.is_open));
+        assert_eq!(vaults[2].path, usb_path);
+        assert!(vaults[2].is_active);
+
+        assert!(pl.delete_vault("USB", "usb_password", None).is_err());
+
+        pl.delete_vault("USB", "usb_password", Some(&keyfile_path))
+            .unwrap();
+
+        assert!(!usb_path.exists());
+        assert!(pl.get_pw("bank").is_err());
+        assert_eq!(pl.list_vaults().unwrap().len(), 2);
+
+        // The default vault is still opened on start once it has been renamed
+        pl.rename_vault(DEFAULT_VAULT_NAME, "Personal").unwrap();
+        pl.lock();
+        pl.start("password").unwrap();
+
+        let vaults = pl.list_vaults().unwrap();
+        let names: Vec<&str> = vaults.iter().map(|vault| vault.name.as_str()).collect();
+
+        assert_eq!(names, vec!["Office", "Personal"]);
+        assert!(vaults[1].is_active);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn should_repair_vaults_with_damaged_docs() {
+        let root = std::env::temp_dir().join(format!("fizzgig-test-{}", generate_id()));
+        let mut pl = PasswordLedgerHandler::with_registry(VaultRegistry::load(root.clone()));
+
+        pl.start("password").unwrap();
+        pl.add_entry("bank", "test1234").unwrap();
+
+        let ledger_dir = pl.active_ledger().unwrap().get_ledger_dir().unwrap();
+        let bank_path = std::fs::read_dir(&ledger_dir)
+            .unwrap()
+            .map(|dir_entry| dir_entry.unwrap().path())
+            .find(|path| {
+                let file_name = path.file_name().unwrap().to_str().unwrap();
+
+                file_name != "META_DOC.json" && file_name != "INDEX_DOC.json"
+            })
+            .unwrap();
+
+        pl.add_entry("shop", "test5678").unwrap();
+        pl.lock();
+
+        // Its payload no longer decrypts
+        let mut bank_doc: Value =
+            serde_json::from_slice(&std::fs::read(&bank_path).unwrap()).unwrap();
+
+        bank_doc["encrypted_data"] = Value::String("RlpHMWRhbWFnZWQ=".to_owned());
+        std::fs::write(&bank_path, serde_json::to_vec(&bank_doc).unwrap()).unwrap();
+
+        // The damaged doc does not keep the vault from opening
+        pl.start("password").unwrap();
+
+        assert_eq!(pl.verify_vault().unwrap().len(), 1);
+        assert_eq!(pl.repair_vault().unwrap().len(), 1);
+        assert!(pl.verify_vault().unwrap().is_empty());
+        assert_eq!(
+            pl.list_entry_names().unwrap(),
+            vec![("shop".to_owned(), false)]
+        );
+        assert_eq!(pl.get_pw("shop").unwrap(), "test5678");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_preview_merges_before_applying_them() {
+        let root = std::env::temp_dir().join(format!("fizzgig-test-{}", generate_id()));
+        let mut pl = PasswordLedgerHandler::with_registry(VaultRegistry::load(root.clone()));
+        let pulled_docs = |pl: &PasswordLedgerHandler| {
+            let ledger_dir = pl.active_ledger().unwrap().get_ledger_dir().unwrap();
+            let docs: Vec<Value> = std::fs::read_dir(ledger_dir)
+                .unwrap()
+                .map(|dir_entry| dir_entry.unwrap().path())
+                .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+                .map(|path| serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap())
+                .collect();
+
+            tokio_stream::iter(docs.into_iter().map(Ok))
+        };
+
+        pl.start("password").unwrap();
+        pl.add_entry("bank", "test1234").unwrap();
+
+        let preview = pl.preview_merge(pulled_docs(&pl)).await.unwrap();
+
+        assert_eq!(preview.unchanged_entries, vec!["bank"]);
+        assert!(preview.new_entries.is_empty());
+
+        // They wait for their vault while another one is active
+        pl.create_vault("Work", "work_password", None, None)
+            .unwrap();
+
+        assert!(pl.apply_pending_merge().await.is_err());
+
+        pl.open_vault(DEFAULT_VAULT_NAME, "password", None).unwrap();
+        pl.apply_pending_merge().await.unwrap();
+
+        assert!(pl.apply_pending_merge().await.is_err());
+
+        // Pulled changes do not outlive the vault they were previewed against
+        pl.preview_merge(pulled_docs(&pl)).await.unwrap();
+        pl.lock();
+        pl.start("password").unwrap();
+
+        assert!(pl.apply_pending_merge().await.is_err());
+        assert_eq!(pl.get_pw("bank").unwrap(), "test1234");
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
 }
@@ -0,0 +1,253 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use local_ledger::{DocumentStoreRef, FileSystemStore};
+use utility::generate_id;
+
+/// Name of the vault every installation starts out with
+pub const DEFAULT_VAULT_NAME: &str = "Default";
+
+/// Ledger name of the default vault.  It predates the registry, so it is not an opaque id like
+/// the ledger names of other vaults.
+const DEFAULT_VAULT_LABEL: &str = "Password_Ledger";
+
+/// File, in the vault root, listing the vaults of the installation
+const REGISTRY_FILE_NAME: &str = "vaults.json";
+
+/// Where a vault is stored.  The vault is the ledger `label` of a store rooted at `root`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VaultLocation {
+    pub root: PathBuf,
+    pub label: String,
+}
+
+impl VaultLocation {
+    /// A vault kept in the directory `path`, which does not have to exist yet
+    pub fn at_path(path: &Path) -> Result<Self, String> {
+        let root = path.parent().ok_or("Vault path has no parent directory")?;
+        let label = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or("Vault path must end in a directory name")?;
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            label: label.to_string(),
+        })
+    }
+
+    pub fn store(&self) -> DocumentStoreRef {
+        Arc::new(FileSystemStore::new(self.root.clone()))
+    }
+
+    /// Directory the entries of the vault live in
+    pub fn path(&self) -> PathBuf {
+        self.root.join(&self.label)
+    }
+}
+
+/// The vaults of an installation, by name.  Names are only known to this installation, renaming
+/// a vault does not touch its files.
+#[derive(Debug)]
+pub struct VaultRegistry {
+    /// Where the registry and vaults without a path of their own are kept
+    root: PathBuf,
+    vaults: BTreeMap<String, VaultLocation>,
+}
+
+impl VaultRegistry {
+    /// Loads the registry kept in `root`.  An installation without one gets the default vault.
+    pub fn load(root: PathBuf) -> Result<Self, String> {
+        let registry_path = root.join(REGISTRY_FILE_NAME);
+
+        if !registry_path.exists() {
+            let mut registry = Self {
+                root,
+                vaults: BTreeMap::new(),
+            };
+            let default_location = registry.default_location();
+
+            registry
+                .vaults
+                .insert(DEFAULT_VAULT_NAME.to_string(), default_location);
+
+            return Ok(registry);
+        }
+
+        let contents = std::fs::read(&registry_path)
+            .map_err(|e| format!("Failed to read vault registry: {}", e))?;
+        let vaults = serde_json::from_slice(&contents)
+            .map_err(|e| format!("Failed to parse vault registry: {}", e))?;
+
+        Ok(Self { root, vaults })
+    }
+
+    /// Loads the registry kept in `~/.fizzgig`
+    pub fn load_home() -> Result<Self, String> {
+        let root = dirs::home_dir()
+            .map(|home_dir| home_dir.join(".fizzgig"))
+            .ok_or("Failed to get home directory")?;
+
+        Self::load(root)
+    }
+
+    pub fn vaults(&self) -> &BTreeMap<String, VaultLocation> {
+        &self.vaults
+    }
+
+    /// Returns where the vault `name` is stored.  The default vault can always be found, even
+    /// after it has been deleted, so opening it creates it again.  Once it has been renamed it is
+    /// only found under its new name.
+    pub fn location(&self, name: &str) -> Option<VaultLocation> {
+        match self.vaults.get(name) {
+            Some(location) => Some(location.clone()),
+            None if name == DEFAULT_VAULT_NAME && self.default_vault_name().is_none() => {
+                Some(self.default_location())
+            }
+            None => None,
+        }
+    }
+
+    /// Name the default vault is registered under, if it is
+    pub fn default_vault_name(&self) -> Option<&str> {
+        let default_location = self.default_location();
+
+        self.vaults
+            .iter()
+            .find(|(_name, location)| **location == default_location)
+            .map(|(name, _location)| name.as_str())
+    }
+
+    /// Picks the location of a new vault.  Vaults without a path are kept in the registry root.
+    pub fn new_location(&self, path: Option<&Path>) -> Result<VaultLocation, String> {
+        match path {
+            Some(path) => VaultLocation::at_path(path),
+            None => Ok(VaultLocation {
+                root: self.root.clone(),
+                label: generate_id(),
+            }),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, location: VaultLocation) -> Result<(), String> {
+        let name = validate_vault_name(name)?;
+
+        if self.vaults.contains_key(name) {
+            return Err("Vault names must be unique".to_string());
+        }
+
+        self.vaults.insert(name.to_string(), location);
+        self.save()
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), String> {
+        let new_name = validate_vault_name(new_name)?;
+
+        if self.vaults.contains_key(new_name) {
+            return Err("Vault names must be unique".to_string());
+        }
+
+        let location = self.vaults.remove(name).ok_or("Vault not found.")?;
+
+        self.vaults.insert(new_name.to_string(), location);
+        self.save()
+    }
+
+    /// Forgets the vault `name`.  Its files are left alone.
+    pub fn remove(&mut self, name: &str) -> Result<VaultLocation, String> {
+        let location = self.vaults.remove(name).ok_or("Vault not found.")?;
+
+        self.save()?;
+
+        Ok(location)
+    }
+
+    fn default_location(&self) -> VaultLocation {
+        VaultLocation {
+            root: self.root.clone(),
+            label: DEFAULT_VAULT_LABEL.to_string(),
+        }
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_vec_pretty(&self.vaults)
+            .map_err(|e| format!("Failed to serialize vault registry: {}", e))?;
+
+        std::fs::create_dir_all(&self.root)
+            .and_then(|_| std::fs::write(self.root.join(REGISTRY_FILE_NAME), contents))
+            .map_err(|e| format!("Failed to save vault registry: {}", e))
+    }
+}
+
+/// Returns `name` trimmed, if it can be used as the name of a vault
+pub fn validate_vault_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+
+    if name.is_empty() {
+        return Err("Vault name cannot be empty".to_string());
+    }
+
+    if name.chars().any(char::is_control) {
+        return Err("Vault name cannot contain control characters".to_string());
+    }
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_keep_vaults_across_loads() {
+        let root = std::env::temp_dir().join(format!("fizzgig-test-{}", generate_id()));
+        let mut registry = VaultRegistry::load(root.clone()).unwrap();
+
+        assert_eq!(
+            registry.vaults().keys().collect::<Vec<_>>(),
+            vec![DEFAULT_VAULT_NAME]
+        );
+
+        let work_location = registry.new_location(None).unwrap();
+        let usb_location = registry
+            .new_location(Some(&root.join("usb").join("vault")))
+            .unwrap();
+
+        registry.add("Work", work_location.clone()).unwrap();
+        registry.add(" USB ", usb_location.clone()).unwrap();
+
+        assert!(registry.add("Work", work_location.clone()).is_err());
+        assert!(registry.add("", work_location.clone()).is_err());
+
+        registry.rename("Work", "Office").unwrap();
+        registry.rename(DEFAULT_VAULT_NAME, "Personal").unwrap();
+
+        // The renamed default vault is not found under its old name as well
+        assert!(registry.location(DEFAULT_VAULT_NAME).is_none());
+        assert_eq!(registry.default_vault_name(), Some("Personal"));
+
+        registry.remove("Personal").unwrap();
+
+        let registry = VaultRegistry::load(root.clone()).unwrap();
+
+        assert_eq!(
+            registry.vaults().keys().collect::<Vec<_>>(),
+            vec!["Office", "USB"]
+        );
+        assert_eq!(registry.location("Office"), Some(work_location));
+        assert_eq!(
+            registry.location("USB").unwrap().path(),
+            root.join("usb").join("vault")
+        );
+        assert_eq!(
+            registry.location(DEFAULT_VAULT_NAME).unwrap().label,
+            DEFAULT_VAULT_LABEL
+        );
+        assert!(registry.location("Work").is_none());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
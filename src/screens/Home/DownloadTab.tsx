@@ -1,10 +1,9 @@
 import { Ok, Err, Result } from "ts-results-intraloop-fork";
 import { invoke } from "@tauri-apps/api/tauri";
-import { createSignal, Show } from "solid-js";
+import { createSignal, For, Show } from "solid-js";
 import styles from "./home.module.css";
 import {
   createLazyPwLoader,
-  err,
   set_err,
   set_sync_in_progress,
   sync_in_progress,
@@ -12,9 +11,24 @@ import {
 
 const { load: load_pw_data } = createLazyPwLoader();
 
+/** What downloading would change, see `MergePreview` on the Rust side */
+type MergePreview = {
+  new_entries: string[];
+  updated_entries: string[];
+  unchanged_entries: string[];
+  conflicting_entries: string[];
+  meta_doc:
+    | "missing"
+    | "unchanged"
+    | "updated"
+    | "conflict"
+    | "vault_key_replaced";
+};
+
 export function DownloadTab() {
   const [temp_pw, set_temp_pw] = createSignal("");
   const [pin, set_pin] = createSignal("");
+  const [preview, set_preview] = createSignal<MergePreview | null>(null);
 
   return (
     <>
@@ -23,7 +37,65 @@ export function DownloadTab() {
           Sync in progress. One moment please.
         </p>
       </Show>
-      <Show when={!sync_in_progress()}>
+      <Show when={sync_in_progress() ? null : preview()}>
+        {(merge_preview) => (
+          <div class={styles.pull_container}>
+            <p>Review the downloaded changes</p>
+            <Show when={merge_preview().meta_doc === "conflict"}>
+              <p>
+                These changes come from a vault that was changed on both
+                devices and can not be applied.
+              </p>
+            </Show>
+            <Show when={merge_preview().meta_doc === "vault_key_replaced"}>
+              <p>
+                The password of this vault was changed on the other device.
+                After applying, unlock the vault with the new password and
+                download again.
+              </p>
+            </Show>
+            <PreviewEntries
+              title="New"
+              entries={merge_preview().new_entries}
+            />
+            <PreviewEntries
+              title="Updated"
+              entries={merge_preview().updated_entries}
+            />
+            <PreviewEntries
+              title="Conflicting"
+              entries={merge_preview().conflicting_entries}
+            />
+            <p>{merge_preview().unchanged_entries.length} unchanged</p>
+            <div style={styles.item_buttons}>
+              <button
+                disabled={merge_preview().meta_doc === "conflict"}
+                onClick={async () => {
+                  set_sync_in_progress(true);
+                  const apply_result = await apply_pull();
+                  set_sync_in_progress(false);
+                  set_preview(null);
+
+                  if (apply_result.err) {
+                    return set_err(apply_result.val);
+                  }
+                }}
+              >
+                Apply
+              </button>
+              <button
+                onClick={async () => {
+                  await invoke("discard_pull");
+                  set_preview(null);
+                }}
+              >
+                Cancel
+              </button>
+            </div>
+          </div>
+        )}
+      </Show>
+      <Show when={!sync_in_progress() && !preview()}>
         <div class={styles.pull_container}>
           <p>Download from sync server</p>
           <div class={styles.push_temp_pw_container}>
@@ -51,6 +123,8 @@ export function DownloadTab() {
                 if (pull_result.err) {
                   return set_err(pull_result.val);
                 }
+
+                set_preview(pull_result.val);
               }}
             >
               Download
@@ -62,18 +136,46 @@ export function DownloadTab() {
   );
 }
 
-async function pull(tempPw: string, pin: string): Promise<Result<any, string>> {
+function PreviewEntries(props: { title: string; entries: string[] }) {
+  return (
+    <Show when={props.entries.length > 0}>
+      <p>{props.title}</p>
+      <ul>
+        <For each={props.entries}>{(entry) => <li>{entry}</li>}</For>
+      </ul>
+    </Show>
+  );
+}
+
+async function pull(
+  tempPw: string,
+  pin: string
+): Promise<Result<MergePreview, string>> {
+  try {
+    const merge_preview = await invoke<MergePreview>("pull", { tempPw, pin });
+
+    return Ok(merge_preview);
+  } catch (e) {
+    if (typeof e !== "string") {
+      return Err(`An unknown error occurred: ${JSON.stringify(e)}`);
+    }
+
+    return Err(e);
+  }
+}
+
+async function apply_pull(): Promise<Result<null, string>> {
   try {
-    const values = await invoke<any>("pull", { tempPw, pin });
+    await invoke("apply_pull");
 
     load_pw_data();
 
-    return Ok(values);
+    return Ok(null);
   } catch (e) {
-    if (typeof err !== "string") {
+    if (typeof e !== "string") {
       return Err(`An unknown error occurred: ${JSON.stringify(e)}`);
     }
 
-    return Err(err);
+    return Err(e);
   }
 }
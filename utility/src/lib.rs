@@ -1,3 +1,5 @@
+mod sensitive_string;
 mod utility;
 
+pub use crate::sensitive_string::SensitiveString;
 pub use crate::utility::*;
@@ -22,6 +22,7 @@ pub enum LocalLedgerErrorType {
     Default,
     Confict,
     MetaDocConflict,
+    Locked,
 }
 
 #[derive(Debug, Clone, PartialEq, serde::Serialize)]
@@ -54,10 +55,21 @@ impl LocalLedgerError {
         }
     }
 
+    pub fn locked(m: &str) -> Self {
+        LocalLedgerError {
+            message: m.to_string(),
+            err_type: LocalLedgerErrorType::Locked,
+        }
+    }
+
     pub fn is_conflict_err(&self) -> bool {
         self.err_type == LocalLedgerErrorType::Confict
     }
 
+    pub fn is_locked_err(&self) -> bool {
+        self.err_type == LocalLedgerErrorType::Locked
+    }
+
     pub fn err_type(&self) -> &LocalLedgerErrorType {
         &self.err_type
     }